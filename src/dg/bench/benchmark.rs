@@ -13,10 +13,10 @@
 // limitations under the License.
 
 use dg_go::utils::sgf::{self, Sgf, SgfEntry};
+use dg_utils::gzip;
 
-use std::fs::File;
 use std::time::Instant;
-use std::io::{BufRead, BufReader};
+use std::io::BufRead;
 
 pub trait BenchmarkExecutor {
     fn new() -> Self;
@@ -34,12 +34,16 @@ impl<B: BenchmarkExecutor> Benchmark<B> {
         }
     }
 
+    pub fn executor(&self) -> &B {
+        &self.executor
+    }
+
     pub fn evaluate(&mut self, sgf_file: &str) -> f64 {
         let start_time = Instant::now();
         let mut count = 0;
 
-        if let Ok(f) = File::open(sgf_file) {
-            for line in BufReader::new(&f).lines().map(|x| x.unwrap()) {
+        if let Ok(f) = gzip::open(sgf_file) {
+            for line in f.lines().map(|x| x.unwrap()) {
                 if let Ok(komi) = sgf::get_komi_from_sgf(&line) {
                     for entry in Sgf::new(line.as_bytes(), komi) {
                         if let Ok(entry) = entry {
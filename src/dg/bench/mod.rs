@@ -16,10 +16,12 @@ mod benchmark;
 mod feature;
 mod forward;
 mod mcts;
+mod self_play;
 mod sgf;
 
 pub use self::benchmark::*;
 pub use self::feature::*;
 pub use self::forward::*;
 pub use self::mcts::*;
+pub use self::self_play::*;
 pub use self::sgf::*;
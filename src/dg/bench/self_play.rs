@@ -0,0 +1,122 @@
+// Copyright 2021 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use bench::{Benchmark, BenchmarkExecutor};
+use dg_go::utils::sgf::SgfEntry;
+use dg_go::Point;
+use dg_mcts::options::StandardSearch;
+use dg_mcts::predictors::DefaultPredictor;
+use dg_mcts::pool::Pool;
+use dg_mcts::predict;
+use dg_mcts::time_control::RolloutLimit;
+use dg_mcts::tree;
+use dg_utils::config;
+
+use std::time::{Duration, Instant};
+
+/// The maximum number of moves to play in the benchmark game.
+const MAX_MOVES: usize = 60;
+
+/// Plays a single short game, starting from the first position of the first
+/// game record, with both players using a fixed rollout budget per move and
+/// re-using the search tree between moves like self-play does.
+pub struct SelfPlayBenchmarkExecutor {
+    pool: Pool,
+    num_moves: usize,
+    num_playouts: usize,
+    elapsed: Duration,
+    is_done: bool
+}
+
+impl SelfPlayBenchmarkExecutor {
+    /// Returns the number of moves that were played.
+    pub fn num_moves(&self) -> usize {
+        self.num_moves
+    }
+
+    /// Returns the total number of playouts over all moves.
+    pub fn num_playouts(&self) -> usize {
+        self.num_playouts
+    }
+
+    /// Returns the wall-clock time it took to play the game.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+}
+
+impl BenchmarkExecutor for SelfPlayBenchmarkExecutor {
+    fn new() -> Self {
+        let pool = Pool::new(Box::new(DefaultPredictor::default()));
+
+        Self {
+            pool,
+            num_moves: 0,
+            num_playouts: 0,
+            elapsed: Duration::default(),
+            is_done: false
+        }
+    }
+
+    fn call(&mut self, entry: SgfEntry) -> usize {
+        if self.is_done {
+            return 0;
+        }
+
+        let start_time = Instant::now();
+        let mut board = entry.board;
+        let mut to_move = entry.color;
+        let mut root: Option<tree::Node> = None;
+        let mut pass_count = 0;
+        let mut num_playouts = 0;
+
+        while self.num_moves < MAX_MOVES && pass_count < 2 {
+            // the re-used tree already contains the playouts of earlier
+            // searches, so only count the ones that this search added
+            let starting_count = root.as_ref().map(|root| root.total_count).unwrap_or(0);
+            let (_value, index, tree) = predict(
+                &self.pool,
+                Box::new(StandardSearch::default()),
+                Box::new(RolloutLimit::new(usize::from(*config::NUM_ROLLOUT))),
+                root.take(),
+                &board,
+                to_move
+            ).expect("could not search the benchmark game");
+
+            num_playouts += (tree.total_count - starting_count).max(0) as usize;
+
+            let point = Point::from_packed_parts(index);
+            if point == Point::default() {
+                board.pass(to_move);
+                pass_count += 1;
+            } else {
+                board.place(to_move, point);
+                pass_count = 0;
+            }
+
+            root = tree::Node::forward(tree, index);
+            to_move = to_move.opposite();
+            self.num_moves += 1;
+        }
+
+        self.num_playouts += num_playouts;
+        self.elapsed += start_time.elapsed();
+        self.is_done = true;
+
+        num_playouts
+    }
+}
+
+pub type SelfPlayBenchmark = Benchmark<SelfPlayBenchmarkExecutor>;
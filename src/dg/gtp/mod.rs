@@ -14,16 +14,15 @@
 
 use regex::Regex;
 use std::env;
-use std::fs::File;
-use std::io::{BufRead, BufReader, Read};
+use std::io::{BufRead, Read};
 use std::time::Instant;
 
-use dg_go::utils::score::{Score, StoneStatus};
+use dg_go::utils::score::{GroupStatus, Score, StoneStatus};
 use dg_go::utils::sgf::Sgf;
-use dg_go::{DEFAULT_KOMI, Board, Color, Point};
-use dg_mcts::time_control::{TimeStrategy, RolloutLimit, ByoYomi};
+use dg_go::{Board, Color, Point, recommended_komi};
+use dg_mcts::time_control::{TimeStrategy, RolloutLimit, ByoYomi, GameClock, SuddenDeath};
 use dg_mcts as mcts;
-use dg_utils::config;
+use dg_utils::{config, gzip};
 
 mod ponder_service;
 mod time_settings;
@@ -323,6 +322,11 @@ impl Gtp {
     ///
     /// If the given `color` is not the players whose turn it is according to the
     /// search tree then the tree is fast-forwarded until it is that players turn.
+    /// A one-line status of the search is written to stderr at regular intervals
+    /// while searching (see `genmove_verbose`).
+    ///
+    /// Returns the generated move, which is `Point::default()` for a passing
+    /// move, or `None` if the engine resigned or could not generate a move.
     ///
     /// # Arguments
     ///
@@ -335,31 +339,48 @@ impl Gtp {
         let board = self.history.last().unwrap();
         let result = self.ponder.service(|service, search_tree, p_state| {
             let search_tree = if search_tree.to_move != to_move {
-                // passing moves are not recorded in GTP, so we will just assume
-                // the other player passed once if we are in this situation
+                // the other player did not play a move since our last one, so we
+                // will just assume they passed once if we are in this situation
                 mcts::tree::Node::forward(search_tree, 361)
             } else {
                 Some(search_tree)
             };
 
+            // pass back if the opponent passed in a settled position that we are
+            // winning, in order to end the game gracefully
+            if *config::FRIENDLY_PASS && mcts::should_pass(service.predictor(), &board, to_move) {
+                let search_tree = search_tree.and_then(|tree| mcts::tree::Node::forward(tree, 361));
+                let explain_last_move = "pass, since the opponent passed in a settled position".to_string();
+                let mut other = board.clone();
+
+                other.pass(to_move);
+                return (Some((None, false, explain_last_move)), search_tree, (other, to_move.opposite()));
+            }
+
+            let total_visits = search_tree.as_ref()
+                .map(|tree| tree.total_count)
+                .unwrap_or(0);
             let search_options: Box<dyn TimeStrategy + Sync> =
-                if main_time.is_finite() && byo_yomi_time.is_finite() {
-                    let total_visits = search_tree.as_ref()
-                        .map(|tree| tree.total_count)
-                        .unwrap_or(0);
+                if main_time.is_finite() && byo_yomi_time == 0.0 {
+                    // sudden death, so spread the remaining time over the
+                    // rest of the game
+                    let clock = GameClock::new(main_time, 0.0, board.count(), board);
 
+                    Box::new(SuddenDeath::new(clock, total_visits))
+                } else if main_time.is_finite() && byo_yomi_time.is_finite() {
                     Box::new(ByoYomi::new(board.count(), total_visits, main_time, byo_yomi_time, byo_yomi_periods))
                 } else {
                     Box::new(RolloutLimit::new((*config::NUM_ROLLOUT).into()))
                 };
 
-            let result = mcts::predict(
+            let tie_break = mode.search_strategy().tiebreak();
+            let result = mcts::genmove_verbose(
                 service,
                 mode.search_strategy(),
-                search_options,
                 search_tree,
                 &board,
-                to_move
+                to_move,
+                search_options
             );
 
             if result.is_none() {
@@ -368,26 +389,32 @@ impl Gtp {
 
             // disqualify the `pass` move, and any move that is not in contested territory, if
             // we are doing clean-up and the board is not scorable.
-            let (value, index, mut tree) = result.unwrap();
-            let (value, index) = if mode.is_cleanup() && index == 361 && !board.is_scorable() {
+            let (_, summary, mut tree) = result.unwrap();
+            let summary = if mode.is_cleanup() && summary.index == 361 && !board.is_scorable() {
                 tree.disqualify(361);
 
                 for &index in &board.get_scorable_territory() {
                     tree.disqualify(index.to_packed_index());
                 }
 
-                tree.best(0.0)
+                let (value, index) = tree.best(0.0, tie_break);
+
+                mcts::SearchSummary::new(value, index, &tree, summary.elapsed)
             } else {
-                (value, index)
+                summary
             };
 
-            let explain_last_move = mcts::tree::to_pretty(&tree).to_string();
+            let (value, index) = (summary.value, summary.index);
+            let explain_last_move = format!("{}{}", mcts::tree::to_pretty(&tree, tie_break), summary);
             eprintln!("{}", explain_last_move);
 
             let should_resign = !*config::NO_RESIGN && value.is_finite() && value < 0.1;  // 10% chance of winning
             let index = if should_resign { 361 } else { index };
             let (vertex, tree, other) = if index >= 361 {  // passing move
-                (None, mcts::tree::Node::forward(tree, 361), board.clone())
+                let mut other = board.clone();
+
+                other.pass(to_move);
+                (None, mcts::tree::Node::forward(tree, 361), other)
             } else {
                 let at_point = Point::from_packed_parts(index);
                 let mut other = board.clone();
@@ -406,12 +433,12 @@ impl Gtp {
             if should_resign {
                 success!(id, "resign");
                 None
-            } else if let Some(point) = point {  // passing move
+            } else if let Some(point) = point {
                 success!(id, &format!("{}", Vertex::from(point)));
                 Some(point)
-            } else {
+            } else {  // passing move
                 success!(id, "pass");
-                None
+                Some(Point::default())
             }
         } else if let Ok(None) = result {
             error!(id, "unrecognized error");
@@ -450,9 +477,11 @@ impl Gtp {
 
                 // before doing a greedy walk, traverse the current best path in any search tree
                 // we have computed
-                for index in GreedyPath::new(&search_tree, 8) {
+                for index in GreedyPath::new(&search_tree, 8, ScoringSearch::default().tiebreak()) {
                     if index != 361 {
                         board._place(to_move, Point::from_packed_parts(index));
+                    } else {
+                        board.pass(to_move);
                     }
 
                     to_move = to_move.opposite();
@@ -530,27 +559,34 @@ impl Gtp {
                     let board = self.history.last().unwrap();
 
                     if let Some(at_point) = at_point {
-                        if board.is_valid(color, at_point) {
+                        let legality = board.legality(color, at_point);
+
+                        if legality.is_legal() {
                             let mut other = board.clone();
 
                             other.place(color, at_point);
                             self.ponder.forward(color, Some(at_point));
-                            Some(other)
+                            Ok(other)
                         } else {
-                            None
+                            Err(legality)
                         }
                     } else {
-                        self.ponder.forward(color, None);
+                        let mut other = board.clone();
 
-                        Some(board.clone())
+                        other.pass(color);
+                        self.ponder.forward(color, None);
+                        Ok(other)
                     }
                 };
 
-                if let Some(next_board) = next_board {
-                    self.history.push(next_board);
-                    success!(id, "");
-                } else {
-                    error!(id, "illegal move");
+                match next_board {
+                    Ok(next_board) => {
+                        self.history.push(next_board);
+                        success!(id, "");
+                    },
+                    Err(legality) => {
+                        error!(id, format!("illegal move ({})", legality));
+                    }
                 }
             },
             Command::ListCommands => {
@@ -577,7 +613,12 @@ impl Gtp {
                 if !mode.is_regression() {
                     if let Some(at_point) = at_point {
                         let mut board = self.history.last().unwrap().clone();
-                        board.place(color, at_point);
+
+                        if at_point == Point::default() {
+                            board.pass(color);
+                        } else {
+                            board.place(color, at_point);
+                        }
 
                         self.history.push(board);
                     }
@@ -623,16 +664,32 @@ impl Gtp {
                 let result = self.greedy_playout(&board);
 
                 if let Ok(finished) = result {
-                    let status_list = board.get_stone_status(&finished);
-                    let vertices = status_list.into_iter()
-                        .filter_map(|(index, stone_status)| {
-                            if stone_status.contains(&status) {
-                                Some(format!("{}", Vertex::from(index)))
-                            } else {
-                                None
-                            }
-                        })
-                        .collect::<Vec<String>>();
+                    let group_status = match status {
+                        StoneStatus::Alive => Some(GroupStatus::Alive),
+                        StoneStatus::Dead => Some(GroupStatus::Dead),
+                        StoneStatus::Seki => Some(GroupStatus::Seki),
+                        _ => None
+                    };
+                    let vertices = if let Some(group_status) = group_status {
+                        let mut points = board.get_group_status(&finished).into_iter()
+                            .filter_map(|(point, other)| if other == group_status { Some(point) } else { None })
+                            .collect::<Vec<_>>();
+                        points.sort_by_key(|point| point.to_packed_index());
+
+                        points.into_iter()
+                            .map(|point| format!("{}", Vertex::from(point)))
+                            .collect::<Vec<String>>()
+                    } else {
+                        board.get_stone_status(&finished).into_iter()
+                            .filter_map(|(index, stone_status)| {
+                                if stone_status.contains(&status) {
+                                    Some(format!("{}", Vertex::from(index)))
+                                } else {
+                                    None
+                                }
+                            })
+                            .collect::<Vec<String>>()
+                    };
 
                     success!(id, vertices.join(" "));
                 } else {
@@ -640,8 +697,7 @@ impl Gtp {
                 }
             },
             Command::LoadSgf(filename, move_number) => {
-                if let Ok(file) = File::open(filename) {
-                    let mut buf_reader = BufReader::new(file);
+                if let Ok(mut buf_reader) = gzip::open(filename) {
                     let mut content = vec! [];
 
                     if let Err(_reason) = buf_reader.read_to_end(&mut content) {
@@ -652,9 +708,12 @@ impl Gtp {
                     self.explain_last_move = String::new();
                     self.finished_board = None;
 
+                    let mut last_move = None;
+
                     for entry in Sgf::new(&content, self.komi).take(move_number) {
                         match entry {
                             Ok(entry) => {
+                                last_move = Some((entry.color, entry.point));
                                 self.history.push(entry.board);
                             },
                             Err(_reason) => {
@@ -664,6 +723,23 @@ impl Gtp {
                         }
                     }
 
+                    // each entry is the position _before_ its move, so if the
+                    // whole game was loaded we still need to play the final move
+                    // to keep the move history of the current position intact
+                    if self.history.len() < move_number {
+                        let mut board = self.history.last().cloned().unwrap_or_else(|| Board::new(self.komi));
+
+                        if let Some((color, point)) = last_move {
+                            if point == Point::default() {
+                                board.pass(color);
+                            } else {
+                                board.place(color, point);
+                            }
+                        }
+
+                        self.history.push(board);
+                    }
+
                     // start the pondering agent
                     let board = self.history.last().unwrap().clone();
                     self.ponder = PonderService::new(board);
@@ -758,9 +834,9 @@ pub fn run() {
     let stdin = ::std::io::stdin();
     let stdin_lock = stdin.lock();
     let mut gtp = Gtp {
-        ponder: PonderService::new(Board::new(DEFAULT_KOMI)),
-        history: vec! [Board::new(DEFAULT_KOMI)],
-        komi: DEFAULT_KOMI,
+        ponder: PonderService::new(Board::default()),
+        history: vec! [Board::default()],
+        komi: recommended_komi(19),
         explain_last_move: String::new(),
         finished_board: None,
         time_settings: [
@@ -784,6 +860,12 @@ pub fn run() {
             break
         }
     }
+
+    let _ = gtp.ponder.service(|pool, search_tree, p_state| {
+        mcts::predictor::save_cache_file(pool.predictor());
+
+        ((), Some(search_tree), p_state)
+    });
 }
 
 #[cfg(test)]
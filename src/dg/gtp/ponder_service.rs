@@ -41,7 +41,7 @@ pub struct PonderTimeControl {
 impl TimeStrategy for PonderTimeControl {
     fn try_extend(&self, root: &tree::Node) -> TimeStrategyResult {
         if self.is_running.load(Ordering::Relaxed) {
-            let total_visits = root.size();
+            let total_visits = root.visit_count();
 
             if total_visits < self.max_tree_size {
                 TimeStrategyResult::NotExpired(self.max_tree_size - total_visits)
@@ -131,6 +131,7 @@ impl PonderService {
             is_running: is_running,
             worker: Some(thread::spawn(move || {
                 let pool = Pool::new(Box::new(DefaultPredictor::default()));
+                mcts::predictor::load_cache_file(pool.predictor());
 
                 ponder_worker(pool, None, board, to_move, is_running_worker)
             })),
@@ -208,9 +209,9 @@ impl PonderService {
     pub fn forward(&mut self, color: Color, at_point: Option<Point>) {
         let _result = self.service(move |_service, search_tree, (board, to_move)| {
             let search_tree = if to_move != color {
-                // passing moves are not recorded in the GTP protocol, so we
-                // will just assume the other player passed once if we are in
-                // this situation
+                // the other player did not play a move since their last one, so
+                // we will just assume they passed once if we are in this
+                // situation
                 mcts::tree::Node::forward(search_tree, 361)
             } else {
                 Some(search_tree)
@@ -230,7 +231,10 @@ impl PonderService {
                 other.place(color, point);
                 other
             } else {
-                board
+                let mut other = board;
+
+                other.pass(color);
+                other
             };
 
             ((), search_tree, (other, color.opposite()))
@@ -29,6 +29,10 @@ use dg_utils::config::{self, Procedure};
 
 /// Main function.
 fn main() {
+    // exit early on configuration values that cannot be parsed, instead of
+    // when they are first used
+    lazy_static::initialize(&config::RESIGN_CRITERION);
+
     match &*config::PROCEDURE {
         Procedure::Help => {
             println!("Usage: ./dream-go [options]");
@@ -40,18 +44,26 @@ fn main() {
             println!("                           policies");
             println!("  --bench <sgf...>         Run benchmarks on the board positions in the provided SGF");
             println!("  --gtp                    Run GTP client (default)");
+            println!("  --dump-config            Print every effective configuration value");
             println!();
             println!("Advanced options:");
             println!("  --safe-time <n>          The minimum number of milliseconds to leave on the game clock");
             println!("  --num-rollout <n>        The number of rollouts to add to the search tree for every move");
             println!("  --num-ex-it-rollout <n>  The number of rollouts to add to the search tree for every move");
+            println!("  --max-nodes <n>          The maximum number of nodes in each search tree");
             println!("  --num-games <n>          The number of games to play or extract in parallel");
             println!("  --num-threads <n>        The number of search threads to use in total");
             println!("  --num-samples <n>        The number of games to extract from each game record");
             println!("  --batch-size <n>         The number parallel rollouts to perform on the GPU");
+            println!("  --cache-size <n>         The maximum number of network evaluations to cache");
+            println!("  --cache-file <path>      Load the evaluation cache from this file at startup, and save");
+            println!("                           it to the same file before exiting");
             println!("  --tt                     Play using Tromp-Taylor rules");
             println!("  --no-ponder              Do not think in the background during idle time");
             println!("  --no-resign              Do not allow the engine to resign in games");
+            println!("  --self-play-resign       Allow the engine to resign in self-play games");
+            println!("  --friendly-pass          Pass when the opponent passes in a settled position that the");
+            println!("                           engine is winning");
         },
 
         Procedure::Benchmark => {
@@ -62,24 +74,52 @@ fn main() {
                 println!("  batch_size {}", *config::BATCH_SIZE);
                 println!("    forward: {:.4} per second", bench::ForwardBenchmark::new().evaluate(&sgf_file));
                 println!("    mcts:    {:.4} per second", bench::MctsBenchmark::new().evaluate(&sgf_file));
+
+                let mut self_play = bench::SelfPlayBenchmark::new();
+                let rate = self_play.evaluate(&sgf_file);
+                let executor = self_play.executor();
+
+                println!("  self_play: {:.4} per second", rate);
+                println!("    moves:    {}", executor.num_moves());
+                println!("    playouts: {}", executor.num_playouts());
+                println!("    elapsed:  {:.4} seconds", executor.elapsed().as_secs_f64());
             }
         },
 
+        Procedure::DumpConfig => {
+            println!("{}", config::dump());
+        },
+
         Procedure::Reanalyze(files) => {
-            let (receiver, _server) = dg_mcts::reanalyze(files);
+            let (receiver, server) = dg_mcts::reanalyze(files);
+
+            let network = server.predictor().identifier();
 
             for result in receiver.iter() {
                 eprint!(".");
-                println!("{}", result);
+                println!("{}", result.to_sgf(network.as_deref()));
             }
+
+            eprintln!("batch sizes: {:?}", server.batch_size_histogram());
         },
 
         Procedure::SelfPlay(n, ex_it) => {
-            let (receiver, _server) = dg_mcts::self_play(*n, *ex_it);
+            let (receiver, server) = dg_mcts::self_play(*n, *config::NUM_GAMES, *ex_it);
+
+            let network = server.predictor().identifier();
 
             for result in receiver.iter() {
                 eprint!(".");
-                println!("{}", result);
+                println!("{}", result.to_sgf(network.as_deref()));
+            }
+
+            eprintln!("batch sizes: {:?}", server.batch_size_histogram());
+            dg_mcts::predictor::save_cache_file(server.predictor());
+
+            if *config::SELF_PLAY_RESIGN {
+                let (num_checks, num_false_resigns) = dg_mcts::false_resign_count();
+
+                eprintln!("false resigns: {} / {}", num_false_resigns, num_checks);
             }
         },
 
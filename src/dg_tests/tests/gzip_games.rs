@@ -0,0 +1,57 @@
+// Copyright 2021 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+extern crate dg_go;
+extern crate dg_utils;
+
+use dg_go::utils::sgf::{self, Sgf};
+use dg_utils::gzip;
+
+use std::fs::File;
+use std::io::{BufRead, Read};
+
+#[test]
+fn same_as_uncompressed() {
+    let mut expected = vec! [];
+    File::open("fixtures/example_games.sgf").unwrap().read_to_end(&mut expected).unwrap();
+
+    let mut actual = vec! [];
+    gzip::open("fixtures/example_games.sgf.gz").unwrap().read_to_end(&mut actual).unwrap();
+
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn plain_files_are_untouched() {
+    let mut expected = vec! [];
+    File::open("fixtures/example_games.sgf").unwrap().read_to_end(&mut expected).unwrap();
+
+    let mut actual = vec! [];
+    gzip::open("fixtures/example_games.sgf").unwrap().read_to_end(&mut actual).unwrap();
+
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn all_games_parse() {
+    for line in gzip::open("fixtures/example_games.sgf.gz").unwrap().lines() {
+        let line = line.unwrap();
+        let komi = sgf::get_komi_from_sgf(&line).unwrap();
+
+        for entry in Sgf::new(line.as_bytes(), komi) {
+            assert!(entry.is_ok(), "{}", line);
+        }
+    }
+}
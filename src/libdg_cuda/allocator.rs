@@ -18,7 +18,7 @@ use crate::memory::*;
 use std::cell::{RefCell, Ref};
 use std::collections::btree_map::BTreeMap;
 use std::rc::Rc;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, MutexGuard};
 use std::ops::{Deref, DerefMut};
 
 pub trait Allocator {
@@ -53,7 +53,8 @@ impl Allocator for Native {
 
 pub struct Sticky<A: Allocator> {
     allocator: A,
-    ptrs: BTreeMap<usize, Vec<Ptr>>
+    ptrs: BTreeMap<usize, Vec<Ptr>>,
+    num_allocations: usize
 }
 
 impl<A: Allocator + Default> Default for Sticky<A> {
@@ -65,12 +66,18 @@ impl<A: Allocator + Default> Default for Sticky<A> {
 impl<A: Allocator> Sticky<A> {
     pub fn new(allocator: A) -> Self {
         let ptrs = BTreeMap::new();
-        Self { allocator, ptrs }
+        Self { allocator, ptrs, num_allocations: 0 }
     }
 
     pub fn allocator(&self) -> &A {
         &self.allocator
     }
+
+    /// Returns the number of allocations that could not be served by a
+    /// previously freed pointer, and was forwarded to the inner allocator.
+    pub fn num_allocations(&self) -> usize {
+        self.num_allocations
+    }
 }
 
 impl<A: Allocator> Drop for Sticky<A> {
@@ -87,7 +94,10 @@ impl<A: Allocator> Allocator for Sticky<A> {
     fn alloc(&mut self, size_in_bytes: usize) -> Result<Ptr, Error> {
         match self.ptrs.get_mut(&size_in_bytes) {
             Some(x) if x.len() > 0 => Ok(x.pop().unwrap()),
-            _ => self.allocator.alloc(size_in_bytes)
+            _ => {
+                self.num_allocations += 1;
+                self.allocator.alloc(size_in_bytes)
+            }
         }
     }
 
@@ -160,6 +170,10 @@ impl<A: Allocator> Concurrent<A> {
     pub fn new(allocator: A) -> Self {
         Self { allocator: Arc::new(Mutex::new(allocator)) }
     }
+
+    pub fn allocator(&self) -> MutexGuard<'_, A> {
+        self.allocator.lock().expect("could not acquire allocator")
+    }
 }
 
 impl<A: Allocator> Allocator for Concurrent<A> {
@@ -263,6 +277,22 @@ mod tests {
 
         assert_eq!(allocator.allocator().total_allocs, 1);
         assert_eq!(allocator.allocator().total_frees, 0);
+        assert_eq!(allocator.num_allocations(), 1);
+    }
+
+    #[test]
+    fn sticky_concurrent_ptrs() {
+        let counting_allocator = CountingAllocator { total_allocs: 0, total_frees: 0 };
+        let mut allocator = Sticky::new(counting_allocator);
+
+        for _i in 0..10 {
+            let ptr_1 = allocator.alloc(12).unwrap();
+            let ptr_2 = allocator.alloc(12).unwrap();
+            allocator.free(ptr_1);
+            allocator.free(ptr_2);
+        }
+
+        assert_eq!(allocator.num_allocations(), 2);
     }
 
     #[test]
@@ -13,6 +13,7 @@
 // limitations under the License.
 
 use crate::cudnn::*;
+use dg_utils::config;
 
 use libc::{c_void, size_t};
 
@@ -64,7 +65,11 @@ impl ConvolutionBiasActivation {
         y: TensorDescriptor,
     ) -> Result<Self, Status>
     {
-        let fwd_algo_perf = ConvolutionFwdAlgoPerf::new(handle, &x, &w, &conv, &y)?;
+        let fwd_algo_perf = if *config::CUDNN_FIND_ALGO {
+            ConvolutionFwdAlgoPerf::find(handle, &x, &w, &conv, &y)?
+        } else {
+            ConvolutionFwdAlgoPerf::new(handle, &x, &w, &conv, &y)?
+        };
         let alpha = [alpha_1, alpha_2];
 
         Ok(Self {
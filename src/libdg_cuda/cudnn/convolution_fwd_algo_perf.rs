@@ -31,6 +31,17 @@ extern {
         returned_algo_count: *mut c_int,
         perf_results: *mut cudnnConvolutionFwdAlgoPerf_t
     ) -> cudnnStatus_t;
+
+    fn cudnnFindConvolutionForwardAlgorithm(
+        handle: cudnnHandle_t,
+        x_desc: cudnnTensorDescriptor_t,
+        w_desc: cudnnFilterDescriptor_t,
+        conv_desc: cudnnConvolutionDescriptor_t,
+        y_desc: cudnnTensorDescriptor_t,
+        requested_algo_count: c_int,
+        returned_algo_count: *mut c_int,
+        perf_results: *mut cudnnConvolutionFwdAlgoPerf_t
+    ) -> cudnnStatus_t;
 }
 
 #[repr(C)]
@@ -44,7 +55,33 @@ pub struct ConvolutionFwdAlgoPerf {
     reserved: [c_int; 3]
 }
 
+impl Default for ConvolutionFwdAlgoPerf {
+    fn default() -> Self {
+        Self {
+            algo: ConvolutionFwdAlgo::ImplicitGemm,
+            status: Status::Success,
+            time: 0.0,
+            memory: 0,
+            determinism: Determinism::NonDeterministic,
+            math_type: MathType::DefaultMath,
+            reserved: [0; 3]
+        }
+    }
+}
+
 impl ConvolutionFwdAlgoPerf {
+    /// Returns the algorithm that the heuristics of cuDNN recommends for the
+    /// given convolution. This is instant and deterministic, but may not pick
+    /// the fastest algorithm.
+    ///
+    /// # Arguments
+    ///
+    /// * `handle` -
+    /// * `x` - the input tensor
+    /// * `w` - the filter
+    /// * `conv` - the convolution
+    /// * `y` - the output tensor
+    ///
     pub fn new(
         handle: &Handle,
         x: &TensorDescriptor,
@@ -54,15 +91,7 @@ impl ConvolutionFwdAlgoPerf {
     ) -> Result<Self, Status>
     {
         let mut count = 0;
-        let mut out = Self {
-            algo: ConvolutionFwdAlgo::ImplicitGemm,
-            status: Status::Success,
-            time: 0.0,
-            memory: 0,
-            determinism: Determinism::NonDeterministic,
-            math_type: MathType::DefaultMath,
-            reserved: [0; 3]
-        };
+        let mut out = Self::default();
         let status =
             unsafe {
                 cudnnGetConvolutionForwardAlgorithm_v7(
@@ -77,8 +106,59 @@ impl ConvolutionFwdAlgoPerf {
                 )
             };
 
-        assert_eq!(count, 1);
-        status.into_result(out)
+        status.into_result(())?;
+
+        if count != 1 {
+            return Err(Status::NotSupported);
+        }
+
+        Ok(out)
+    }
+
+    /// Returns the fastest algorithm for the given convolution, as measured
+    /// by running every algorithm on the device. This is slow, and the
+    /// result may differ between runs since it depends on the timings.
+    ///
+    /// # Arguments
+    ///
+    /// * `handle` -
+    /// * `x` - the input tensor
+    /// * `w` - the filter
+    /// * `conv` - the convolution
+    /// * `y` - the output tensor
+    ///
+    pub fn find(
+        handle: &Handle,
+        x: &TensorDescriptor,
+        w: &FilterDescriptor,
+        conv: &ConvolutionDescriptor,
+        y: &TensorDescriptor,
+    ) -> Result<Self, Status>
+    {
+        let mut count = 0;
+        let mut out = Self::default();
+        let status =
+            unsafe {
+                cudnnFindConvolutionForwardAlgorithm(
+                    **handle,
+                    **x,
+                    **w,
+                    **conv,
+                    **y,
+                    1,
+                    &mut count,
+                    &mut out
+                )
+            };
+
+        status.into_result(())?;
+
+        if count != 1 {
+            return Err(Status::NotSupported);
+        }
+
+        // the search can succeed even if the best algorithm failed to run
+        out.status.into_result(out)
     }
 
     pub fn algo(&self) -> ConvolutionFwdAlgo {
@@ -128,4 +208,36 @@ mod tests {
         assert_eq!(out.algo, ConvolutionFwdAlgo::ImplicitPrecompGemm);
         assert_eq!(out.math_type, MathType::DefaultMath);
     }
+
+    #[test]
+    fn find_dilated_perf() {
+        let handle = Handle::new().unwrap();
+        let x = TensorDescriptor::new(
+            TensorFormat::NHWC,
+            DataType::Float,
+            [16, 256, 19, 19]
+        ).unwrap();
+        let w = FilterDescriptor::new(
+            DataType::Float,
+            TensorFormat::NHWC,
+            [256, 256, 3, 3]
+        ).unwrap();
+        let conv = ConvolutionDescriptor::new(
+            [2, 2],
+            [1, 1],
+            [2, 2],
+            ConvolutionMode::CrossCorrelation,
+            DataType::Float
+        ).unwrap();
+        let out = ConvolutionFwdAlgoPerf::find(
+            &handle,
+            &x,
+            &w,
+            &conv,
+            &x
+        );
+
+        assert!(out.is_ok());
+        assert_eq!(out.unwrap().math_type, MathType::DefaultMath);
+    }
 }
\ No newline at end of file
@@ -28,6 +28,7 @@ extern {
 #[derive(Clone, Copy, PartialEq)]
 pub enum Status {
     Success = 0,
+    NotSupported = 9,
 }
 
 impl Status {
@@ -68,4 +69,9 @@ mod tests {
 
         assert_eq!(format!("{:?}", status), "CUDNN_STATUS_NOT_INITIALIZED");
     }
+
+    #[test]
+    fn status_not_supported() {
+        assert_eq!(format!("{:?}", Status::NotSupported), "CUDNN_STATUS_NOT_SUPPORTED");
+    }
 }
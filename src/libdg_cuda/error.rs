@@ -28,6 +28,10 @@ extern {
 #[derive(Clone, Copy, PartialEq)]
 pub enum Error {
     Success = 0,
+
+    /// The queried work has not finished yet, which is not an actual failure
+    /// of it.
+    NotReady = 600,
 }
 
 impl Error {
@@ -95,6 +95,16 @@ impl Stream {
         unsafe { cudaStreamQuery(self.stream) }.into_result(())
     }
 
+    /// Returns whether all work enqueued on this stream has finished, without
+    /// waiting for it to do so.
+    pub fn is_finished(&self) -> Result<bool, Error> {
+        match self.query() {
+            Ok(()) => Ok(true),
+            Err(Error::NotReady) => Ok(false),
+            Err(other) => Err(other)
+        }
+    }
+
     pub fn wait_event(&self, event: &Event) -> Result<(), Error> {
         unsafe { cudaStreamWaitEvent(self.stream, event.event, 0) }.into_result(())
     }
@@ -23,6 +23,62 @@ use iter::IsPartOf;
 use point::Point;
 use point_state::Vertex;
 
+/// The stones that differ between two boards, as returned by `Board::diff`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BoardDiff {
+    /// The stones that are present in the other board, but not in this one.
+    pub added: Vec<(Color, Point)>,
+
+    /// The stones that are present in this board, but not in the other one.
+    pub removed: Vec<(Color, Point)>
+}
+
+/// Whether a move is legal, and if not then why, as returned by
+/// `Board::legality`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Legality {
+    /// The move is legal.
+    Legal,
+
+    /// There is already a stone at the vertex.
+    Occupied,
+
+    /// The move would leave its own group without any liberties.
+    Suicide,
+
+    /// The move immediately re-takes a ko.
+    Ko,
+
+    /// The move repeats an earlier board position (other than an immediate
+    /// ko re-take).
+    Superko,
+
+    /// The vertex is not on the board.
+    OffBoard
+}
+
+impl Legality {
+    /// Returns true if the move is legal.
+    pub fn is_legal(self) -> bool {
+        self == Legality::Legal
+    }
+}
+
+impl fmt::Display for Legality {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match *self {
+            Legality::Legal => "legal",
+            Legality::Occupied => "occupied",
+            Legality::Suicide => "suicide",
+            Legality::Ko => "ko",
+            Legality::Superko => "superko",
+            Legality::OffBoard => "off board"
+        };
+
+        write!(fmt, "{}", reason)
+    }
+}
+
 ///
 #[derive(Clone)]
 #[repr(align(64))]
@@ -47,9 +103,28 @@ pub struct Board {
 
     /// The color of the player who played the most recent move.
     pub(super) last_played: Option<Color>,
+
+    /// The number of opponent stones captured by each color. This is not
+    /// part of the compact binary encoding.
+    pub(super) prisoners: [u16; 3],
+}
+
+impl Default for Board {
+    /// Returns an empty board with the conventional komi for its size, as
+    /// given by `recommended_komi`.
+    fn default() -> Board {
+        Board::new(::recommended_komi(19))
+    }
 }
 
 impl Board {
+    /// Returns an empty board with the given komi. Use `Board::default()` if
+    /// the komi is not known, instead of guessing one.
+    ///
+    /// # Arguments
+    ///
+    /// * `komi` - the komi used for this game
+    ///
     pub fn new(komi: f32) -> Board {
         Board {
             inner: BoardFast::new(),
@@ -59,7 +134,58 @@ impl Board {
             last_played: None,
             zobrist_hash: 0,
             zobrist_history: SmallSet64::new(),
+            prisoners: [0; 3],
+        }
+    }
+
+    /// Returns a board with the stones of the given ASCII diagram, where `.`
+    /// is an empty vertex, `X` a black stone, and `O` a white stone. Whitespace
+    /// between vertices is ignored. The rows and columns of the diagram are the
+    /// matrix coordinates of `Point::from_row_col`, so the top row is given
+    /// first, where the top edge is `y = 0` as the board is drawn from an SGF
+    /// file (which is upside down compared to the pretty-printed board). A
+    /// diagram with fewer than 19 rows or columns describes the upper left
+    /// corner of the board, i.e. the first character of the first row is
+    /// `Point::new(0, 0)`.
+    ///
+    /// The stones are added as setup stones, see `add_setup_stone`, so the
+    /// returned board has no history. Returns `None` if the diagram is too
+    /// large, contains an unrecognized character, or contains a stone without
+    /// any liberties.
+    ///
+    /// # Arguments
+    ///
+    /// * `komi` -
+    /// * `diagram` -
+    ///
+    pub fn from_diagram(komi: f32, diagram: &str) -> Option<Board> {
+        let rows = diagram.lines()
+            .map(|line| line.chars().filter(|c| !c.is_whitespace()).collect::<Vec<char>>())
+            .filter(|row| !row.is_empty())
+            .collect::<Vec<_>>();
+
+        if rows.len() > 19 || rows.iter().any(|row| row.len() > 19) {
+            return None;
+        }
+
+        let mut board = Board::new(komi);
+
+        for (i, row) in rows.iter().enumerate() {
+            for (j, &c) in row.iter().enumerate() {
+                let color = match c {
+                    '.' => continue,
+                    'X' => Color::Black,
+                    'O' => Color::White,
+                    _ => return None
+                };
+
+                if !board.add_setup_stone(color, Point::from_row_col(i, j)) {
+                    return None;
+                }
+            }
         }
+
+        Some(board)
     }
 
     /// Returns the width and height of this board.
@@ -80,12 +206,118 @@ impl Board {
         self.komi = komi;
     }
 
+    /// Returns the number of opponent stones that has been captured by the
+    /// given color.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - the color that made the captures
+    ///
+    #[inline]
+    pub fn prisoners(&self, color: Color) -> usize {
+        self.prisoners[color as usize] as usize
+    }
+
+    /// Sets the number of opponent stones that has been captured by the given
+    /// color, for example when the board was set up from a position with
+    /// known prisoner counts.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - the color that made the captures
+    /// * `prisoners` - the number of captured stones
+    ///
+    #[inline]
+    pub fn set_prisoners(&mut self, color: Color, prisoners: usize) {
+        self.prisoners[color as usize] = prisoners as u16;
+    }
+
     /// Returns the number of moves that has been played on this board.
     #[inline]
     pub fn count(&self) -> usize {
         self.count as usize
     }
 
+    /// Returns the number of black and white stones on this board, in that
+    /// order.
+    pub fn count_by_color(&self) -> (usize, usize) {
+        let mut black = 0;
+        let mut white = 0;
+
+        for point in Point::all() {
+            match self.at(point) {
+                Some(Color::Black) => { black += 1 },
+                Some(Color::White) => { white += 1 },
+                None => {}
+            }
+        }
+
+        (black, white)
+    }
+
+    /// Returns the features of the square window of `2 * radius + 1` vertices
+    /// to each side centered around `center`, from the perspective of `color`.
+    /// The window is returned as two planes, where each plane lists the
+    /// vertices row by row starting at the lower left corner of the window:
+    ///
+    /// 1. The stones, `1` for a stone of `color`, `-1` for a stone of the
+    ///    opponent, `0` for an empty vertex, and `2` for a vertex outside of
+    ///    the board.
+    /// 2. The liberties, which for a stone is the number of liberties of its
+    ///    group, and for an empty vertex is the number of liberties a stone of
+    ///    `color` would have if played there (`0` if that move is illegal). This
+    ///    is at most `6`, as in the full features, and `-1` for a vertex
+    ///    outside of the board.
+    ///
+    /// # Arguments
+    ///
+    /// * `center` - the vertex at the center of the window
+    /// * `radius` - the number of vertices on each side of the center
+    /// * `color` - the color whose perspective to use
+    ///
+    pub fn local_features(&self, center: Point, radius: usize, color: Color) -> Vec<i8> {
+        let size = 2 * radius + 1;
+        let mut out = vec! [0; 2 * size * size];
+        let radius = radius as isize;
+
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                let x = center.x() as isize + dx;
+                let y = center.y() as isize + dy;
+                let index = size * (dy + radius) as usize + (dx + radius) as usize;
+
+                if !(0..19).contains(&x) || !(0..19).contains(&y) {
+                    out[index] = 2;
+                    out[size * size + index] = -1;
+                    continue;
+                }
+
+                let point = Point::new(x as usize, y as usize);
+
+                match self.at(point) {
+                    Some(other) => {
+                        out[index] = if other == color { 1 } else { -1 };
+                        out[size * size + index] = self.inner.get_n_liberty(point).min(6) as i8;
+                    },
+                    None => {
+                        if self.inner.is_valid(color, point) {
+                            out[size * size + index] = self.inner.get_n_liberty_if(color, point).min(6) as i8;
+                        }
+                    }
+                }
+            }
+        }
+
+        out
+    }
+
+    /// Returns the number of vertices on this board that has no stone on them.
+    pub fn empty_count(&self) -> usize {
+        let (black, white) = self.count_by_color();
+
+        361 - black - white
+    }
+
     /// Returns the zobrist hash of this board.
     #[inline]
     pub fn zobrist_hash(&self) -> u64 {
@@ -107,6 +339,86 @@ impl Board {
         }
     }
 
+    /// Sets the color whose turn it is to play a move, for example after
+    /// setting up a handicap position where the turn does not follow from
+    /// the moves played.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - the color of the player to move
+    ///
+    #[inline]
+    pub fn set_to_move(&mut self, color: Color) {
+        self.last_played = Some(color.opposite());
+    }
+
+    /// Returns the most recently played moves, newest first. Passing moves
+    /// are not part of the history.
+    pub fn history<'a>(&'a self) -> impl Iterator<Item=Point> + 'a {
+        self.history.iter().filter(|&point| point != Point::default())
+    }
+
+    /// Returns the most recently played moves, newest first, where a passing
+    /// move is `Point::default()`. Unlike `history` this is exactly the
+    /// history that the features are computed from.
+    pub fn history_with_passes<'a>(&'a self) -> impl Iterator<Item=Point> + 'a {
+        self.history.iter()
+    }
+
+    /// Returns true if this board and `other` describes the same position,
+    /// i.e. the same stones, the same ko points, and the same player to
+    /// move. Unlike `==`, the order in which the stones were played does
+    /// not matter.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - the board to compare against
+    ///
+    pub fn position_eq(&self, other: &Board) -> bool {
+        let to_move = self.to_move();
+
+        self.zobrist_hash == other.zobrist_hash
+            && to_move == other.to_move()
+            && Point::all().all(|p| {
+                let color = self.inner[p].color();
+
+                color == other.inner[p].color() && (
+                    color.is_some() || !self.inner.is_valid(to_move, p) || {
+                        self._is_ko(to_move, p) == other._is_ko(to_move, p)
+                    }
+                )
+            })
+    }
+
+    /// Returns the stones that were added and removed between this board
+    /// and `other`. A vertex whose stone changed color is reported as both
+    /// removed and added.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - the board to compare against
+    ///
+    pub fn diff(&self, other: &Board) -> BoardDiff {
+        let mut diff = BoardDiff::default();
+
+        for point in Point::all() {
+            let before = self.inner[point].color();
+            let after = other.inner[point].color();
+
+            if before != after {
+                if let Some(color) = before {
+                    diff.removed.push((color, point));
+                }
+
+                if let Some(color) = after {
+                    diff.added.push((color, point));
+                }
+            }
+        }
+
+        diff
+    }
+
     /// Returns the color (if the vertex is not empty) of the stone at
     /// the given coordinates.
     ///
@@ -121,6 +433,25 @@ impl Board {
         self.inner[point].color()
     }
 
+    /// Returns the number of liberties that the group at `my_point` would have
+    /// after capturing the opponent group at `opponent_point`. If the two groups
+    /// are not adjacent then this is the current number of liberties of the
+    /// group at `my_point`.
+    ///
+    /// # Arguments
+    ///
+    /// * `my_point` - a stone in the group to count the liberties of
+    /// * `opponent_point` - a stone in the group to capture
+    ///
+    pub fn liberties_after_capturing(&self, my_point: Point, opponent_point: Point) -> usize {
+        debug_assert!(self.is_part_of(my_point));
+        debug_assert!(self.is_part_of(opponent_point));
+        debug_assert!(self.at(my_point).is_some());
+        debug_assert_eq!(self.at(opponent_point), self.at(my_point).map(|c| c.opposite()));
+
+        self.inner.get_n_liberty_after_capture(my_point, opponent_point)
+    }
+
     /// Returns true if playing at the given index violated the
     /// super-ko rule.
     ///
@@ -152,6 +483,54 @@ impl Board {
         self.inner.is_valid(color, at_point) && !self._is_ko(color, at_point)
     }
 
+    /// Returns whether the given move is legal according to the Tromp-Taylor
+    /// rules, and if it is not then the reason why. This agrees with
+    /// `is_valid` for every vertex on the board.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - the color of the move
+    /// * `at_point` - where to play the move
+    ///
+    pub fn legality(&self, color: Color, at_point: Point) -> Legality {
+        if !self.is_part_of(at_point) {
+            Legality::OffBoard
+        } else if self.inner[at_point].color().is_some() {
+            Legality::Occupied
+        } else if !self.inner.is_valid(color, at_point) {
+            Legality::Suicide
+        } else if self.inner[at_point].visited() {
+            let adjust = self.inner.place_if(color, at_point);
+            let next_zobrist_hash = self.zobrist_hash ^ adjust;
+
+            if next_zobrist_hash == self.zobrist_history.nth_most_recent(1) {
+                Legality::Ko
+            } else if self.zobrist_history.contains(next_zobrist_hash) {
+                Legality::Superko
+            } else {
+                Legality::Legal
+            }
+        } else {
+            Legality::Legal
+        }
+    }
+
+    /// Returns whether playing a stone of the given color at the given vertex
+    /// would capture at least one opponent stone.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - the color of the move
+    /// * `at_point` - where to play the move
+    ///
+    pub fn is_capture(&self, color: Color, at_point: Point) -> bool {
+        let opponent = color.opposite();
+
+        self.inner.adjacent_to(at_point).any(|other_point| {
+            self.inner[other_point].color() == Some(opponent) && !self.inner.has_n_liberty(other_point, 2)
+        })
+    }
+
     /// Place the given stone on the board without checking if it is legal, the
     /// board is then updated according to the Tromp-Taylor rules with the
     /// except that ones own color is not cleared.
@@ -164,7 +543,10 @@ impl Board {
     pub fn _place(&mut self, color: Color, at_point: Point) {
         // place the stone on the board regardless of whether it is legal
         // or not.
-        self.zobrist_hash ^= self.inner.place(color, at_point);
+        let (hash, num_captured) = self.inner.place(color, at_point);
+
+        self.zobrist_hash ^= hash;
+        self.prisoners[color as usize] += num_captured as u16;
         self.last_played = Some(color);
         self.count += 1;
 
@@ -186,6 +568,204 @@ impl Board {
     pub fn place(&mut self, color: Color, at_point: Point) {
         self._place(color, at_point)
     }
+
+    /// Adds a stone of the given color to the board as part of setting up a
+    /// position, e.g. from the `AB` and `AW` properties of an SGF file. Unlike
+    /// `place` this is not a move, so the history, the move count, the super-ko
+    /// history, and the player to move are left unchanged.
+    ///
+    /// Returns `false`, and leaves the board unchanged, if the vertex is not
+    /// empty, or if the stone would have no liberties or capture any stones.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - the color of the stone
+    /// * `at_point` - where to add the stone
+    ///
+    pub fn add_setup_stone(&mut self, color: Color, at_point: Point) -> bool {
+        if !self.inner.is_valid(color, at_point) {
+            return false;
+        }
+
+        let opponent = Some(color.opposite());
+        let is_capture = self.inner.adjacent_to(at_point).any(|other_point| {
+            self.inner[other_point].color() == opponent && !self.inner.has_n_liberty(other_point, 2)
+        });
+
+        if is_capture {
+            return false;
+        }
+
+        let (hash, _) = self.inner.place(color, at_point);
+        self.zobrist_hash ^= hash;
+
+        true
+    }
+
+    /// Records that the given player passed, which does not change the stones
+    /// on the board but gives the turn to the opponent and adds a passing move
+    /// to the recent history (which is part of the features).
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - the color of the player who passed
+    ///
+    pub fn pass(&mut self, color: Color) {
+        self.last_played = Some(color);
+        self.count += 1;
+        self.history.push(Point::default());
+    }
+
+    /// Returns a compact binary encoding of this board, that contains the
+    /// stones, the player to move, the komi, the recent history, and enough
+    /// information to detect super-ko. The layout (all integers are little
+    /// endian) is:
+    ///
+    /// - `u8` the format version, which is currently `1`
+    /// - `u8` the color of the last player, `0` if no one has played
+    /// - `u16` the number of moves played
+    /// - `f32` the komi
+    /// - `8 x u16` the recent move history (packed indices), newest first
+    /// - `u8` the next slot of the zobrist history
+    /// - `16 x u64` the zobrist history
+    /// - `91 x u8` two bits per vertex (`0` empty, `1` black, `2` white,
+    ///   and `3` empty but previously occupied)
+    ///
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(SERIALIZED_SIZE);
+
+        out.push(SERIALIZED_VERSION);
+        out.push(self.last_played.map(|c| c as u8).unwrap_or(0));
+        out.extend_from_slice(&self.count.to_le_bytes());
+        out.extend_from_slice(&self.komi.to_bits().to_le_bytes());
+
+        for point in self.history.iter() {
+            out.extend_from_slice(&(point.to_packed_index() as u16).to_le_bytes());
+        }
+
+        out.push(self.zobrist_history.position() as u8);
+        for z in self.zobrist_history.iter() {
+            out.extend_from_slice(&z.to_le_bytes());
+        }
+
+        let mut vertices = [0u8; (361 + 3) / 4];
+
+        for point in Point::all() {
+            let i = point.to_packed_index();
+            let vertex = self.inner[point];
+            let value = match vertex.color() {
+                Some(color) => color as u8,
+                None => if vertex.visited() { 3 } else { 0 }
+            };
+
+            vertices[i / 4] |= value << (2 * (i % 4));
+        }
+
+        out.extend_from_slice(&vertices);
+        out
+    }
+
+    /// Returns the board that was encoded in the given bytes by `to_bytes`, or
+    /// `None` if the bytes does not contain a valid board.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - the encoded board
+    ///
+    pub fn from_bytes(bytes: &[u8]) -> Option<Board> {
+        if bytes.len() != SERIALIZED_SIZE || bytes[0] != SERIALIZED_VERSION {
+            return None;
+        }
+
+        let last_played = match bytes[1] {
+            0 => None,
+            1 => Some(Color::Black),
+            2 => Some(Color::White),
+            _ => { return None }
+        };
+        let mut out = Board::new(f32::from_bits(read_u32(&bytes[4..8])));
+        out.last_played = last_played;
+        out.count = read_u16(&bytes[2..4]);
+
+        // the history is stored newest first, so play it back in reverse
+        for i in (0..8).rev() {
+            let packed_index = read_u16(&bytes[(8 + 2*i)..(10 + 2*i)]) as usize;
+
+            if packed_index > 361 {
+                return None;
+            }
+
+            out.history.push(Point::from_packed_parts(packed_index));
+        }
+
+        let position = bytes[24] as usize;
+        let mut zobrist_history = [0u64; 16];
+
+        if position >= 16 {
+            return None;
+        }
+
+        for i in 0..16 {
+            zobrist_history[i] = read_u64(&bytes[(25 + 8*i)..(33 + 8*i)]);
+        }
+
+        out.zobrist_history = SmallSet64::from_raw_parts(zobrist_history, position);
+
+        // place the stones, this never capture anything if the encoded board
+        // was legal since every group has at least one liberty in the final
+        // position.
+        let vertices = &bytes[153..];
+
+        for point in Point::all() {
+            let i = point.to_packed_index();
+
+            match (vertices[i / 4] >> (2 * (i % 4))) & 0x3 {
+                1 => { out.zobrist_hash ^= out.inner.place(Color::Black, point).0; },
+                2 => { out.zobrist_hash ^= out.inner.place(Color::White, point).0; },
+                3 => { out.inner[point].set_visited(true); },
+                _ => {}
+            }
+        }
+
+        for point in Point::all() {
+            let i = point.to_packed_index();
+            let expected = match (vertices[i / 4] >> (2 * (i % 4))) & 0x3 {
+                1 => Some(Color::Black),
+                2 => Some(Color::White),
+                _ => None
+            };
+
+            if out.inner[point].color() != expected {
+                return None;
+            }
+        }
+
+        Some(out)
+    }
+}
+
+/// The version of the binary format produced by `Board::to_bytes`.
+const SERIALIZED_VERSION: u8 = 1;
+
+/// The size (in bytes) of the binary format produced by `Board::to_bytes`.
+const SERIALIZED_SIZE: usize = 1 + 1 + 2 + 4 + 16 + 1 + 128 + (361 + 3) / 4;
+
+fn read_u16(bytes: &[u8]) -> u16 {
+    let mut buf = [0; 2];
+    buf.copy_from_slice(bytes);
+    u16::from_le_bytes(buf)
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    let mut buf = [0; 4];
+    buf.copy_from_slice(bytes);
+    u32::from_le_bytes(buf)
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    let mut buf = [0; 8];
+    buf.copy_from_slice(bytes);
+    u64::from_le_bytes(buf)
 }
 
 impl fmt::Display for Board {
@@ -275,6 +855,57 @@ mod tests {
     use board::*;
     use color::*;
 
+    #[test]
+    fn default_uses_recommended_komi() {
+        let board = Board::default();
+
+        assert_eq!(board.komi(), ::recommended_komi(19));
+        assert_eq!(board.count(), 0);
+    }
+
+    #[test]
+    fn pass() {
+        let mut board = Board::new(7.5);
+        board.place(Color::Black, Point::new(3, 3));
+
+        let zobrist_hash = board.zobrist_hash();
+        board.pass(Color::White);
+
+        assert_eq!(board.to_move(), Color::Black);
+        assert_eq!(board.zobrist_hash(), zobrist_hash);
+        assert_eq!(board.history.iter().take(2).collect::<Vec<_>>(), vec! [Point::default(), Point::new(3, 3)]);
+        assert_eq!(board.history().collect::<Vec<_>>(), vec! [Point::new(3, 3)]);
+        assert_eq!(board.history_with_passes().take(2).collect::<Vec<_>>(), vec! [Point::default(), Point::new(3, 3)]);
+    }
+
+    #[test]
+    fn local_features() {
+        let mut board = Board::new(7.5);
+        board.place(Color::Black, Point::new(0, 0));
+        board.place(Color::White, Point::new(1, 0));
+
+        let features = board.local_features(Point::new(0, 0), 1, Color::Black);
+
+        assert_eq!(features.len(), 18);
+        assert_eq!(&features[0..9], &[2, 2, 2, 2, 1, -1, 2, 0, 0]);
+        assert_eq!(&features[9..18], &[-1, -1, -1, -1, 1, 2, -1, 2, 3]);
+    }
+
+    #[test]
+    fn count_by_color() {
+        let mut board = Board::new(7.5);
+
+        assert_eq!(board.count_by_color(), (0, 0));
+        assert_eq!(board.empty_count(), 361);
+
+        board.place(Color::Black, Point::new(9, 9));
+        board.place(Color::White, Point::new(8, 9));
+        board.place(Color::Black, Point::new(3, 3));
+
+        assert_eq!(board.count_by_color(), (2, 1));
+        assert_eq!(board.empty_count(), 358);
+    }
+
     /// Test that it is possible to capture a stone in the middle of the
     /// board.
     #[test]
@@ -290,6 +921,125 @@ mod tests {
         assert_eq!(board.at(Point::new(9, 9)), None);
     }
 
+    #[test]
+    fn from_diagram() {
+        let board = Board::from_diagram(7.5, "
+            . O . .
+            X . . .
+            . X . .
+        ").expect("could not parse diagram");
+
+        assert_eq!(board.komi(), 7.5);
+        assert_eq!(board.at(Point::new(0, 1)), Some(Color::Black));
+        assert_eq!(board.at(Point::new(1, 2)), Some(Color::Black));
+        assert_eq!(board.at(Point::new(1, 0)), Some(Color::White));
+        assert_eq!(board.at(Point::new(0, 0)), None);
+        assert_eq!(board.at(Point::new(0, 2)), None);
+    }
+
+    #[test]
+    fn from_diagram_row_col() {
+        for point in Point::all() {
+            let (row, col) = point.to_row_col();
+            let diagram = (0..19)
+                .map(|i| (0..19).map(|j| if (i, j) == (row, col) { 'X' } else { '.' }).collect::<String>())
+                .collect::<Vec<_>>()
+                .join("\n");
+            let board = Board::from_diagram(7.5, &diagram).expect("could not parse diagram");
+
+            assert_eq!(board.at(point), Some(Color::Black));
+            assert_eq!(board.count_by_color(), (1, 0));
+        }
+    }
+
+    #[test]
+    fn add_setup_stone() {
+        let mut board = Board::new(7.5);
+
+        assert!(board.add_setup_stone(Color::White, Point::new(3, 3)));
+        assert!(board.add_setup_stone(Color::Black, Point::new(15, 15)));
+        assert!(!board.add_setup_stone(Color::Black, Point::new(3, 3)));
+
+        assert_eq!(board.at(Point::new(3, 3)), Some(Color::White));
+        assert_eq!(board.at(Point::new(15, 15)), Some(Color::Black));
+        assert_eq!(board.count(), 0);
+        assert_eq!(board.history().count(), 0);
+        assert_eq!(board.last_played(), None);
+        assert_ne!(board.zobrist_hash(), 0);
+    }
+
+    #[test]
+    fn add_setup_stone_capture() {
+        let mut board = Board::new(7.5);
+
+        assert!(board.add_setup_stone(Color::White, Point::new(0, 0)));
+        assert!(board.add_setup_stone(Color::Black, Point::new(1, 0)));
+        assert!(!board.add_setup_stone(Color::Black, Point::new(0, 1)));
+        assert_eq!(board.at(Point::new(0, 0)), Some(Color::White));
+        assert_eq!(board.at(Point::new(0, 1)), None);
+    }
+
+    #[test]
+    fn from_diagram_invalid() {
+        assert!(Board::from_diagram(7.5, "X ? .").is_none());
+        assert!(Board::from_diagram(7.5, &"X".repeat(20)).is_none());
+        assert!(Board::from_diagram(7.5, "O X\nX .").is_none());
+    }
+
+    /// Test that captured stones are counted as prisoners of the capturing
+    /// color.
+    #[test]
+    fn prisoners() {
+        let mut board = Board::new(7.5);
+
+        board.place(Color::Black, Point::new( 9,  9));
+        board.place(Color::White, Point::new( 8,  9));
+        board.place(Color::White, Point::new(10,  9));
+        board.place(Color::White, Point::new( 9,  8));
+        board.place(Color::White, Point::new( 9, 10));
+
+        assert_eq!(board.prisoners(Color::Black), 0);
+        assert_eq!(board.prisoners(Color::White), 1);
+
+        board.set_prisoners(Color::Black, 3);
+        assert_eq!(board.prisoners(Color::Black), 3);
+    }
+
+    /// Test that `diff` reports the captured stones as removed, and the
+    /// capturing stone as added.
+    #[test]
+    fn diff_capture() {
+        let mut board = Board::new(7.5);
+
+        board.place(Color::Black, Point::new( 9,  9));
+        board.place(Color::White, Point::new( 8,  9));
+        board.place(Color::White, Point::new(10,  9));
+        board.place(Color::White, Point::new( 9,  8));
+
+        let before = board.clone();
+        board.place(Color::White, Point::new( 9, 10));
+
+        assert_eq!(before.diff(&board), BoardDiff {
+            added: vec! [(Color::White, Point::new(9, 10))],
+            removed: vec! [(Color::Black, Point::new(9, 9))]
+        });
+        assert_eq!(board.diff(&board), BoardDiff::default());
+    }
+
+    /// Test that capturing an adjacent group adds its stones as liberties.
+    #[test]
+    fn liberties_after_capturing() {
+        let mut board = Board::new(7.5);
+
+        board.place(Color::Black, Point::new(0, 0));
+        board.place(Color::White, Point::new(0, 1));
+        board.place(Color::Black, Point::new(1, 0));
+        board.place(Color::White, Point::new(1, 1));
+
+        assert_eq!(board.liberties_after_capturing(Point::new(0, 0), Point::new(1, 1)), 3);
+        assert_eq!(board.liberties_after_capturing(Point::new(1, 1), Point::new(0, 0)), 5);
+    }
+
     /// Test that it is possible to capture a group of stones in the corner.
     #[test]
     fn capture_group() {
@@ -356,6 +1106,39 @@ mod tests {
         board.place(Color::White, Point::new(0, 1));
 
         assert!(!board.is_valid(Color::Black, Point::new(0, 0)));
+        assert_eq!(board.legality(Color::Black, Point::new(0, 0)), Legality::Ko);
+    }
+
+    #[test]
+    fn legality() {
+        let mut board = Board::new(7.5);
+
+        board.place(Color::Black, Point::new(1, 0));
+        board.place(Color::Black, Point::new(0, 1));
+
+        assert_eq!(board.legality(Color::White, Point::new(5, 5)), Legality::Legal);
+        assert_eq!(board.legality(Color::White, Point::new(1, 0)), Legality::Occupied);
+        assert_eq!(board.legality(Color::White, Point::new(0, 0)), Legality::Suicide);
+        assert_eq!(board.legality(Color::Black, Point::new(0, 0)), Legality::Legal);
+        assert_eq!(board.legality(Color::White, Point::default()), Legality::OffBoard);
+
+        for point in Point::all() {
+            for &color in &[Color::Black, Color::White] {
+                assert_eq!(board.legality(color, point).is_legal(), board.is_valid(color, point));
+            }
+        }
+    }
+
+    #[test]
+    fn is_capture() {
+        let mut board = Board::new(7.5);
+
+        board.place(Color::White, Point::new(0, 0));
+        board.place(Color::Black, Point::new(1, 0));
+
+        assert!(board.is_capture(Color::Black, Point::new(0, 1)));
+        assert!(!board.is_capture(Color::White, Point::new(0, 1)));
+        assert!(!board.is_capture(Color::Black, Point::new(5, 5)));
     }
 
     /// Test that when the same group is a neighbour multiple times we do
@@ -388,6 +1171,115 @@ mod tests {
         assert_eq!(board.at(Point::new(2, 0)), Some(Color::Black));
     }
 
+    /// Test that two boards with the same stones, but reached using different
+    /// move orders, are considered to be the same position.
+    #[test]
+    fn position_eq_transposition() {
+        let mut board_1 = Board::new(7.5);
+        let mut board_2 = Board::new(7.5);
+
+        board_1.place(Color::Black, Point::new(3, 3));
+        board_1.place(Color::White, Point::new(15, 15));
+        board_1.place(Color::Black, Point::new(3, 15));
+        board_1.place(Color::White, Point::new(15, 3));
+
+        board_2.place(Color::Black, Point::new(3, 15));
+        board_2.place(Color::White, Point::new(15, 3));
+        board_2.place(Color::Black, Point::new(3, 3));
+        board_2.place(Color::White, Point::new(15, 15));
+
+        assert!(board_1.position_eq(&board_2));
+        assert!(board_1 != board_2);
+    }
+
+    /// Test that two boards with the same stones, but a different player to
+    /// move, are not considered to be the same position.
+    #[test]
+    fn position_eq_to_move() {
+        let mut board_1 = Board::new(7.5);
+        let mut board_2 = Board::new(7.5);
+
+        board_1.place(Color::Black, Point::new(3, 3));
+        board_1.place(Color::White, Point::new(15, 15));
+
+        board_2.place(Color::White, Point::new(15, 15));
+        board_2.place(Color::Black, Point::new(3, 3));
+
+        assert!(!board_1.position_eq(&board_2));
+    }
+
+    /// Test that two boards with the same stones where only one of them has
+    /// an active ko are not considered to be the same position.
+    #[test]
+    fn position_eq_ko() {
+        let mut board_1 = Board::new(7.5);
+        let mut board_2 = Board::new(7.5);
+
+        board_1.place(Color::Black, Point::new(0, 0));
+        board_1.place(Color::Black, Point::new(0, 2));
+        board_1.place(Color::Black, Point::new(1, 1));
+        board_1.place(Color::White, Point::new(1, 0));
+        board_1.place(Color::White, Point::new(0, 1));
+
+        board_2.place(Color::White, Point::new(1, 0));
+        board_2.place(Color::Black, Point::new(0, 2));
+        board_2.place(Color::Black, Point::new(1, 1));
+        board_2.place(Color::White, Point::new(0, 1));
+
+        assert!(!board_1.is_valid(Color::Black, Point::new(0, 0)));
+        assert!(board_2.is_valid(Color::Black, Point::new(0, 0)));
+        assert!(!board_1.position_eq(&board_2));
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let mut board = Board::new(6.5);
+
+        board.place(Color::Black, Point::new(0, 0));
+        board.place(Color::Black, Point::new(0, 2));
+        board.place(Color::Black, Point::new(1, 1));
+        board.place(Color::White, Point::new(1, 0));
+        board.place(Color::White, Point::new(0, 1));
+        board.place(Color::Black, Point::new(15, 15));
+
+        let bytes = board.to_bytes();
+        assert_eq!(bytes.len(), 244);
+
+        let other = Board::from_bytes(&bytes).expect("could not decode board");
+
+        assert!(other == board);
+        assert!(other.position_eq(&board));
+        assert_eq!(other.komi(), 6.5);
+        assert_eq!(other.count(), board.count());
+        assert_eq!(other.zobrist_hash(), board.zobrist_hash());
+        assert_eq!(other.to_move(), Color::White);
+        assert_eq!(other.history().collect::<Vec<_>>(), board.history().collect::<Vec<_>>());
+        assert_eq!(other.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn bytes_round_trip_ko() {
+        let mut board = Board::new(7.5);
+
+        board.place(Color::Black, Point::new(0, 0));
+        board.place(Color::Black, Point::new(0, 2));
+        board.place(Color::Black, Point::new(1, 1));
+        board.place(Color::White, Point::new(1, 0));
+        board.place(Color::White, Point::new(0, 1));
+
+        let other = Board::from_bytes(&board.to_bytes()).expect("could not decode board");
+
+        assert!(!other.is_valid(Color::Black, Point::new(0, 0)));
+    }
+
+    #[test]
+    fn bytes_invalid() {
+        let bytes = Board::new(7.5).to_bytes();
+
+        assert!(Board::from_bytes(&bytes[1..]).is_none());
+        assert!(Board::from_bytes(&[&[2], &bytes[1..]].concat()).is_none());
+    }
+
     #[test]
     fn black_starts() {
         let board = Board::new(0.5);
@@ -409,4 +1301,20 @@ mod tests {
         board.place(Color::White, Point::new(2, 2));
         assert_eq!(board.to_move(), Color::Black);
     }
+
+    #[test]
+    fn set_to_move() {
+        let mut board = Board::new(0.5);
+
+        board.place(Color::Black, Point::new(3, 3));
+        board.place(Color::Black, Point::new(15, 15));
+        board.set_to_move(Color::White);
+        assert_eq!(board.to_move(), Color::White);
+
+        let other = Board::from_bytes(&board.to_bytes()).expect("could not decode board");
+        assert_eq!(other.to_move(), Color::White);
+
+        board.set_to_move(Color::Black);
+        assert_eq!(board.to_move(), Color::Black);
+    }
 }
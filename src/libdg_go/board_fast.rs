@@ -382,16 +382,18 @@ impl BoardFast {
     /// * `at_point` - the index of a stone in the group to capture
     ///
     #[inline]
-    pub fn capture(&mut self, color: Color, at_point: Point) -> u64 {
+    pub fn capture(&mut self, color: Color, at_point: Point) -> (u64, usize) {
         let mut hash = 0;
+        let mut count = 0;
 
         for other_index in self.block_at(at_point) {
             hash ^= zobrist::TABLE[color as usize][other_index];
             self[other_index].set_color(None);
             self.incr_adjacent_liberties(other_index);
+            count += 1;
         }
 
-        hash
+        (hash, count)
     }
 
     /// Returns the zobrist hash adjustments that are would be made if a stone
@@ -423,7 +425,8 @@ impl BoardFast {
     }
 
     /// Place a some of the given `color` at the given `at_point` on this board. This function
-    /// assume that the given move is valid.
+    /// assume that the given move is valid. Returns the zobrist hash adjustment, and the
+    /// number of opponent stones that were captured.
     ///
     /// # Arguments
     ///
@@ -431,7 +434,7 @@ impl BoardFast {
     /// * `at_point` -
     ///
     #[inline]
-    pub fn place(&mut self, color: Color, at_point: Point) -> u64 {
+    pub fn place(&mut self, color: Color, at_point: Point) -> (u64, usize) {
         // place the stone on the board regardless of whether it is legal
         // or not.
         let num_immediate_liberties = self
@@ -448,6 +451,7 @@ impl BoardFast {
         // connect this stone to any neighbouring groups, and clear the
         // opponents color
         let mut hash = zobrist::TABLE[color as usize][at_point];
+        let mut num_captured = 0;
         let mut seen_blocks = [Point::default(); 4];
         let opponent = color.opposite();
 
@@ -464,13 +468,16 @@ impl BoardFast {
                     seen_blocks[i] = head;
 
                     if !self.has_n_liberty(head, 1) {
-                        hash ^= self.capture(opponent, head);
+                        let (capture_hash, capture_count) = self.capture(opponent, head);
+
+                        hash ^= capture_hash;
+                        num_captured += capture_count;
                     }
                 }
             }
         }
 
-        hash
+        (hash, num_captured)
     }
 
     /// Returns the number of liberties that the given `color` and `at_point` would
@@ -537,6 +544,37 @@ impl BoardFast {
 
         num_liberties
     }
+
+    /// Returns the number of liberties that the block at `at_point` would have
+    /// if the block at `captured_point` was removed from the board.
+    ///
+    /// # Arguments
+    ///
+    /// * `at_point` - a vertex in the block to count the liberties of
+    /// * `captured_point` - a vertex in the block to remove
+    ///
+    pub fn get_n_liberty_after_capture(&self, at_point: Point, captured_point: Point) -> usize {
+        debug_assert!(self[at_point].color().is_some());
+        debug_assert!(self[captured_point].color().is_some());
+
+        let captured_head = self[captured_point].head_point();
+        let mut already_seen = [false; Point::MAX];
+        let mut num_liberties = 0;
+
+        for adjacent_point in self.adjacencies_of(at_point) {
+            if !already_seen[adjacent_point] {
+                already_seen[adjacent_point] = true;
+
+                let is_empty = self[adjacent_point].color().is_none();
+
+                if is_empty || self[adjacent_point].head_point() == captured_head {
+                    num_liberties += 1;
+                }
+            }
+        }
+
+        num_liberties
+    }
 }
 
 #[cfg(test)]
@@ -561,6 +599,20 @@ mod tests {
         assert_eq!(board.get_n_liberty_if(Color::White, Point::new(0, 0)), 2);
     }
 
+    #[test]
+    fn check_get_n_liberty_after_capture() {
+        let mut board = BoardFast::new();
+        board.place(Color::Black, Point::new(0, 0));
+        board.place(Color::Black, Point::new(1, 0));
+        board.place(Color::White, Point::new(0, 1));
+        board.place(Color::White, Point::new(1, 1));
+        board.place(Color::White, Point::new(9, 9));
+
+        assert_eq!(board.get_n_liberty(Point::new(0, 0)), 1);
+        assert_eq!(board.get_n_liberty_after_capture(Point::new(0, 0), Point::new(0, 1)), 3);
+        assert_eq!(board.get_n_liberty_after_capture(Point::new(0, 0), Point::new(9, 9)), 1);
+    }
+
     #[bench]
     fn bench_get_n_liberty_if(b: &mut Bencher) {
         let mut board = BoardFast::new();
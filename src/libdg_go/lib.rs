@@ -43,3 +43,33 @@ pub use self::point_state::*;
 pub use self::iter::IsPartOf;
 
 pub const DEFAULT_KOMI: f32 = 7.5;
+
+/// Returns the conventional komi, under area scoring, for a board of the given
+/// size. This is `7.5` for all board sizes except `9x9` where `7.0` is the
+/// convention.
+///
+/// # Arguments
+///
+/// * `board_size` - the width (and height) of the board
+///
+pub fn recommended_komi(board_size: usize) -> f32 {
+    match board_size {
+        9 => 7.0,
+        _ => DEFAULT_KOMI
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recommended_komi_19x19() {
+        assert_eq!(recommended_komi(19), DEFAULT_KOMI);
+    }
+
+    #[test]
+    fn recommended_komi_9x9() {
+        assert_eq!(recommended_komi(9), 7.0);
+    }
+}
@@ -118,6 +118,29 @@ impl Point {
         TO_Y[self.to_i()] as usize
     }
 
+    /// Returns the point at the given matrix coordinates, where row `0` is
+    /// the top edge of the board and column `0` is the left edge, as the
+    /// board is drawn from an SGF file. So `(row, col)` is the same as the
+    /// point `Point::new(col, row)`, and the SGF coordinate `"da"` is row
+    /// `0` column `3`. This is the same convention as the rows and columns
+    /// of `Board::from_diagram`, but it is upside down compared to the
+    /// pretty-printed board.
+    ///
+    /// # Arguments
+    ///
+    /// * `row` - the row, counted from the top edge
+    /// * `col` - the column, counted from the left edge
+    ///
+    pub fn from_row_col(row: usize, col: usize) -> Self {
+        Self::new(col, row)
+    }
+
+    /// Returns the matrix coordinates `(row, col)` of this point, see
+    /// `from_row_col` for the origin of the coordinates.
+    pub fn to_row_col(&self) -> (usize, usize) {
+        (self.y(), self.x())
+    }
+
     pub fn offset(&self, dx: isize, dy: isize) -> Point {
         debug_assert!(-19 < dx && dx < 19);
         debug_assert!(-19 < dy && dy < 19);
@@ -226,6 +249,22 @@ impl Iterator for PointIter {
 mod tests {
     use std::collections::hash_set::HashSet;
     use super::*;
+    use utils::sgf::{CGoban, SgfCoordinate};
+
+    #[test]
+    fn row_col() {
+        let point = Point::from_row_col(0, 3);
+
+        assert_eq!(CGoban::to_sgf(point), "da");
+        assert_eq!(point.to_row_col(), (0, 3));
+        assert_eq!(CGoban::parse("as").ok().map(|p| p.to_row_col()), Some((18, 0)));
+
+        for point in Point::all() {
+            let (row, col) = point.to_row_col();
+
+            assert_eq!(Point::from_row_col(row, col), point);
+        }
+    }
 
     #[test]
     fn from_packed() {
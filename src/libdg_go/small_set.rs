@@ -30,6 +30,25 @@ impl SmallSet64 {
         SmallSet64 { buf: [0; SET_SIZE], count: 0 }
     }
 
+    /// Returns a set with the given slots, where `count` is the slot that the
+    /// next value will be added to.
+    ///
+    /// # Arguments
+    ///
+    /// * `buf` - the slots of the set
+    /// * `count` - the slot of the next value
+    ///
+    pub fn from_raw_parts(buf: [u64; SET_SIZE], count: usize) -> SmallSet64 {
+        debug_assert!(count < SET_SIZE);
+
+        SmallSet64 { buf, count }
+    }
+
+    /// Returns the slot that the next value will be added to.
+    pub fn position(&self) -> usize {
+        self.count
+    }
+
     /// Adds the given value to this set, removing the oldest value if
     /// the set overflows.
     ///
@@ -46,6 +65,19 @@ impl SmallSet64 {
         }
     }
 
+    /// Returns the `n`:th most recently added value, where `0` is the most
+    /// recently added value. Slots that have never been written to are zero.
+    ///
+    /// # Arguments
+    ///
+    /// * `n` - how many values back to look, must be less than `SET_SIZE`
+    ///
+    pub fn nth_most_recent(&self, n: usize) -> u64 {
+        debug_assert!(n < SET_SIZE);
+
+        self.buf[(self.count + 2 * SET_SIZE - 1 - n) % SET_SIZE]
+    }
+
     /// Returns true if this set contains the given value.
     ///
     /// # Arguments
@@ -106,6 +138,19 @@ mod tests {
         assert!(!s.contains(4));
     }
 
+    #[test]
+    fn nth_most_recent() {
+        let mut s = SmallSet64::new();
+
+        for i in 1..=20 {
+            s.push(i);
+        }
+
+        assert_eq!(s.nth_most_recent(0), 20);
+        assert_eq!(s.nth_most_recent(1), 19);
+        assert_eq!(s.nth_most_recent(SET_SIZE - 1), 5);
+    }
+
     #[bench]
     fn contains_64(b: &mut Bencher) {
         let mut s = SmallSet64::new();
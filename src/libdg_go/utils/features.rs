@@ -17,6 +17,8 @@ use color::Color;
 use point::Point;
 use point_state::Vertex;
 
+use dg_utils::config;
+
 use super::benson::BensonImpl;
 use super::ladder::Ladder;
 use super::symmetry;
@@ -76,12 +78,35 @@ pub trait Features {
 }
 
 pub struct V1<'a> {
-    board: &'a Board
+    board: &'a Board,
+    position_only: bool
 }
 
 impl<'a> V1<'a> {
+    /// Returns features for the given board, which ignore the move history
+    /// if `POSITION_ONLY_FEATURES` is set, see `V1::position_only`.
+    ///
+    /// # Arguments
+    ///
+    /// * `board` -
+    ///
     pub fn new(board: &'a Board) -> Self {
-        Self { board }
+        Self { board, position_only: *config::POSITION_ONLY_FEATURES }
+    }
+
+    /// Returns features for the given board that only depend on the position,
+    /// i.e. the stones on the board, the komi, and the player to move. The
+    /// one-hot historic board state planes, and the super-ko planes, are
+    /// always zero since they depend on the move history. This makes an
+    /// isolated position (such as a tsumego) without any move history get the
+    /// same features as the same position reached through a game.
+    ///
+    /// # Arguments
+    ///
+    /// * `board` -
+    ///
+    pub fn position_only(board: &'a Board) -> Self {
+        Self { board, position_only: true }
     }
 
     /// Returns the number of channels.
@@ -113,6 +138,11 @@ impl<'a> Features for V1<'a> {
     ///  4. Most recent move ( 0)
     ///  5. Most recent move (-1)
     ///
+    /// These are all zero if there is no history available, or if the
+    /// features were created using `V1::position_only`. The same is true
+    /// for the super-ko planes (`3` and `30`), since they also depend on the
+    /// history.
+    ///
     /// ## Liberties
     ///
     ///  6. Our liberties (>= 1)
@@ -167,7 +197,7 @@ impl<'a> Features for V1<'a> {
 
         // board state (one-hot historic)
         for (i, point) in self.board.history.iter().take(2).enumerate() {
-            if point != Point::default() {
+            if point != Point::default() && !self.position_only {
                 let other = symmetry_table[point];
 
                 features[o.index(3+i, other)] = c_1;
@@ -214,7 +244,7 @@ impl<'a> Features for V1<'a> {
                 // pass
             } else if self.board.inner.is_valid(to_move, index) {
                 // is super-ko
-                if self.board._is_ko(to_move, index) {
+                if !self.position_only && self.board._is_ko(to_move, index) {
                     is_ko = c_1;
 
                     features[o.index(29, other)] = c_1;
@@ -232,11 +262,13 @@ impl<'a> Features for V1<'a> {
             }
         }
 
-        // global properties
+        // global properties, which are read from the board so that the color
+        // planes always agree with the board on whose turn it is
         let c_komi = T::from((0.5 + (0.5 * self.board.komi) / 7.5).min(1.0).max(0.0));
+        let board_to_move = self.board.to_move();
 
-        let is_black = if to_move == Color::Black { c_komi } else { c_0 };
-        let is_white = if to_move == Color::White { c_komi } else { c_0 };
+        let is_black = if board_to_move == Color::Black { c_komi } else { c_0 };
+        let is_white = if board_to_move == Color::White { c_komi } else { c_0 };
 
         for index in Point::all() {
             let other = symmetry_table[index];
@@ -277,7 +309,7 @@ impl<'a> V2<'a> {
                 Color::White => self.board.komi()
             };
 
-        (komi / 7.5).max(1.0).min(-1.0)
+        (komi / 7.5).max(-1.0).min(1.0)
     }
 }
 
@@ -471,8 +503,121 @@ impl<'a> Features for LzFeatures<'a> {
 
 #[cfg(test)]
 mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
     use super::*;
 
+    #[test]
+    fn komi_changes_features() {
+        let low = Board::new(0.5);
+        let high = Board::new(7.5);
+
+        assert_ne!(
+            V1::new(&low).get_features::<HWC, f32>(Color::Black, symmetry::Transform::Identity),
+            V1::new(&high).get_features::<HWC, f32>(Color::Black, symmetry::Transform::Identity)
+        );
+        assert_ne!(
+            V2::new(&low).get_features::<HWC, f32>(Color::Black, symmetry::Transform::Identity),
+            V2::new(&high).get_features::<HWC, f32>(Color::Black, symmetry::Transform::Identity)
+        );
+    }
+
+    #[test]
+    fn v2_komi_plane() {
+        let board = Board::new(3.75);
+        let black = V2::new(&board).get_features::<HWC, f32>(Color::Black, symmetry::Transform::Identity);
+        let white = V2::new(&board).get_features::<HWC, f32>(Color::White, symmetry::Transform::Identity);
+
+        assert_eq!(black[1], -0.5);
+        assert_eq!(white[1], 0.5);
+        assert_eq!(V2::new(&Board::new(15.0)).get_features::<HWC, f32>(Color::White, symmetry::Transform::Identity)[1], 1.0);
+    }
+
+    /// Returns a random legal position after `num_moves` moves, and the moves
+    /// that were played to reach it.
+    fn random_position(rng: &mut StdRng, num_moves: usize) -> (Board, Vec<(Color, Point)>) {
+        let mut board = Board::new(7.5);
+        let mut moves = vec! [];
+        let mut to_move = Color::Black;
+
+        for _ in 0..num_moves {
+            let candidates = Point::all()
+                .filter(|&point| board.is_valid(to_move, point))
+                .collect::<Vec<_>>();
+
+            if candidates.is_empty() {
+                break;
+            }
+
+            let point = candidates[rng.gen_range(0..candidates.len())];
+
+            board.place(to_move, point);
+            moves.push((to_move, point));
+            to_move = to_move.opposite();
+        }
+
+        (board, moves)
+    }
+
+    /// Test that extracting the features with a symmetry is the same as
+    /// extracting the identity features, and then permuting every plane.
+    #[test]
+    fn check_features_symmetry_permutation() {
+        let mut rng = StdRng::seed_from_u64(1);
+
+        for num_moves in (0..200).step_by(10) {
+            let (board, _) = random_position(&mut rng, num_moves);
+            let to_move = board.to_move();
+            let identity = V1::new(&board).get_features::<CHW, f32>(to_move, symmetry::Transform::Identity);
+
+            for &t in &symmetry::ALL {
+                let features = V1::new(&board).get_features::<CHW, f32>(to_move, t);
+
+                for c in 0..V1::num_features() {
+                    for point in Point::all() {
+                        let other = t.apply(point);
+
+                        assert_eq!(
+                            identity[CHW.index(c, point)],
+                            features[CHW.index(c, other)],
+                            "plane {} at {:?} with {:?} after {} moves", c, point, t, num_moves
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Test that extracting the features of a transformed position is the same
+    /// as extracting the features of the original position with that symmetry.
+    #[test]
+    fn check_features_symmetry_board() {
+        let mut rng = StdRng::seed_from_u64(2);
+
+        for num_moves in (0..200).step_by(10) {
+            let (board, moves) = random_position(&mut rng, num_moves);
+            let to_move = board.to_move();
+
+            for &t in &symmetry::ALL {
+                let mut other = Board::new(board.komi());
+
+                for &(color, point) in &moves {
+                    other.place(color, t.apply(point));
+                }
+
+                let expected = V1::new(&board).get_features::<CHW, f32>(to_move, t);
+                let features = V1::new(&other).get_features::<CHW, f32>(to_move, symmetry::Transform::Identity);
+
+                for (i, (a, b)) in expected.iter().zip(features.iter()).enumerate() {
+                    assert_eq!(
+                        a, b,
+                        "plane {} at {:?} with {:?} after {} moves", i / 361, Point::from_packed_parts(i % 361), t, num_moves
+                    );
+                }
+            }
+        }
+    }
+
     #[test]
     fn check_features_chw() {
         let board = Board::new(0.5);
@@ -490,4 +635,48 @@ mod tests {
 
         assert_eq!(features.len(), V1::size());
     }
+
+    #[test]
+    fn check_features_to_move() {
+        let mut board = Board::new(7.5);
+        board.set_to_move(Color::White);
+
+        let features = V1::new(&board)
+            .get_features::<CHW, f32>(Color::White, symmetry::Transform::Identity);
+
+        assert_eq!(features[0], 0.0);
+        assert_eq!(features[361], 1.0);
+    }
+
+    #[test]
+    fn check_features_to_move_from_board() {
+        let mut board = Board::new(7.5);
+        board.set_to_move(Color::White);
+
+        let features = V1::new(&board)
+            .get_features::<CHW, f32>(Color::Black, symmetry::Transform::Identity);
+
+        assert_eq!(features[0], 0.0);
+        assert_eq!(features[361], 1.0);
+    }
+
+    #[test]
+    fn check_features_position_only() {
+        let mut board = Board::new(7.5);
+        board.place(Color::Black, Point::new(3, 3));
+        board.place(Color::White, Point::new(15, 15));
+
+        let mut other = Board::new(7.5);
+        other.place(Color::White, Point::new(15, 15));
+        other.place(Color::Black, Point::new(3, 3));
+        other.set_to_move(Color::Black);
+
+        let features = V1::position_only(&board)
+            .get_features::<CHW, f32>(Color::Black, symmetry::Transform::Identity);
+        let other_features = V1::position_only(&other)
+            .get_features::<CHW, f32>(Color::Black, symmetry::Transform::Identity);
+
+        assert!(features[(3*361)..(5*361)].iter().all(|&x| x == 0.0));
+        assert_eq!(features, other_features);
+    }
 }
@@ -56,9 +56,11 @@ fn _is_ladder_capture(mut board: BoardFast, color: Color, at_point: Point) -> bo
     // if any of the neighbouring opponent groups were reduced to one
     // liberty (and it cannot counter capture a group) then extend into
     // that liberty. if no such group exists then this is not a ladder
-    // capturing move.
+    // capturing move. Every such group is checked, since which one is
+    // found first depends on the orientation of the board.
     let opponent = Some(color.opposite());
-    let opponent_index = board.adjacent_to(at_point)
+
+    board.adjacent_to(at_point)
         .filter_map(|other_point| {
             if board[other_point].color() == opponent {
                 let is_in_atari = !board.has_n_liberty(other_point, 2);
@@ -73,14 +75,19 @@ fn _is_ladder_capture(mut board: BoardFast, color: Color, at_point: Point) -> bo
             }
         })
         .filter(|&opponent_point| board.is_valid(color.opposite(), opponent_point))
-        .next();
-
-    if opponent_index.is_none() {
-        return false
-    }
-
-    let opponent_index = opponent_index.unwrap();
+        .any(|opponent_point| _is_ladder_extension_capture(board.clone(), color, opponent_point))
+}
 
+/// Returns true if the opponent group that is extended at the given index can
+/// be captured in a serie of ataris.
+///
+/// # Arguments
+///
+/// * `board` - the `vertices` of the board to check
+/// * `color` - the color of the current player
+/// * `opponent_index` - the index of the vertex the opponent extends into
+///
+fn _is_ladder_extension_capture(mut board: BoardFast, color: Color, opponent_index: Point) -> bool {
     board.place(color.opposite(), opponent_index);
 
     // check the number of liberties after extending the group that was put in atari
@@ -19,7 +19,7 @@ use color::Color;
 use point::Point;
 use point_state::Vertex;
 
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum StoneStatus {
@@ -52,6 +52,28 @@ impl ::std::str::FromStr for StoneStatus {
     }
 }
 
+/// The status of a group of stones at the end of a game.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GroupStatus {
+    Alive,
+    Dead,
+    Seki
+}
+
+/// The rules to use when scoring a board.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScoringRules {
+    /// Pure Tromp-Taylor scoring, every stone on the board is counted as
+    /// alive and an empty vertex is counted for a color if it only reaches
+    /// stones of that color.
+    TrompTaylor,
+
+    /// Chinese area scoring, which is the same as Tromp-Taylor scoring except
+    /// that stones that are inside of the opponents (pass-alive) territory
+    /// are removed as dead beforehand.
+    Area
+}
+
 pub trait Score {
     /// Returns true if this game is fully scorable, a game is
     /// defined as scorable if the following conditions hold:
@@ -67,11 +89,38 @@ pub trait Score {
     fn get_scorable_territory(&self) -> Vec<Point>;
 
     /// Returns the score for each player `(black, white)` of the
-    /// current board state according to the Tromp-Taylor rules.
+    /// current board state according to the given rules.
+    ///
+    /// This method does not take any komi into account, you will
+    /// need to add it yourself.
+    ///
+    /// # Arguments
+    ///
+    /// * `rules` - the rules to score the board with
+    ///
+    fn get_score(&self, rules: ScoringRules) -> (usize, usize);
+
+    /// Returns the score for each player `(black, white)` of the
+    /// current board state according to Japanese territory scoring, i.e.
+    /// the empty vertices that are only reachable from one color, plus the
+    /// number of prisoners. Stones inside of an opponents pass-alive
+    /// territory are removed as dead, and counted as prisoners.
     ///
     /// This method does not take any komi into account, you will
     /// need to add it yourself.
-    fn get_score(&self) -> (usize, usize);
+    fn get_japanese_score(&self) -> (usize, usize);
+
+    /// Returns the score margin (from the perspective of black, including
+    /// komi) according to Chinese area scoring, after the groups of every
+    /// stone in `dead` have been removed from the board. This is how the
+    /// score is determined when the dead stones are agreed upon by the
+    /// players, or a referee.
+    ///
+    /// # Arguments
+    ///
+    /// * `dead` - stones whose groups are dead, empty vertices are ignored
+    ///
+    fn chinese_score_with_dead(&self, dead: &[Point]) -> f32;
 
     /// Returns the score for each player `(black, white)` of the
     /// current board state after any stones that are not part of
@@ -92,13 +141,31 @@ pub trait Score {
     ///
     /// - **alive** if the stone is present on both
     /// - **dead** if the stone is not present in the _finished_ board
-    /// - **seki** if the stone is present on both, but not scorable
+    /// - **seki** if the stone is present on both, but not scorable, and its
+    ///   group is in seki with an opponent group (see `is_seki`)
     ///
     /// # Arguments
     ///
     /// * `finished` - A copy of this board that has been played to
     ///   finish, using some heuristic
     fn get_stone_status(&self, finished: &Board) -> Vec<(Point, Vec<StoneStatus>)>;
+
+    /// Returns the status of the group of every stone on the board, which is
+    /// determined by the first of the following that applies:
+    ///
+    /// - **alive** if the group is pass-alive
+    /// - **dead** if the stone is inside of a pass-alive opponent eye
+    /// - **dead** if the stone is not present in the _finished_ board
+    /// - **seki** if the group is not pass-alive in the _finished_ board, and
+    ///   is in seki with an opponent group (see `is_seki`)
+    /// - **alive** otherwise
+    ///
+    /// # Arguments
+    ///
+    /// * `finished` - A copy of this board that has been played to
+    ///   finish, using some heuristic
+    ///
+    fn get_group_status(&self, finished: &Board) -> HashMap<Point, GroupStatus>;
 }
 
 impl Score for Board {
@@ -127,12 +194,65 @@ impl Score for Board {
             .collect()
     }
 
-    fn get_score(&self) -> (usize, usize) {
-        if self.zobrist_hash != 0 {  // at least one stone has been played
-            get_tt_score(&self.inner)
-        } else {
+    fn get_score(&self, rules: ScoringRules) -> (usize, usize) {
+        if self.zobrist_hash == 0 {  // no stones has been played
             (0, 0)
+        } else if rules == ScoringRules::Area {
+            let benson_black = BensonImpl::new(self, Color::Black);
+            let benson_white = BensonImpl::new(self, Color::White);
+            let cleaned = clear_dead_stones(self, &benson_black, &benson_white);
+
+            get_tt_score(&cleaned.inner)
+        } else {
+            get_tt_score(&self.inner)
+        }
+    }
+
+    fn chinese_score_with_dead(&self, dead: &[Point]) -> f32 {
+        let mut cleaned = self.clone();
+
+        for &point in dead {
+            if self.inner[point].color().is_some() {
+                for other_point in self.inner.block_at(point) {
+                    cleaned.inner[other_point].set_color(None);
+                }
+            }
+        }
+
+        let (black, white) = get_tt_score(&cleaned.inner);
+
+        black as f32 - white as f32 - self.komi()
+    }
+
+    fn get_japanese_score(&self) -> (usize, usize) {
+        let benson_black = BensonImpl::new(self, Color::Black);
+        let benson_white = BensonImpl::new(self, Color::White);
+        let cleaned = clear_dead_stones(self, &benson_black, &benson_white);
+        let black_distance = get_territory_distance(&cleaned.inner, Color::Black);
+        let white_distance = get_territory_distance(&cleaned.inner, Color::White);
+        let mut black = self.prisoners(Color::Black);
+        let mut white = self.prisoners(Color::White);
+
+        for point in Point::all() {
+            if cleaned.inner[point].color().is_some() {
+                continue;
+            }
+
+            // a dead stone is counted as a prisoner
+            match self.inner[point].color() {
+                Some(Color::Black) => { white += 1 },
+                Some(Color::White) => { black += 1 },
+                None => {}
+            }
+
+            if white_distance[point] == 0xff && black_distance[point] != 0xff {
+                black += 1; // only reachable from black
+            } else if black_distance[point] == 0xff && white_distance[point] != 0xff {
+                white += 1; // only reachable from white
+            }
         }
+
+        (black, white)
     }
 
     fn get_guess_score(&self, finished: &Board) -> (usize, usize) {
@@ -155,20 +275,24 @@ impl Score for Board {
             if self.inner[point].color() == Some(Color::White) {
                 if benson_white.is_alive(point) {
                     status_list.push((point, vec! [StoneStatus::Alive, StoneStatus::WhiteTerritory]));
-                } else if benson_black.is_eye(point) {
+                } else if benson_black.is_eye(point) || finished.inner[point].color() != Some(Color::White) {
                     status_list.push((point, vec! [StoneStatus::Dead, StoneStatus::BlackTerritory]));
-                } else {
+                } else if is_seki(finished, &benson_black, point) {
                     status_list.push((point, vec! [StoneStatus::Seki, StoneStatus::WhiteTerritory]));
+                } else {
+                    status_list.push((point, vec! [StoneStatus::Alive, StoneStatus::WhiteTerritory]));
                 }
             }
 
             if self.inner[point].color() == Some(Color::Black) {
                 if benson_black.is_alive(point) {
                     status_list.push((point, vec! [StoneStatus::Alive, StoneStatus::BlackTerritory]));
-                } else if benson_white.is_eye(point) {
+                } else if benson_white.is_eye(point) || finished.inner[point].color() != Some(Color::Black) {
                     status_list.push((point, vec! [StoneStatus::Dead, StoneStatus::WhiteTerritory]));
-                } else {
+                } else if is_seki(finished, &benson_white, point) {
                     status_list.push((point, vec! [StoneStatus::Seki, StoneStatus::BlackTerritory]));
+                } else {
+                    status_list.push((point, vec! [StoneStatus::Alive, StoneStatus::BlackTerritory]));
                 }
             }
 
@@ -183,6 +307,75 @@ impl Score for Board {
 
         status_list
     }
+
+    fn get_group_status(&self, finished: &Board) -> HashMap<Point, GroupStatus> {
+        let benson_black = BensonImpl::new(self, Color::Black);
+        let benson_white = BensonImpl::new(self, Color::White);
+        let finished_black = BensonImpl::new(finished, Color::Black);
+        let finished_white = BensonImpl::new(finished, Color::White);
+        let mut status_map = HashMap::with_capacity(361);
+
+        for point in Point::all() {
+            let color = match self.inner[point].color() {
+                Some(color) => color,
+                None => { continue }
+            };
+            let (benson_our, benson_opp, finished_our, finished_opp) = match color {
+                Color::Black => (&benson_black, &benson_white, &finished_black, &finished_white),
+                Color::White => (&benson_white, &benson_black, &finished_white, &finished_black)
+            };
+
+            let status = if benson_our.is_alive(point) {
+                GroupStatus::Alive
+            } else if benson_opp.is_eye(point) {
+                GroupStatus::Dead
+            } else if finished.inner[point].color() != Some(color) {
+                GroupStatus::Dead
+            } else if !finished_our.is_alive(point) && is_seki(finished, finished_opp, point) {
+                GroupStatus::Seki
+            } else {
+                GroupStatus::Alive
+            };
+
+            status_map.insert(point, status);
+        }
+
+        status_map
+    }
+}
+
+/// Returns true if the group at the given point is in seki, i.e. it shares at
+/// least one liberty with an opponent group that is not pass-alive, and
+/// neither player can play on any of the shared liberties without putting
+/// their own group in atari. Neither of the two groups can therefore be
+/// killed by the opponent.
+///
+/// # Arguments
+///
+/// * `board` - the board to check
+/// * `benson_opp` - the pass-alive groups of the opponent on `board`
+/// * `point` - a stone in the group to check
+///
+fn is_seki(board: &Board, benson_opp: &BensonImpl, point: Point) -> bool {
+    let color = match board.inner[point].color() {
+        Some(color) => color,
+        None => { return false }
+    };
+    let opponent = color.opposite();
+    let is_self_atari = |color: Color, liberty: Point| {
+        !board.inner.is_valid(color, liberty) || board.inner.get_n_liberty_if(color, liberty) < 2
+    };
+    let mut shared_liberties = board.inner.liberties_of(point)
+        .filter(|&liberty| {
+            board.inner.adjacent_to(liberty).any(|other| {
+                board.inner[other].color() == Some(opponent) && !benson_opp.is_alive(other)
+            })
+        })
+        .peekable();
+
+    shared_liberties.peek().is_some() && shared_liberties.all(|liberty| {
+        is_self_atari(color, liberty) && is_self_atari(opponent, liberty)
+    })
 }
 
 /// Returns a clone of the given `board` with all stones that are inside of an
@@ -210,6 +403,33 @@ fn clear_board(board: &Board, benson_black: &BensonImpl, benson_white: &BensonIm
     cleaned
 }
 
+/// Returns a clone of the given `board` with all stones that are not alive,
+/// and inside of an opponents eye, removed.
+///
+/// # Arguments
+///
+/// * `board` -
+/// * `benson_black` -
+/// * `benson_white` -
+///
+fn clear_dead_stones(board: &Board, benson_black: &BensonImpl, benson_white: &BensonImpl) -> Board {
+    let mut cleaned = board.clone();
+
+    for point in Point::all() {
+        let is_dead = match board.inner[point].color() {
+            Some(Color::Black) => !benson_black.is_alive(point) && benson_white.is_eye(point),
+            Some(Color::White) => !benson_white.is_alive(point) && benson_black.is_eye(point),
+            None => false
+        };
+
+        if is_dead {
+            cleaned.inner[point].set_color(None);
+        }
+    }
+
+    cleaned
+}
+
 /// Returns the score of the given board according to the Tromp-Taylor
 /// rules.
 ///
@@ -280,6 +500,57 @@ fn get_territory_distance(board: &BoardFast, color: Color) -> [u8; Point::MAX] {
     territory
 }
 
+/// The ownership above which a vertex is counted for black, and below the
+/// negation of which a vertex is counted for white, in `score_from_ownership`.
+const OWNERSHIP_THRESHOLD: f32 = 0.5;
+
+/// Returns the score margin (from the perspective of black, including komi) of
+/// the given board according to the given ownership, and the stones that are
+/// dead. Every vertex whose ownership strongly favors a color is counted for
+/// that color, and a stone whose ownership strongly favors its opponent is
+/// dead. A stone whose ownership is uncertain is counted as alive, while empty
+/// vertices where the ownership is uncertain are not counted for either color.
+///
+/// # Arguments
+///
+/// * `board` - the board to score
+/// * `ownership` - the ownership of each vertex (packed index) in `[-1, 1]`,
+///   where `1` is black and `-1` is white
+/// * `komi` - the komi to subtract from the score of black
+///
+pub fn score_from_ownership(board: &Board, ownership: &[f32], komi: f32) -> (f32, Vec<Point>) {
+    debug_assert!(ownership.len() >= 361);
+
+    let mut black = 0;
+    let mut white = 0;
+    let mut dead = vec! [];
+
+    for point in Point::all() {
+        let owner = ownership[point.to_packed_index()];
+        let owner = if owner >= OWNERSHIP_THRESHOLD {
+            Some(Color::Black)
+        } else if owner <= -OWNERSHIP_THRESHOLD {
+            Some(Color::White)
+        } else {
+            board.at(point)
+        };
+
+        match owner {
+            Some(Color::Black) => { black += 1 },
+            Some(Color::White) => { white += 1 },
+            None => {}
+        }
+
+        if let Some(color) = board.at(point) {
+            if owner == Some(color.opposite()) {
+                dead.push(point);
+            }
+        }
+    }
+
+    (black as f32 - white as f32 - komi, dead)
+}
+
 #[cfg(test)]
 mod tests {
     use board::*;
@@ -292,7 +563,7 @@ mod tests {
         board.place(Color::Black, Point::new(0, 0));
 
         assert!(!board.is_scorable());
-        assert_eq!(board.get_score(), (361, 0));
+        assert_eq!(board.get_score(ScoringRules::TrompTaylor), (361, 0));
     }
 
     #[test]
@@ -301,7 +572,7 @@ mod tests {
         board.place(Color::White, Point::new(0, 0));
 
         assert!(!board.is_scorable());
-        assert_eq!(board.get_score(), (0, 361));
+        assert_eq!(board.get_score(ScoringRules::TrompTaylor), (0, 361));
     }
 
     #[test]
@@ -322,7 +593,30 @@ mod tests {
         board.place(Color::Black, Point::new(2, 4));
 
         assert!(!board.is_scorable());
-        assert_eq!(board.get_score(), (353, 8));
+        assert_eq!(board.get_score(ScoringRules::TrompTaylor), (353, 8));
+    }
+
+    #[test]
+    fn japanese_score_prisoners() {
+        let mut board = Board::new(7.5);
+        board.place(Color::Black, Point::new( 9,  9));
+        board.place(Color::White, Point::new( 8,  9));
+        board.place(Color::White, Point::new(10,  9));
+        board.place(Color::White, Point::new( 9,  8));
+        board.place(Color::White, Point::new( 9, 10));
+
+        assert_eq!(board.get_japanese_score(), (0, 358));
+    }
+
+    #[test]
+    fn japanese_score_dead_stone() {
+        let board = Board::from_diagram(7.5, "
+            X X X X X X
+            X . X O . X
+            X X X X X X
+        ").expect("could not parse diagram");
+
+        assert_eq!(board.get_japanese_score(), (347, 0));
     }
 
     #[test]
@@ -335,7 +629,7 @@ mod tests {
         }
 
         assert!(board.is_scorable());
-        assert_eq!(board.get_score(), (361, 0));
+        assert_eq!(board.get_score(ScoringRules::TrompTaylor), (361, 0));
     }
 
     #[test]
@@ -348,7 +642,7 @@ mod tests {
         }
 
         assert!(board.is_scorable());
-        assert_eq!(board.get_score(), (0, 361));
+        assert_eq!(board.get_score(ScoringRules::TrompTaylor), (0, 361));
     }
 
     #[test]
@@ -387,7 +681,7 @@ mod tests {
             } else if point == Point::new(9, 9) {
                 assert_eq!(
                     statuses,
-                    vec! [StoneStatus::Seki, StoneStatus::BlackTerritory],
+                    vec! [StoneStatus::Alive, StoneStatus::BlackTerritory],
                     "{:?} {:?}",
                     point,
                     statuses
@@ -403,4 +697,128 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn area_removes_dead_stones() {
+        let mut board = Board::new(0.5);
+        board.place(Color::White, Point::new(0, 1));
+        board.place(Color::White, Point::new(1, 1));
+        board.place(Color::White, Point::new(2, 0));
+        board.place(Color::White, Point::new(2, 1));
+        board.place(Color::White, Point::new(3, 1));
+        board.place(Color::White, Point::new(4, 0));
+        board.place(Color::White, Point::new(4, 1));
+
+        board.place(Color::Black, Point::new(0, 0));
+        board.place(Color::Black, Point::new(9, 9));
+
+        assert_eq!(board.get_score(ScoringRules::TrompTaylor), (2, 8));
+        assert_eq!(board.get_score(ScoringRules::Area), (1, 10));
+    }
+
+    #[test]
+    fn group_status() {
+        let mut board = Board::new(0.5);
+        board.place(Color::White, Point::new(0, 1));
+        board.place(Color::White, Point::new(1, 1));
+        board.place(Color::White, Point::new(2, 0));
+        board.place(Color::White, Point::new(2, 1));
+        board.place(Color::White, Point::new(3, 1));
+        board.place(Color::White, Point::new(4, 0));
+        board.place(Color::White, Point::new(4, 1));
+        board.place(Color::White, Point::new(9, 11));
+
+        board.place(Color::Black, Point::new(0, 0));
+        board.place(Color::Black, Point::new(9, 9));
+        board.place(Color::Black, Point::new(15, 15));
+
+        let mut finished = board.clone();
+        finished.inner[Point::new(15, 15)].set_color(None);
+
+        let status_map = board.get_group_status(&finished);
+
+        assert_eq!(status_map.len(), 11);
+        assert_eq!(status_map[&Point::new(2, 1)], GroupStatus::Alive);
+        assert_eq!(status_map[&Point::new(0, 0)], GroupStatus::Dead);
+        assert_eq!(status_map[&Point::new(15, 15)], GroupStatus::Dead);
+        assert_eq!(status_map[&Point::new(9, 9)], GroupStatus::Alive);
+        assert_eq!(status_map[&Point::new(9, 11)], GroupStatus::Alive);
+    }
+
+    /// Test that two groups without any eyes that share two liberties, where
+    /// filling either liberty puts your own group in atari, are in seki but
+    /// the walls surrounding them are not.
+    #[test]
+    fn seki_shape() {
+        let board = Board::from_diagram(7.5, "
+            . O X . O X .
+            . O X . O X .
+            . O X X O X .
+            . O O O X X .
+        ").expect("could not parse diagram");
+
+        let status_map = board.get_group_status(&board);
+
+        assert_eq!(status_map[&Point::new(2, 0)], GroupStatus::Seki);
+        assert_eq!(status_map[&Point::new(4, 0)], GroupStatus::Seki);
+        assert_eq!(status_map[&Point::new(1, 0)], GroupStatus::Alive);
+        assert_eq!(status_map[&Point::new(5, 0)], GroupStatus::Alive);
+
+        let stone_status = board.get_stone_status(&board).into_iter().collect::<HashMap<_, _>>();
+
+        assert_eq!(stone_status[&Point::new(3, 2)], vec! [StoneStatus::Seki, StoneStatus::BlackTerritory]);
+        assert_eq!(stone_status[&Point::new(4, 2)], vec! [StoneStatus::Seki, StoneStatus::WhiteTerritory]);
+        assert_eq!(stone_status[&Point::new(3, 3)], vec! [StoneStatus::Alive, StoneStatus::WhiteTerritory]);
+        assert_eq!(stone_status[&Point::new(4, 3)], vec! [StoneStatus::Alive, StoneStatus::BlackTerritory]);
+    }
+
+    #[test]
+    fn score_from_ownership_dead_stone() {
+        let mut board = Board::new(7.5);
+        let mut ownership = vec! [0.0; 361];
+
+        for point in Point::all() {
+            ownership[point.to_packed_index()] = if point.x() <= 9 { 0.9 } else { -0.9 };
+        }
+
+        for y in 0..19 {
+            board.place(Color::Black, Point::new(9, y));
+            board.place(Color::White, Point::new(10, y));
+        }
+
+        board.place(Color::Black, Point::new(15, 15));
+
+        let (margin, dead) = score_from_ownership(&board, &ownership, 7.5);
+
+        assert_eq!(margin, 190.0 - 171.0 - 7.5);
+        assert_eq!(dead, vec! [Point::new(15, 15)]);
+    }
+
+    #[test]
+    fn score_from_ownership_uncertain() {
+        let mut board = Board::new(7.5);
+        board.place(Color::White, Point::new(3, 3));
+
+        let (margin, dead) = score_from_ownership(&board, &[0.1; 361], 7.5);
+
+        assert_eq!(margin, -1.0 - 7.5);
+        assert!(dead.is_empty());
+    }
+
+    #[test]
+    fn chinese_score_with_dead() {
+        let mut board = Board::new(7.5);
+
+        for y in 0..19 {
+            board.place(Color::Black, Point::new(9, y));
+            board.place(Color::White, Point::new(10, y));
+        }
+
+        board.place(Color::Black, Point::new(15, 15));
+        board.place(Color::Black, Point::new(15, 16));
+
+        assert_eq!(board.chinese_score_with_dead(&[]), 192.0 - 19.0 - 7.5);
+        assert_eq!(board.chinese_score_with_dead(&[Point::new(15, 16)]), 190.0 - 171.0 - 7.5);
+        assert_eq!(board.chinese_score_with_dead(&[Point::new(15, 16), Point::new(0, 0)]), 190.0 - 171.0 - 7.5);
+    }
 }
@@ -144,8 +144,32 @@ fn skip_until_next<'a>(bytes: &'a [u8], start_at: &mut usize, goal: u8) -> &'a [
         .unwrap_or_else(|| &bytes[0..0])
 }
 
-fn peek_forward2(bytes: &[u8], at_index: usize, peek_1: u8, peek_2: u8) -> bool {
-    at_index < bytes.len() - 2 && bytes[at_index] == peek_1 && bytes[at_index+1] == peek_2
+/// Returns the bytes until the next occurence of `goal` that is not escaped
+/// by a backslash, and advance `start_at` past it.
+///
+/// # Arguments
+///
+/// * `bytes` -
+/// * `start_at` -
+/// * `goal` -
+///
+fn skip_until_unescaped<'a>(bytes: &'a [u8], start_at: &mut usize, goal: u8) -> &'a [u8] {
+    let starting_point = *start_at;
+    let mut i = starting_point;
+
+    while i < bytes.len() {
+        if bytes[i] == b'\\' {
+            i += 2;
+        } else if bytes[i] == goal {
+            *start_at = i + 1;
+
+            return &bytes[starting_point..i];
+        } else {
+            i += 1;
+        }
+    }
+
+    &bytes[0..0]
 }
 
 fn find_next_property<'a, 'b>(bytes: &'a [u8], start_at: &mut usize) -> Option<(&'b [u8], &'b [u8])>
@@ -153,8 +177,8 @@ fn find_next_property<'a, 'b>(bytes: &'a [u8], start_at: &mut usize) -> Option<(
 {
     skip_ws(bytes, start_at);
 
-    let key = skip_until_next(bytes, start_at, b'[');
-    let value = skip_until_next(bytes, start_at, b']');
+    let key = trim_ws(skip_until_next(bytes, start_at, b'['));
+    let value = skip_until_unescaped(bytes, start_at, b']');
 
     if key.is_empty() {
         None
@@ -178,8 +202,62 @@ fn find_next_vertex(bytes: &[u8], start_at: &mut usize) -> Option<(Color, Point)
     }
 }
 
+/// Returns the given bytes without any leading or trailing whitespace.
+///
+/// # Arguments
+///
+/// * `bytes` -
+///
+fn trim_ws(bytes: &[u8]) -> &[u8] {
+    let is_ws = |ch: &u8| *ch == 32 || *ch == 9 || *ch == 10 || *ch == 13;
+    let begin = bytes.iter().position(|ch| !is_ws(ch)).unwrap_or(bytes.len());
+    let end = bytes.iter().rposition(|ch| !is_ws(ch)).map(|i| i + 1).unwrap_or(begin);
+
+    &bytes[begin..end]
+}
+
+/// Apply the given property to the board if it is a setup property, i.e. one
+/// of `AB` or `AW` which adds a stone of the given color to the board, or `PL`
+/// which sets the player to move. The stones are added with
+/// `Board::add_setup_stone`, so they are not part of the history, and a stone
+/// that would capture is rejected. Any other property is ignored.
+///
+/// # Arguments
+///
+/// * `board` - the board to apply the property to
+/// * `key` - the identifier of the property
+/// * `value` - the value of the property
+///
+fn apply_setup(board: &mut Board, key: &[u8], value: &[u8]) -> Result<(), SgfError> {
+    let color = match key {
+        b"AB" => Color::Black,
+        b"AW" => Color::White,
+        b"PL" => {
+            let color = ::std::str::from_utf8(value).ok()
+                .and_then(|x| x.trim().parse::<Color>().ok())
+                .ok_or(SgfError::ParseError)?;
+
+            board.set_to_move(color);
+            return Ok(());
+        },
+        _ => { return Ok(()); }
+    };
+
+    let point = ::std::str::from_utf8(value).ok()
+        .and_then(|x| CGoban::parse(x.trim()).ok())
+        .ok_or(SgfError::ParseError)?;
+
+    if point == Point::default() || board.add_setup_stone(color, point) {
+        Ok(())
+    } else {
+        Err(SgfError::IllegalMove)
+    }
+}
+
 /// Returns the next occurence of a vertex in the given SGF file, where a
-/// vertex is denoted by the pattern `;[BW]\[(...)\]`.
+/// vertex is denoted by the pattern `;[BW]\[(...)\]`. The policy (`P`) and
+/// value (`V`) of the vertex are taken from the remaining properties of the
+/// same node, regardless of their order.
 ///
 /// # Arguments
 ///
@@ -187,29 +265,31 @@ fn find_next_vertex(bytes: &[u8], start_at: &mut usize) -> Option<(Color, Point)
 /// * `start_at` -
 ///
 fn find_next_move<'a>(bytes: &'a [u8], start_at: &mut usize) -> Option<SgfMatch<'a>> {
-    while *start_at < bytes.len() - 4 {
+    while *start_at + 4 <= bytes.len() {
         if bytes[*start_at] == b';' {
             let starting_index = *start_at;
 
             *start_at += 1;
             if let Some((color, point)) = find_next_vertex(bytes, start_at) {
-                skip_ws(bytes, start_at);
-                let policy = if peek_forward2(bytes, *start_at, b'P', b'[') {
-                    find_next_property(bytes, start_at).and_then(|x| {
-                        Some(x.1)
-                    })
-                } else {
-                    None
-                };
-
-                skip_ws(bytes, start_at);
-                let value = if peek_forward2(bytes, *start_at, b'V', b'[') {
-                    find_next_property(bytes, start_at).and_then(|x| {
-                        ::std::str::from_utf8(x.1).ok().and_then(|x| x.parse::<f32>().ok())
-                    })
-                } else {
-                    None
-                };
+                let mut policy = None;
+                let mut value = None;
+
+                loop {
+                    skip_ws(bytes, start_at);
+
+                    if *start_at >= bytes.len() || bytes[*start_at] == b';' || bytes[*start_at] == b'(' || bytes[*start_at] == b')' {
+                        break;
+                    }
+
+                    match find_next_property(bytes, start_at) {
+                        Some((b"P", x)) => { policy = Some(x); },
+                        Some((b"V", x)) => {
+                            value = ::std::str::from_utf8(x).ok().and_then(|x| x.parse::<f32>().ok());
+                        },
+                        Some(_) => {},
+                        None => break
+                    }
+                }
 
                 return Some(SgfMatch {
                     color: color,
@@ -246,16 +326,37 @@ impl<'a> Iterator for Sgf<'a> {
         let starting_index = if self.index == 0 { 0 } else { self.index - 1 };
 
         if let Some(m) = find_next_move(self.content, &mut self.index) {
-            // unwind the stack for the nested game tree
+            // unwind the stack for the nested game tree, and apply any setup
+            // properties to the board they belong to
+            let content = self.content;
             let mut in_property = false;
+            let mut key_begin = starting_index;
+            let mut key: &[u8] = &[];
+            let mut value_begin = starting_index;
 
             for i in starting_index..m.begin {
-                if i == 0 || self.content[i-1] != b'\'' {
-                    if self.content[i] == b'[' {
+                if i == 0 || content[i-1] != b'\'' {
+                    if !in_property && content[i] == b'[' {
+                        let next_key = trim_ws(&content[key_begin..i]);
+
+                        if !next_key.is_empty() {
+                            key = next_key;
+                        }
+
                         in_property = true;
-                    } else if self.content[i] == b']' {
+                        value_begin = i + 1;
+                    } else if in_property && content[i] == b']' {
                         in_property = false;
-                    } else if !in_property && self.content[i] == b'(' {
+                        key_begin = i + 1;
+
+                        if let Some(board) = self.board.last_mut() {
+                            if let Err(err) = apply_setup(board, key, &content[value_begin..i]) {
+                                return Some(Err(err));
+                            }
+                        } else {
+                            return Some(Err(SgfError::ParseError));
+                        }
+                    } else if !in_property && content[i] == b'(' {
                         if self.board.is_empty() {
                             return Some(Err(SgfError::ParseError));
                         }
@@ -263,26 +364,35 @@ impl<'a> Iterator for Sgf<'a> {
                         let prev_board = self.board.last().unwrap().clone();
 
                         self.board.push(prev_board);
-                    } else if !in_property && self.content[i] == b')' {
+                        key = &[];
+                        key_begin = i + 1;
+                    } else if !in_property && content[i] == b')' {
                         if self.board.is_empty() {
                             return Some(Err(SgfError::ParseError));
                         }
 
                         self.board.pop();
+                        key = &[];
+                        key_begin = i + 1;
+                    } else if !in_property && (content[i] == b';' || content[i] == b']') {
+                        key = &[];
+                        key_begin = i + 1;
                     }
                 }
             }
 
-            // if we have a valid, or pass, move then advance the board state
+            // if we have a valid, or pass, move then advance the board state. A
+            // pass is recorded too so that the move history matches a board
+            // that was reached by playing the same moves.
             let board = self.board.last_mut().unwrap();
             let prev_board = board.clone();
 
-            if m.point != Point::default() {
-                if board.is_valid(m.color, m.point) {
-                    board.place(m.color, m.point);
-                } else {
-                    return Some(Err(SgfError::IllegalMove));
-                }
+            if m.point == Point::default() {
+                board.pass(m.color);
+            } else if board.is_valid(m.color, m.point) {
+                board.place(m.color, m.point);
+            } else {
+                return Some(Err(SgfError::IllegalMove));
             }
 
             Some(Ok(SgfEntry {
@@ -371,6 +481,8 @@ pub fn is_scored(content: &str) -> bool {
 mod tests {
     use test::{black_box, Bencher};
     use super::*;
+    use utils::features::{self, Features, HWC};
+    use utils::symmetry::Transform;
 
     #[test]
     fn simple_sgf() {
@@ -388,6 +500,76 @@ mod tests {
         assert_eq!(moves[1].point, Point::new(3, 3));
     }
 
+    #[test]
+    fn policy_after_other_properties() {
+        let moves = Sgf::new(b"(;B[dp]C[a \\] ;b]TR[dd]P[abc]V[0.5];W[dd]V[-0.25]P[def];B[])", 7.5)
+            .map(|x| x.ok().unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(moves.len(), 3);
+        assert_eq!(moves[0].policy, Some(&b"abc"[..]));
+        assert_eq!(moves[0].value, Some(0.5));
+        assert_eq!(moves[1].point, Point::new(3, 3));
+        assert_eq!(moves[1].policy, Some(&b"def"[..]));
+        assert_eq!(moves[1].value, Some(-0.25));
+        assert_eq!(moves[2].point, Point::default());
+    }
+
+    #[test]
+    fn same_features_as_replay() {
+        let moves = Sgf::new(b"(;B[dp];W[dd];B[];W[pp];B[pd];W[dj];B[jj])", 7.5)
+            .map(|x| x.ok().unwrap())
+            .collect::<Vec<_>>();
+        let mut board = Board::new(7.5);
+
+        board.place(Color::Black, Point::new(3, 15));
+        board.place(Color::White, Point::new(3, 3));
+        board.pass(Color::Black);
+        board.place(Color::White, Point::new(15, 15));
+        board.place(Color::Black, Point::new(15, 3));
+        board.place(Color::White, Point::new(3, 9));
+
+        let loaded = &moves[6].board;
+
+        assert_eq!(loaded.count(), board.count());
+        assert_eq!(loaded.last_played(), board.last_played());
+        assert_eq!(loaded.history().collect::<Vec<_>>(), board.history().collect::<Vec<_>>());
+        assert_eq!(
+            features::Default::new(loaded).get_features::<HWC, f32>(Color::Black, Transform::Identity),
+            features::Default::new(&board).get_features::<HWC, f32>(Color::Black, Transform::Identity)
+        );
+    }
+
+    #[test]
+    fn setup_sgf() {
+        let moves = Sgf::new(b"(;GM[1]KM[7.5]AB[dd][pd]\nAW [pp]PL[W];W[dp];B[qq])", 7.5)
+            .map(|x| x.ok().unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(moves.len(), 2);
+        assert_eq!(moves[0].board.at(Point::new(3, 3)), Some(Color::Black));
+        assert_eq!(moves[0].board.at(Point::new(15, 3)), Some(Color::Black));
+        assert_eq!(moves[0].board.at(Point::new(15, 15)), Some(Color::White));
+        assert_eq!(moves[0].board.to_move(), Color::White);
+        assert_eq!(moves[0].color, Color::White);
+        assert_eq!(moves[1].board.at(Point::new(3, 15)), Some(Color::White));
+        assert_eq!(moves[1].board.to_move(), Color::Black);
+    }
+
+    #[test]
+    fn setup_on_occupied_point_sgf() {
+        let mut moves = Sgf::new(b"(;AB[dd]AW[dd];B[pp])", 7.5);
+
+        assert!(moves.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn setup_capture_sgf() {
+        let mut moves = Sgf::new(b"(;AW[aa]AB[ba][ab];B[pp])", 7.5);
+
+        assert!(moves.next().unwrap().is_err());
+    }
+
     #[test]
     fn rparen_sgf() {
         let moves = Sgf::new(b"(;B[dp]C[)))];W[dd])", 0.5)
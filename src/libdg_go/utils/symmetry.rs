@@ -13,6 +13,7 @@
 // limitations under the License.
 
 use board::Board;
+use color::Color;
 use point::Point;
 use point_state::Vertex;
 
@@ -102,6 +103,25 @@ impl Transform {
         }
     }
 
+    /// Returns the transform that is equivalent to first applying `self`, and
+    /// then applying `other`, i.e. `self.compose(other).apply(p)` is equal to
+    /// `other.apply(self.apply(p))` for every point `p`.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - the transform to apply after this one
+    ///
+    pub fn compose(self, other: Transform) -> Transform {
+        // the image of a vertex that is neither on an axis, nor on a diagonal,
+        // of the board is unique for every transform.
+        let probe = Point::new(10, 11);
+        let target = other.apply(self.apply(probe));
+
+        *ALL.iter()
+            .find(|t| t.apply(probe) == target)
+            .expect("could not find the composition of two transforms")
+    }
+
     pub fn get_table(self) -> &'static [Point] {
         match self {
             Transform::Identity => &_IDENTITY,
@@ -144,11 +164,246 @@ pub fn is_symmetric(board: &Board, transform: Transform) -> bool {
     })
 }
 
+/// Returns the color of every vertex of `board` after it has been transformed
+/// by `transform`, in the order given by `Point::all()`.
+///
+/// # Arguments
+///
+/// * `board` -
+/// * `transform` -
+///
+fn get_transformed_key(board: &Board, transform: Transform) -> Vec<u8> {
+    let inverse = transform.inverse();
+
+    Point::all()
+        .map(|point| {
+            match board.inner[inverse.apply(point)].color() {
+                None => 0,
+                Some(Color::Black) => 1,
+                Some(Color::White) => 2
+            }
+        })
+        .collect()
+}
+
+/// Returns the transform that maps the given board to its lexicographically
+/// smallest representation. If several transforms produce the same
+/// representation then the first one in `ALL` is returned.
+///
+/// # Arguments
+///
+/// * `board` - the board to get the canonical transform of
+///
+pub fn canonical_transform(board: &Board) -> Transform {
+    ALL.iter()
+        .map(|&t| (get_transformed_key(board, t), t))
+        .min_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, t)| t)
+        .unwrap_or(Transform::Identity)
+}
+
+/// Returns the transform that maps the given board to its lexicographically
+/// smallest representation, and the transformed board. Two boards that are
+/// symmetric to each other will have the same canonical form.
+///
+/// The returned board is the transformed board, including its move history,
+/// move count, prisoners, and which moves are forbidden by (super-)ko.
+///
+/// # Arguments
+///
+/// * `board` - the board to get the canonical form of
+///
+pub fn canonical(board: &Board) -> (Transform, Board) {
+    let transform = canonical_transform(board);
+
+    (transform, transform_board(board, transform))
+}
+
+/// Returns the given board with the given transform applied to every stone
+/// and every move in its history.
+///
+/// The positions that the super-ko history refers to cannot be transformed,
+/// since only their hashes are known. Instead the transformed board forbids
+/// exactly the transformed moves that are forbidden by (super-)ko on the
+/// original board, which is all that the super-ko history is used for.
+///
+/// # Arguments
+///
+/// * `board` - the board to transform
+/// * `transform` - the transform to apply
+///
+fn transform_board(board: &Board, transform: Transform) -> Board {
+    let mut other = Board::new(board.komi());
+
+    for point in Point::all() {
+        let other_point = transform.apply(point);
+
+        if let Some(color) = board.inner[point].color() {
+            let (hash, _) = other.inner.place(color, other_point);
+
+            other.zobrist_hash ^= hash;
+        }
+    }
+
+    for point in Point::all() {
+        if board.inner[point].visited() {
+            other.inner[transform.apply(point)].set_visited(true);
+        }
+    }
+
+    // re-create the hashes of the positions that are forbidden by super-ko,
+    // and then of the position an immediate ko re-take would return to, so
+    // that it remains the second most recent hash
+    let mut superko = vec! [];
+    let mut ko = vec! [];
+
+    for point in Point::all() {
+        for &color in &[Color::Black, Color::White] {
+            if board.inner[point].color().is_none() && board.inner[point].visited() && board.inner.is_valid(color, point) {
+                let next_zobrist_hash = board.zobrist_hash ^ board.inner.place_if(color, point);
+                let other_point = transform.apply(point);
+                let other_zobrist_hash = other.zobrist_hash ^ other.inner.place_if(color, other_point);
+
+                if next_zobrist_hash == board.zobrist_history.nth_most_recent(1) {
+                    ko.push(other_zobrist_hash);
+                } else if board.zobrist_history.contains(next_zobrist_hash) {
+                    superko.push(other_zobrist_hash);
+                }
+            }
+        }
+    }
+
+    for zobrist_hash in superko.into_iter().chain(ko) {
+        other.zobrist_history.push(zobrist_hash);
+    }
+    other.zobrist_history.push(other.zobrist_hash);
+
+    for point in board.history_with_passes().collect::<Vec<_>>().into_iter().rev() {
+        other.history.push(if point == Point::default() { point } else { transform.apply(point) });
+    }
+
+    other.count = board.count;
+    other.last_played = board.last_played;
+    other.prisoners = board.prisoners;
+    other
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashSet;
     use super::*;
 
+    #[test]
+    pub fn canonical_is_shared() {
+        let mut board = Board::new(7.5);
+        board.place(Color::Black, Point::new(3, 3));
+        board.place(Color::White, Point::new(15, 16));
+
+        for &t in &ALL {
+            let mut other = Board::new(7.5);
+            other.place(Color::Black, t.apply(Point::new(3, 3)));
+            other.place(Color::White, t.apply(Point::new(15, 16)));
+
+            let (transform, canonical_board) = canonical(&other);
+
+            assert!(canonical_board.position_eq(&canonical(&board).1));
+            assert_eq!(canonical_board.at(transform.apply(t.apply(Point::new(3, 3)))), Some(Color::Black));
+        }
+    }
+
+    #[test]
+    pub fn canonical_keeps_history() {
+        let mut board = Board::new(7.5);
+        board.place(Color::Black, Point::new(15, 16));
+        board.pass(Color::White);
+        board.place(Color::Black, Point::new(3, 3));
+
+        let (transform, canonical_board) = canonical(&board);
+
+        assert_eq!(canonical_board.count(), board.count());
+        assert_eq!(canonical_board.to_move(), board.to_move());
+        assert_eq!(
+            canonical_board.history().collect::<Vec<_>>(),
+            board.history().map(|point| transform.apply(point)).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            canonical_board.history_with_passes().filter(|&point| point == Point::default()).count(),
+            board.history_with_passes().filter(|&point| point == Point::default()).count()
+        );
+    }
+
+    #[test]
+    pub fn canonical_keeps_ko() {
+        let mut board = Board::new(7.5);
+
+        for &(color, x, y) in &[
+            (Color::Black, 1, 0), (Color::White, 2, 0),
+            (Color::Black, 0, 1), (Color::White, 1, 1),
+            (Color::Black, 1, 2), (Color::White, 3, 1),
+            (Color::Black, 10, 10), (Color::White, 2, 2)
+        ] {
+            board.place(color, Point::new(x, y));
+        }
+
+        board.place(Color::Black, Point::new(2, 1));
+        assert_eq!(board.at(Point::new(1, 1)), None);
+        assert!(!board.is_valid(Color::White, Point::new(1, 1)));
+
+        for &t in &ALL {
+            let other = transform_board(&board, t);
+
+            assert!(!other.is_valid(Color::White, t.apply(Point::new(1, 1))));
+            assert!(other.is_valid(Color::White, t.apply(Point::new(15, 15))));
+        }
+    }
+
+    #[test]
+    pub fn canonical_empty() {
+        let (transform, canonical_board) = canonical(&Board::new(7.5));
+
+        assert_eq!(transform, Transform::Identity);
+        assert!(canonical_board.position_eq(&Board::new(7.5)));
+    }
+
+    #[test]
+    pub fn compose_is_closed() {
+        for &a in &ALL {
+            for &b in &ALL {
+                let c = a.compose(b);
+
+                for point in Point::all() {
+                    assert_eq!(c.apply(point), b.apply(a.apply(point)), "{:?} . {:?} = {:?}", a, b, c);
+                }
+            }
+        }
+    }
+
+    #[test]
+    pub fn compose_with_inverse() {
+        for &t in &ALL {
+            assert_eq!(t.compose(t.inverse()), Transform::Identity);
+            assert_eq!(t.inverse().compose(t), Transform::Identity);
+            assert_eq!(t.compose(Transform::Identity), t);
+            assert_eq!(Transform::Identity.compose(t), t);
+        }
+    }
+
+    #[test]
+    pub fn compose_is_dihedral() {
+        // the rotations form a cyclic subgroup of order four, and every
+        // reflection is its own inverse.
+        assert_eq!(Transform::Rot90.compose(Transform::Rot90), Transform::Rot180);
+        assert_eq!(Transform::Rot90.compose(Transform::Rot180), Transform::Rot270);
+        assert_eq!(Transform::Rot270.compose(Transform::Rot90), Transform::Identity);
+
+        for &t in &[Transform::FlipLR, Transform::FlipUD, Transform::Transpose, Transform::TransposeAnti] {
+            assert_eq!(t.compose(t), Transform::Identity);
+        }
+
+        // the group is not abelian
+        assert_ne!(Transform::Rot90.compose(Transform::FlipLR), Transform::FlipLR.compose(Transform::Rot90));
+    }
+
     fn test_symmetry(t: Transform) {
         let mut seen = HashSet::new();
 
@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use rand::Rng;
 
 /// Returns the weighted n:th percentile of the given array, and the sum of
 /// all smaller elements.
@@ -48,6 +49,26 @@ fn percentile<O: Ord + Clone + Copy + Default + Into<f64>>(
     None
 }
 
+/// Choose a random value in `items` using the given random number generator,
+/// see `choose` for details.
+///
+/// # Arguments
+///
+/// * `items` -
+/// * `cutoff_percentile` - the percent of the cumulative value to prune
+/// * `temperature` -
+/// * `rng` - the random number generator to sample `at` from
+///
+pub fn choose_with_rng<O: Ord + Clone + Copy + Default + Into<f64>, R: Rng>(
+    items: &[O],
+    cutoff_percentile: f64,
+    temperature: f64,
+    rng: &mut R
+) -> Option<(usize, f64)>
+{
+    choose(items, cutoff_percentile, temperature, rng.gen::<f64>())
+}
+
 /// Choose the smallest value in `items` whose cumulative sum is at least `at`
 /// percent of the total cumulative value. This is done only on the
 /// `cutoff_percentile` smallest values has been removed.
@@ -94,9 +115,41 @@ pub fn choose<O: Ord + Clone + Copy + Default + Into<f64>>(
     cum.into_iter().enumerate().find(|(_, x)| *x >= at)
 }
 
+/// Returns the index of the largest value in `weights`. Ties are broken by the
+/// largest value in `tiebreak_keys`, and any remaining ties by the smallest
+/// index, so the result only depends on the given values. Returns `None` if
+/// `weights` is empty.
+///
+/// # Arguments
+///
+/// * `weights` -
+/// * `tiebreak_keys` - the key of each index in `weights`, must be at least
+///   as long as `weights`
+///
+pub fn argmax_stable<O: Ord, K: Ord>(weights: &[O], tiebreak_keys: &[K]) -> Option<usize> {
+    debug_assert!(tiebreak_keys.len() >= weights.len());
+
+    let mut best: Option<usize> = None;
+
+    for i in 0..weights.len() {
+        let is_better = match best {
+            None => true,
+            Some(j) => (&weights[i], &tiebreak_keys[i]) > (&weights[j], &tiebreak_keys[j])
+        };
+
+        if is_better {
+            best = Some(i);
+        }
+    }
+
+    best
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::rngs::SmallRng;
+    use rand::SeedableRng;
 
     #[test]
     fn percentile_50() {
@@ -139,4 +192,39 @@ mod tests {
             expected
         );
     }
+
+    #[test]
+    fn choose_with_rng_seeded() {
+        let items = vec! [2, 1, 4, 3, 7, 5, 9, 8];
+        let mut rng_1 = SmallRng::seed_from_u64(1);
+        let mut rng_2 = SmallRng::seed_from_u64(1);
+
+        for _ in 0..100 {
+            assert_eq!(
+                choose_with_rng(&items, 0.5, 1.0, &mut rng_1),
+                choose_with_rng(&items, 0.5, 1.0, &mut rng_2)
+            );
+        }
+    }
+
+    #[test]
+    fn argmax_stable_empty() {
+        assert_eq!(argmax_stable::<i32, i32>(&[], &[]), None);
+    }
+
+    #[test]
+    fn argmax_stable_by_weight() {
+        assert_eq!(argmax_stable(&[1, 3, 2], &[9, 0, 9]), Some(1));
+    }
+
+    #[test]
+    fn argmax_stable_by_tiebreak_key() {
+        assert_eq!(argmax_stable(&[3, 1, 3, 3], &[1, 9, 2, 0]), Some(2));
+    }
+
+    #[test]
+    fn argmax_stable_by_index() {
+        assert_eq!(argmax_stable(&[1, 3, 3, 3], &[(); 4]), Some(1));
+        assert_eq!(argmax_stable(&[3, 3, 1, 3], &[0, 0, 0, 0]), Some(0));
+    }
 }
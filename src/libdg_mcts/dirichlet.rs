@@ -13,7 +13,7 @@
 // limitations under the License.
 
 use rand_distr::{Distribution, Gamma};
-use rand::thread_rng;
+use rand::{thread_rng, Rng};
 
 use dg_utils::config;
 
@@ -25,10 +25,24 @@ use dg_utils::config;
 /// * `scale` - the scale of the distribution
 ///
 pub fn add(x: &mut [f32], shape: f32) {
-    add_ex(x, shape, *config::DIRICHLET_NOISE)
+    add_with_rng(x, shape, &mut thread_rng())
 }
 
-/// Add a dirichlet distribution of the given scale to `x`.
+/// Add a dirichlet distribution of the given scale to `x`, using the given
+/// random number generator.
+///
+/// # Arguments
+///
+/// * `x` - the vector to add the distribution to
+/// * `scale` - the scale of the distribution
+/// * `rng` - the random number generator to use
+///
+pub fn add_with_rng<R: Rng>(x: &mut [f32], shape: f32, rng: &mut R) {
+    add_ex_with_rng(x, shape, *config::DIRICHLET_NOISE, rng)
+}
+
+/// Add a dirichlet distribution of the given scale to `x`, using the given
+/// random number generator.
 ///
 /// # Arguments
 ///
@@ -36,8 +50,9 @@ pub fn add(x: &mut [f32], shape: f32) {
 /// * `scale` - the scale of the distribution
 /// * `beta` - the mixing coefficient between the prior value of `x` and
 ///   the dirichlet distribution.
+/// * `rng` - the random number generator to use
 ///
-pub fn add_ex(x: &mut [f32], shape: f32, beta: f32) {
+pub fn add_ex_with_rng<R: Rng>(x: &mut [f32], shape: f32, beta: f32, rng: &mut R) {
     assert!(shape < 1.0);
 
     let mut g_sum;
@@ -51,7 +66,7 @@ pub fn add_ex(x: &mut [f32], shape: f32, beta: f32) {
 
         for (i, x_) in x.iter().enumerate() {
             if x_.is_finite() {
-                let g_ = gamma.sample(&mut thread_rng());
+                let g_ = gamma.sample(rng);
 
                 count += 1;
                 g_sum += g_;
@@ -75,6 +90,8 @@ pub fn add_ex(x: &mut [f32], shape: f32, beta: f32) {
 mod tests {
     use super::*;
     use dg_utils::config;
+    use rand::rngs::SmallRng;
+    use rand::SeedableRng;
 
     #[test]
     fn dirichlet() {
@@ -99,8 +116,19 @@ mod tests {
         for _i in 0..10000 {
             let mut x = vec! [0.0; 1];
 
-            add_ex(&mut x, 0.03, 1.0);
+            add_ex_with_rng(&mut x, 0.03, 1.0, &mut thread_rng());
             assert_eq!(x[0], 1.0);
         }
     }
+
+    #[test]
+    fn dirichlet_with_rng() {
+        let mut x = vec! [0.1; 100];
+        let mut y = vec! [0.1; 100];
+
+        add_ex_with_rng(&mut x, 0.03, 0.25, &mut SmallRng::seed_from_u64(1));
+        add_ex_with_rng(&mut y, 0.03, 0.25, &mut SmallRng::seed_from_u64(1));
+
+        assert_eq!(x, y);
+    }
 }
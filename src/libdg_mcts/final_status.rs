@@ -0,0 +1,64 @@
+// Copyright 2021 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use dg_go::utils::score::{GroupStatus, Score};
+use dg_go::{Board, Point};
+use super::greedy_score;
+use super::predictor::Predictor;
+
+use std::collections::HashMap;
+
+pub trait FinalStatus {
+    /// Returns the status of the group of every stone on the board, which
+    /// combines Benson's algorithm with whether the stones survive a greedy
+    /// playout until the end of the game (see `Score::get_group_status`).
+    ///
+    /// # Arguments
+    ///
+    /// * `predictor` - the predictor to use during the playout
+    ///
+    fn final_status(&self, predictor: &dyn Predictor) -> HashMap<Point, GroupStatus>;
+}
+
+impl FinalStatus for Board {
+    fn final_status(&self, predictor: &dyn Predictor) -> HashMap<Point, GroupStatus> {
+        let (finished, _sgf) = greedy_score(predictor, self, self.to_move());
+
+        self.get_group_status(&finished)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dg_go::Color;
+    use predictors::RandomPredictor;
+    use super::*;
+
+    #[test]
+    fn settled_position() {
+        let mut board = Board::new(7.5);
+
+        for point in Point::all() {
+            if point != Point::new(0, 0) && point != Point::new(2, 0) {
+                board.place(Color::Black, point);
+            }
+        }
+
+        let status_map = board.final_status(&RandomPredictor::default());
+
+        assert_eq!(status_map.len(), 359);
+        assert!(status_map.values().all(|&status| status == GroupStatus::Alive));
+    }
+}
@@ -0,0 +1,143 @@
+// Copyright 2021 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use dg_go::utils::score::Score;
+use dg_go::{Board, Color, Point};
+use super::{full_forward, greedy_score};
+use super::options::{SearchOptions, StandardSearch};
+use super::predictor::Predictor;
+
+/// Returns true if `color` should pass in response to its opponent passing,
+/// in order to end the game gracefully. This is only the case if:
+///
+/// - the opponent passed on their most recent turn,
+/// - the position is scorable, i.e. it is settled, and
+/// - the estimated score favors `color`.
+///
+/// The opponent has passed if the most recent move in the history of the
+/// board is a pass by the opponent, as recorded by `Board::pass`.
+///
+/// # Arguments
+///
+/// * `predictor` - the predictor to use when estimating the score
+/// * `board` - the current board position
+/// * `color` - the color of the player whose turn it is to play
+///
+pub fn should_pass(predictor: &dyn Predictor, board: &Board, color: Color) -> bool {
+    let opponent_passed =
+        board.last_played() == Some(color.opposite())
+        && board.history_with_passes().next() == Some(Point::default());
+
+    if !opponent_passed || !board.is_scorable() {
+        return false;
+    }
+
+    let (finished, _sgf) = greedy_score(predictor, board, color);
+    let (black, white) = board.get_guess_score(&finished);
+    let score = black as f32 - white as f32 - board.komi();
+
+    match color {
+        Color::Black => score > 0.0,
+        Color::White => score < 0.0
+    }
+}
+
+/// Returns the winrate of `color`, as estimated by the neural network, of the
+/// position after `color` passes. Since passing gives the turn to the
+/// opponent, and adds a pass to the recent history, this is evaluated as the
+/// position of the opponent and not just the current position. Returns `None`
+/// if the predictor did not return a response.
+///
+/// # Arguments
+///
+/// * `predictor` - the predictor to use when evaluating the position
+/// * `board` - the current board position
+/// * `color` - the color of the player who would pass
+///
+pub fn pass_value(predictor: &dyn Predictor, board: &Board, color: Color) -> Option<f32> {
+    let options: Box<dyn SearchOptions + Sync> = Box::new(StandardSearch::new());
+    let mut other = board.clone();
+    other.pass(color);
+
+    full_forward(predictor, &options, &other, color.opposite())
+        .map(|(value, _policy)| 1.0 - value)
+}
+
+#[cfg(test)]
+mod tests {
+    use predictors::{FakePredictor, RandomPredictor};
+    use super::*;
+
+    /// Returns a board where black occupies every vertex except for two eyes.
+    fn black_territory() -> Board {
+        let mut board = Board::new(7.5);
+
+        for point in Point::all() {
+            if point != Point::new(0, 0) && point != Point::new(2, 0) {
+                board.place(Color::Black, point);
+            }
+        }
+
+        board
+    }
+
+    #[test]
+    fn pass_when_winning() {
+        let mut board = black_territory();
+        board.pass(Color::White);
+
+        assert!(should_pass(&RandomPredictor::default(), &board, Color::Black));
+    }
+
+    #[test]
+    fn no_pass_when_losing() {
+        let mut board = black_territory();
+        board.pass(Color::White);
+        board.pass(Color::Black);
+
+        assert!(!should_pass(&RandomPredictor::default(), &board, Color::White));
+    }
+
+    #[test]
+    fn no_pass_when_opponent_played() {
+        let board = black_territory();
+
+        assert!(!should_pass(&RandomPredictor::default(), &board, Color::White));
+    }
+
+    #[test]
+    fn no_pass_when_opponent_did_not_pass() {
+        let board = black_territory();
+
+        assert_eq!(board.to_move(), Color::White);
+        assert!(!should_pass(&RandomPredictor::default(), &board, Color::Black));
+    }
+
+    #[test]
+    fn no_pass_when_unsettled() {
+        let mut board = Board::new(7.5);
+        board.place(Color::Black, Point::new(3, 3));
+
+        assert!(!should_pass(&RandomPredictor::default(), &board, Color::Black));
+    }
+
+    #[test]
+    fn pass_value_is_for_passing_player() {
+        let predictor = FakePredictor::new(361, 0.5);
+        let value = pass_value(&predictor, &black_territory(), Color::Black).expect("could not evaluate pass");
+
+        assert!((value - 0.25).abs() < 1e-3, "{}", value);
+    }
+}
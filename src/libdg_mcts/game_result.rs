@@ -15,36 +15,98 @@
 use dg_go::utils::score::{Score, StoneStatus};
 use dg_go::utils::sgf::{CGoban, SgfCoordinate};
 use dg_go::{Board, Color, Point};
+use dg_utils::config;
 
 use std::fmt;
 
 pub enum GameResult {
     Resign(String, Board, Color, f32),
-    Ended(String, Board)
+    Ended(String, Board),
+
+    /// A game that reached the move cap, and whose final board was settled
+    /// using `greedy_score` before it was scored. The moves of the greedy
+    /// playout are not part of the SGF, only the board they ended in.
+    Capped(String, Board)
 }
 
-impl fmt::Display for GameResult {
-    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+impl GameResult {
+    /// Returns the winner of this game, or `None` if the game is a draw.
+    pub fn winner(&self) -> Option<Color> {
+        match *self {
+            GameResult::Resign(_, _, winner, _) => Some(winner),
+            GameResult::Ended(_, ref board) => {
+                let status_list = board.get_stone_status(board);
+
+                get_winner(board, &status_list, 0.0)
+            },
+            GameResult::Capped(_, ref board) => {
+                let status_list = board.get_stone_status(board);
+
+                get_winner(board, &status_list, *config::CAPPED_SCORE_MARGIN)
+            }
+        }
+    }
+
+    /// Returns the final score of this game, as the area of black minus the
+    /// area of white (including komi), or `None` if the game ended by
+    /// resignation.
+    pub fn score(&self) -> Option<f32> {
+        match *self {
+            GameResult::Resign(_, _, _, _) => None,
+            GameResult::Ended(_, ref board) | GameResult::Capped(_, ref board) => {
+                let status_list = board.get_stone_status(board);
+                let (black, white) = get_area(board, &status_list);
+
+                Some(black - white)
+            }
+        }
+    }
+
+    /// Returns this game as an SGF file. If `network` is given then it is
+    /// recorded as the name of the game (`GN`), so that the network that
+    /// generated a game can be identified later.
+    ///
+    /// # Arguments
+    ///
+    /// * `network` - the identifier of the network that played this game
+    ///
+    pub fn to_sgf(&self, network: Option<&str>) -> String {
         let now = time::now_utc();
         let iso8601 = time::strftime("%Y-%m-%dT%H:%M:%S%z", &now).unwrap();
+        let name = network.map(|network| format!("GN[{}]", network)).unwrap_or_default();
 
         match *self {
             GameResult::Resign(ref sgf, ref board, winner, _) => {
-                write!(fmt, "(;GM[1]FF[4]DT[{}]SZ[19]RU[Chinese]KM[{:.1}]RE[{}+Resign]{})", iso8601, board.komi(), winner, sgf)
+                format!("(;GM[1]FF[4]DT[{}]SZ[19]RU[Chinese]KM[{:.1}]{}RE[{}+Resign]{})", iso8601, board.komi(), name, winner, sgf)
             },
             GameResult::Ended(ref sgf, ref board) => {
                 let status_list = board.get_stone_status(&board);
-                let winner = get_winner_as_sgf(board, &status_list);
+                let winner = get_winner_as_sgf(board, &status_list, 0.0);
+                let territory = get_territory_as_sgf(&status_list);
+
+                format!("(;GM[1]FF[4]DT[{}]SZ[19]RU[Chinese]KM[{:.1}]{}RE[{}]{}{})", iso8601, board.komi(), name, winner, sgf, territory)
+            },
+            GameResult::Capped(ref sgf, ref board) => {
+                let status_list = board.get_stone_status(board);
+                let winner = get_winner_as_sgf(board, &status_list, *config::CAPPED_SCORE_MARGIN);
                 let territory = get_territory_as_sgf(&status_list);
 
-                write!(fmt, "(;GM[1]FF[4]DT[{}]SZ[19]RU[Chinese]KM[{:.1}]RE[{}]{}{})", iso8601, board.komi(), winner, sgf, territory)
+                format!("(;GM[1]FF[4]DT[{}]SZ[19]RU[Chinese]KM[{:.1}]{}RE[{}]GC[capped]{}{})", iso8601, board.komi(), name, winner, sgf, territory)
             }
         }
     }
 }
 
+impl fmt::Display for GameResult {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(fmt, "{}", self.to_sgf(None))
+    }
+}
+
 /// Returns the territory for both colors of the given board, according to
-/// TT-rules, as `TB` and `TW` properties.
+/// area scoring, as `TB` and `TW` properties. The only stones that are
+/// considered dead are those inside of the pass-alive territory of the
+/// opponent, every other stone is counted as alive.
 ///
 /// # Arguments
 ///
@@ -71,23 +133,108 @@ fn get_territory_as_sgf(status_list: &Vec<(Point, Vec<StoneStatus>)>) -> String
     )
 }
 
-/// Returns the winner of the given board, according to TT-rules, as an SGF
-/// property.
+/// Returns the winner of the given board, according to area scoring, or `None`
+/// if the winner does not lead by more than `margin`. See
+/// `get_territory_as_sgf` for which stones are considered dead.
 ///
 /// # Arguments
 ///
 /// * `board` -
 /// * `status_list` -
+/// * `margin` - the minimum lead required to win
+///
+fn get_winner(board: &Board, status_list: &[(Point, Vec<StoneStatus>)], margin: f32) -> Option<Color> {
+    let (black, white) = get_area(board, status_list);
+
+    if black - white > margin {
+        Some(Color::Black)
+    } else if white - black > margin {
+        Some(Color::White)
+    } else {
+        None
+    }
+}
+
+/// Returns the winner of the given board, according to area scoring, as an
+/// SGF property. See `get_territory_as_sgf` for which stones are considered
+/// dead.
 ///
-fn get_winner_as_sgf(board: &Board, status_list: &Vec<(Point, Vec<StoneStatus>)>) -> String {
+/// # Arguments
+///
+/// * `board` -
+/// * `status_list` -
+/// * `margin` - the minimum lead required to win
+///
+fn get_winner_as_sgf(board: &Board, status_list: &[(Point, Vec<StoneStatus>)], margin: f32) -> String {
+    let (black, white) = get_area(board, status_list);
+
+    match get_winner(board, status_list, margin) {
+        Some(Color::Black) => format!("B+{:.1}", black - white),
+        Some(Color::White) => format!("W+{:.1}", white - black),
+        None => "0".to_string()
+    }
+}
+
+/// Returns the area of black and white (including komi) of the given board,
+/// according to area scoring where only the stones inside of the pass-alive
+/// territory of the opponent are considered dead.
+///
+/// # Arguments
+///
+/// * `board` -
+/// * `status_list` -
+///
+fn get_area(board: &Board, status_list: &[(Point, Vec<StoneStatus>)]) -> (f32, f32) {
     let black = status_list.iter().filter(|(_, statuses)| statuses.contains(&StoneStatus::BlackTerritory)).count() as f32;
     let white = status_list.iter().filter(|(_, statuses)| statuses.contains(&StoneStatus::WhiteTerritory)).count() as f32 + board.komi();
 
-    if black > white {
-        format!("B+{:.1}", black - white)
-    } else if white > black {
-        format!("W+{:.1}", white - black)
-    } else {
-        "0".to_string()
+    (black, white)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn winner() {
+        assert_eq!(GameResult::Resign(String::new(), Board::new(7.5), Color::Black, 0.0).winner(), Some(Color::Black));
+        assert_eq!(GameResult::Ended(String::new(), Board::new(7.5)).winner(), Some(Color::White));
+        assert_eq!(GameResult::Ended(String::new(), Board::new(0.0)).winner(), None);
+        assert_eq!(GameResult::Capped(String::new(), Board::new(7.5)).winner(), Some(Color::White));
+    }
+
+    #[test]
+    fn score() {
+        assert_eq!(GameResult::Resign(String::new(), Board::new(7.5), Color::Black, 0.0).score(), None);
+        assert_eq!(GameResult::Ended(String::new(), Board::new(7.5)).score(), Some(-7.5));
+    }
+
+    #[test]
+    fn komi_in_sgf() {
+        let white = format!("{}", GameResult::Ended(String::new(), Board::new(0.5)));
+        let black = format!("{}", GameResult::Ended(String::new(), Board::new(-3.5)));
+
+        assert!(white.contains("KM[0.5]") && white.contains("RE[W+0.5]"), "{}", white);
+        assert!(black.contains("KM[-3.5]") && black.contains("RE[B+3.5]"), "{}", black);
+        assert!(format!("{}", GameResult::Resign(String::new(), Board::new(-7.5), Color::White, 0.0)).contains("KM[-7.5]"));
+    }
+
+    #[test]
+    fn network_name() {
+        let result = GameResult::Ended(";B[dd]".to_string(), Board::new(7.5));
+
+        assert!(result.to_sgf(Some("0a1b2c3d")).contains("GN[0a1b2c3d]"));
+        assert!(!result.to_sgf(None).contains("GN["));
+    }
+
+    #[test]
+    fn capped_margin() {
+        let board = Board::new(7.5);
+        let status_list = board.get_stone_status(&board);
+
+        assert_eq!(get_winner(&board, &status_list, 7.0), Some(Color::White));
+        assert_eq!(get_winner(&board, &status_list, 7.5), None);
+        assert_eq!(get_winner_as_sgf(&board, &status_list, 7.5), "0");
+        assert!(format!("{}", GameResult::Capped(String::new(), board)).contains("GC[capped]"));
     }
 }
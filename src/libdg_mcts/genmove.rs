@@ -0,0 +1,333 @@
+// Copyright 2021 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use dg_go::{Board, Color, Point};
+use options::SearchOptions;
+use pool::Pool;
+use time_control::{TimeStrategy, TimeStrategyResult};
+use tree::{self, PrettyVertex};
+use predict;
+
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The minimum amount of time between two status lines.
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A summary of the search that was performed to generate a move.
+#[derive(Clone, Debug)]
+pub struct SearchSummary {
+    /// The packed index of the generated move, or `361` for pass.
+    pub index: usize,
+
+    /// The estimated winrate of the generated move.
+    pub value: f32,
+
+    /// The packed index of the most likely move according to the policy prior
+    /// of the root, or `361` for pass.
+    pub prior_index: usize,
+
+    /// The estimated winrate of the most likely move according to the policy
+    /// prior of the root.
+    pub prior_value: f32,
+
+    /// The total number of playouts in the search tree.
+    pub playouts: i32,
+
+    /// The wall clock time spent searching.
+    pub elapsed: Duration
+}
+
+impl SearchSummary {
+    /// Returns a summary of the given search tree, where the search generated
+    /// the given move.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - the estimated winrate of the generated move
+    /// * `index` - the packed index of the generated move
+    /// * `root` - the search tree
+    /// * `elapsed` - the wall clock time spent searching
+    ///
+    pub fn new(value: f32, index: usize, root: &tree::Node, elapsed: Duration) -> Self {
+        let (_, prior_index) = root.prior();
+
+        Self {
+            index,
+            value,
+            prior_index,
+            prior_value: root.with(prior_index, |child| child.value()),
+            playouts: root.total_count,
+            elapsed
+        }
+    }
+
+    /// Returns true if the search chose a different move than the most likely
+    /// move according to the policy prior.
+    pub fn is_prior_overruled(&self) -> bool {
+        self.index != self.prior_index
+    }
+
+    /// Returns how much larger the estimated winrate of the generated move is
+    /// compared to the most likely move according to the policy prior, or
+    /// `None` if the search did not overrule the policy prior.
+    pub fn prior_winrate_gap(&self) -> Option<f32> {
+        if self.is_prior_overruled() {
+            Some(self.value - self.prior_value)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for SearchSummary {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmt,
+            "{} winrate {:.1}% playouts {} in {:.1}s",
+            PrettyVertex { inner: self.index },
+            100.0 * self.value,
+            self.playouts,
+            self.elapsed.as_secs_f32()
+        )?;
+
+        if let Some(gap) = self.prior_winrate_gap() {
+            write!(fmt, ", overruled prior {} by {:+.1}%", PrettyVertex { inner: self.prior_index }, 100.0 * gap)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Search the given board position for `color`, and returns the winrate and
+/// index of the best move (as `predict`) in the form of a summary of the
+/// search, together with the search tree. The summary reports whether the
+/// search overruled the policy prior, and by how much.
+///
+/// # Arguments
+///
+/// * `pool` - the pool of workers to use for the search
+/// * `options` -
+/// * `time_strategy` - the strategy that decides when the search is done
+/// * `starting_tree` - the search tree to continue from
+/// * `board` -
+/// * `color` - the color to search for
+///
+pub fn predict_with_summary(
+    pool: &Pool,
+    options: Box<dyn SearchOptions + Sync>,
+    time_strategy: Box<dyn TimeStrategy + Sync>,
+    starting_tree: Option<tree::Node>,
+    board: &Board,
+    color: Color
+) -> Option<(SearchSummary, tree::Node)>
+{
+    let start_time = Instant::now();
+    let (value, index, root) = predict(pool, options, time_strategy, starting_tree, board, color)?;
+    let summary = SearchSummary::new(value, index, &root, start_time.elapsed());
+
+    Some((summary, root))
+}
+
+/// A time strategy that wraps a base strategy, and periodically writes a
+/// one-line status of the search to stderr while it is running.
+struct ReportingTimeStrategy {
+    /// The base strategy that decides when the search is done.
+    base: Box<dyn TimeStrategy + Sync>,
+
+    /// When the search started.
+    start_time: Instant,
+
+    /// When the last status line was written.
+    last_report: Mutex<Instant>
+}
+
+impl ReportingTimeStrategy {
+    fn new(base: Box<dyn TimeStrategy + Sync>) -> Self {
+        let now = Instant::now();
+
+        Self {
+            base,
+            start_time: now,
+            last_report: Mutex::new(now)
+        }
+    }
+}
+
+impl TimeStrategy for ReportingTimeStrategy {
+    fn try_extend(&self, root: &tree::Node) -> TimeStrategyResult {
+        // if another thread is currently reporting then there is no reason to
+        // wait for it
+        if let Ok(mut last_report) = self.last_report.try_lock() {
+            if last_report.elapsed() >= REPORT_INTERVAL {
+                *last_report = Instant::now();
+                eprintln!("{}", status_line(root, self.start_time.elapsed()));
+            }
+        }
+
+        self.base.try_extend(root)
+    }
+}
+
+/// Returns a one-line status of the given search tree, containing the most
+/// visited move, its winrate, and the total number of playouts.
+///
+/// # Arguments
+///
+/// * `root` -
+/// * `elapsed` -
+///
+fn status_line(root: &tree::Node, elapsed: Duration) -> String {
+    let index = root.children.argmax_count();
+    let value = root.with(index, |child| child.value());
+
+    format!(
+        "{:.1}s best {} winrate {:.1}% playouts {}",
+        elapsed.as_secs_f32(),
+        PrettyVertex { inner: index },
+        100.0 * value,
+        root.total_count
+    )
+}
+
+/// Generate a move for `color` at the given board position, while writing a
+/// one-line status of the search to stderr at regular intervals. Returns the
+/// generated move (`None` for pass), a summary of the search, and the search
+/// tree, or `None` if the search failed.
+///
+/// # Arguments
+///
+/// * `pool` - the pool of workers to use for the search
+/// * `options` -
+/// * `starting_tree` - the search tree to continue from
+/// * `board` -
+/// * `color` - the color to generate the move for
+/// * `time_strategy` - the strategy that decides when the search is done
+///
+pub fn genmove_verbose(
+    pool: &Pool,
+    options: Box<dyn SearchOptions + Sync>,
+    starting_tree: Option<tree::Node>,
+    board: &Board,
+    color: Color,
+    time_strategy: Box<dyn TimeStrategy + Sync>
+) -> Option<(Option<Point>, SearchSummary, tree::Node)>
+{
+    let time_strategy = Box::new(ReportingTimeStrategy::new(time_strategy));
+    let (summary, root) = predict_with_summary(pool, options, time_strategy, starting_tree, board, color)?;
+    let index = summary.index;
+
+    eprintln!("{}", status_line(&root, summary.elapsed));
+
+    if index >= 361 {
+        Some((None, summary, root))
+    } else {
+        Some((Some(Point::from_packed_parts(index)), summary, root))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use options::StandardDeterministicSearch;
+    use predictors::RandomPredictor;
+    use time_control::RolloutLimit;
+    use super::*;
+
+    #[test]
+    fn genmove_summary() {
+        let pool = Pool::with_capacity(Box::new(RandomPredictor), 1, None);
+        let board = Board::new(7.5);
+        let (point, summary, root) = genmove_verbose(
+            &pool,
+            Box::new(StandardDeterministicSearch::new()),
+            None,
+            &board,
+            Color::Black,
+            Box::new(RolloutLimit::new(100))
+        ).expect("could not generate a move");
+
+        assert!(summary.playouts > 0, "{} > 0", summary.playouts);
+        assert_eq!(summary.playouts, root.total_count);
+        assert_eq!(point.map(|p| p.to_packed_index()).unwrap_or(361), summary.index);
+    }
+
+    #[test]
+    fn prior_winrate_gap() {
+        let mut summary = SearchSummary {
+            index: 60,
+            value: 0.6,
+            prior_index: 60,
+            prior_value: 0.6,
+            playouts: 100,
+            elapsed: Duration::from_secs(1)
+        };
+
+        assert!(!summary.is_prior_overruled());
+        assert_eq!(summary.prior_winrate_gap(), None);
+
+        summary.prior_index = 61;
+        summary.prior_value = 0.5;
+
+        assert!(summary.is_prior_overruled());
+        assert!((summary.prior_winrate_gap().unwrap() - 0.1).abs() < 1e-6);
+    }
+
+    #[test]
+    fn predict_summary() {
+        let pool = Pool::with_capacity(Box::new(RandomPredictor), 1, None);
+        let board = Board::new(7.5);
+        let (summary, root) = predict_with_summary(
+            &pool,
+            Box::new(StandardDeterministicSearch::new()),
+            Box::new(RolloutLimit::new(100)),
+            None,
+            &board,
+            Color::Black
+        ).expect("could not search the position");
+
+        assert_eq!(summary.playouts, root.total_count);
+        assert_eq!(summary.prior_index, root.prior().1);
+        assert_eq!(summary.is_prior_overruled(), summary.index != root.prior().1);
+    }
+
+    #[test]
+    fn display_summary() {
+        let mut summary = SearchSummary {
+            index: 60,
+            value: 0.6,
+            prior_index: 60,
+            prior_value: 0.6,
+            playouts: 100,
+            elapsed: Duration::from_secs(1)
+        };
+
+        assert_eq!(format!("{}", summary), "d4 winrate 60.0% playouts 100 in 1.0s");
+
+        summary.prior_index = 361;
+        summary.prior_value = 0.5;
+
+        assert_eq!(format!("{}", summary), "d4 winrate 60.0% playouts 100 in 1.0s, overruled prior pass by +10.0%");
+    }
+
+    #[test]
+    fn status_line_contains_playouts() {
+        let root = tree::Node::new(Color::Black, 0.5, vec! [1.0; 362]);
+        let line = status_line(&root, Duration::from_secs(2));
+
+        assert!(line.starts_with("2.0s best "), "{}", line);
+        assert!(line.ends_with("playouts 0"), "{}", line);
+    }
+}
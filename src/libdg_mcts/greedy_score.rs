@@ -13,16 +13,22 @@
 // limitations under the License.
 
 use ordered_float::OrderedFloat;
+use rand::thread_rng;
 
+use dg_go::utils::score::{Score, ScoringRules};
 use dg_go::utils::sgf::{CGoban, SgfCoordinate};
 use dg_go::{Board, Color, Point};
+use super::choose::{argmax_stable, choose_with_rng};
 use super::predictor::Predictor;
 use super::{full_forward, ScoringSearch, SearchOptions};
 
 
 /// Play the given board until the end using the policy of the neural network
 /// in a greedy manner (ignoring the pass move every time) until it is scorable
-/// according to the TT-rules.
+/// according to the Tromp-Taylor rules, i.e. until every stone on the board
+/// can be counted as alive (see `ScoringRules::TrompTaylor`). The playout stops
+/// as soon as every group is pass-alive, and every empty vertex is territory,
+/// since no further moves can change the score.
 ///
 /// # Arguments
 ///
@@ -30,7 +36,76 @@ use super::{full_forward, ScoringSearch, SearchOptions};
 /// * `board` - the board to score
 /// * `to_move` - the color of the player whose turn it is to play
 ///
-pub fn greedy_score(predictor: &dyn Predictor, board: &Board, mut to_move: Color) -> (Board, String) {
+pub fn greedy_score(predictor: &dyn Predictor, board: &Board, to_move: Color) -> (Board, String) {
+    playout(predictor, board, to_move, |policy| {
+        let candidates = (0..361)
+            .filter(|&i| policy[i].is_finite())
+            .collect::<Vec<_>>();
+
+        argmax_stable(
+            &candidates.iter().map(|&i| OrderedFloat(policy[i])).collect::<Vec<_>>(),
+            &vec! [(); candidates.len()]
+        ).map(|j| candidates[j])
+    })
+}
+
+/// Returns the mean and the standard deviation of the score margin for
+/// `to_move`, over `n` playouts of the given board. The first playout is
+/// the same as `greedy_score`, while the remaining ones sample each move
+/// from the policy of the neural network to estimate how uncertain the
+/// score is.
+///
+/// # Arguments
+///
+/// * `predictor` - the predictor to use during evaluation
+/// * `board` - the board to score
+/// * `to_move` - the color of the player whose turn it is to play
+/// * `n` - the number of playouts to perform
+///
+pub fn estimate_score_distribution(predictor: &dyn Predictor, board: &Board, to_move: Color, n: usize) -> (f32, f32) {
+    let margins = (0..n.max(1))
+        .map(|i| {
+            let (finished, _sgf) = if i == 0 {
+                greedy_score(predictor, board, to_move)
+            } else {
+                playout(predictor, board, to_move, |policy| {
+                    let weights = (0..361)
+                        .map(|i| OrderedFloat(if policy[i].is_finite() { policy[i] as f64 } else { 0.0 }))
+                        .collect::<Vec<_>>();
+
+                    choose_with_rng(&weights, 0.5, 1.0, &mut thread_rng())
+                        .map(|(i, _)| i)
+                        .filter(|&i| policy[i].is_finite())
+                })
+            };
+
+            let (black, white) = finished.get_score(ScoringRules::TrompTaylor);
+            let margin = black as f32 - white as f32 - finished.komi();
+
+            if to_move == Color::Black { margin } else { -margin }
+        })
+        .collect::<Vec<_>>();
+
+    let count = margins.len() as f32;
+    let mean = margins.iter().sum::<f32>() / count;
+    let variance = margins.iter().map(|&m| (m - mean) * (m - mean)).sum::<f32>() / count;
+
+    (mean, variance.sqrt())
+}
+
+/// Play the given board until the end, using `pick` to select which move to
+/// play from the policy of the neural network, see `greedy_score` for the
+/// stopping conditions.
+///
+/// # Arguments
+///
+/// * `predictor` - the predictor to use during evaluation
+/// * `board` - the board to play
+/// * `to_move` - the color of the player whose turn it is to play
+/// * `pick` - returns the (packed) index of the move to play given the
+///   policy, or `None` to pass
+///
+fn playout<F: FnMut(&[f32]) -> Option<usize>>(predictor: &dyn Predictor, board: &Board, mut to_move: Color, mut pick: F) -> (Board, String) {
     let options: Box<dyn SearchOptions + Sync> = Box::new(ScoringSearch::default());
     let mut board = board.clone();
     let mut sgf = String::new();
@@ -38,17 +113,20 @@ pub fn greedy_score(predictor: &dyn Predictor, board: &Board, mut to_move: Color
     let mut count = 0;
 
     while count < 722 && pass_count < 2 {
+        // if every group is pass-alive, and every empty vertex is the territory
+        // of one of them, then there is nothing left to play for
+        if board.is_scorable() {
+            break;
+        }
+
         let policy = if let Some(response) = full_forward(predictor, &options, &board, to_move) {
             response.1
         } else {
             return (board, sgf)
         };
 
-        // pick the move with the largest prior value that does not fill an
-        // eye
-        let index = (0..361)
-            .filter(|&i| policy[i].is_finite())
-            .max_by_key(|&i| OrderedFloat(policy[i]));
+        // pick a move that does not fill an eye
+        let index = pick(&policy);
 
         if let Some(index) = index {
             let point = Point::from_packed_parts(index);
@@ -59,6 +137,7 @@ pub fn greedy_score(predictor: &dyn Predictor, board: &Board, mut to_move: Color
         } else {  // no valid moves remaining
             sgf += &format!(";{}[]", to_move);
             pass_count += 1;
+            board.pass(to_move);
         }
 
         // continue with the next turn
@@ -68,3 +147,51 @@ pub fn greedy_score(predictor: &dyn Predictor, board: &Board, mut to_move: Color
 
     (board, sgf)
 }
+
+#[cfg(test)]
+mod tests {
+    use predictors::RandomPredictor;
+    use super::*;
+
+    #[test]
+    fn settled_position() {
+        let mut board = Board::new(7.5);
+
+        for point in Point::all() {
+            if point != Point::new(0, 0) && point != Point::new(2, 0) {
+                board.place(Color::Black, point);
+            }
+        }
+
+        let (finished, sgf) = greedy_score(&RandomPredictor::default(), &board, Color::White);
+
+        assert!(finished == board);
+        assert_eq!(sgf, "");
+    }
+
+    #[test]
+    fn settled_score_distribution() {
+        let mut board = Board::new(7.5);
+
+        for point in Point::all() {
+            if point != Point::new(0, 0) && point != Point::new(2, 0) {
+                board.place(Color::Black, point);
+            }
+        }
+
+        let (mean, stddev) = estimate_score_distribution(&RandomPredictor, &board, Color::White, 4);
+
+        assert_eq!(mean, -353.5);
+        assert_eq!(stddev, 0.0);
+    }
+
+    #[test]
+    fn empty_score_distribution() {
+        let board = Board::new(7.5);
+        let (mean, stddev) = estimate_score_distribution(&RandomPredictor, &board, Color::Black, 2);
+
+        assert!(mean.is_finite());
+        assert!(stddev.is_finite() && stddev >= 0.0);
+        assert!(mean.abs() <= 361.0 + 7.5);
+    }
+}
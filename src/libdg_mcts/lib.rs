@@ -34,36 +34,52 @@ extern crate time;
 pub mod asm;
 mod choose;
 mod dirichlet;
+mod final_status;
+mod friendly_pass;
 mod game_result;
+mod genmove;
 mod lru_cache;
 mod greedy_score;
 pub mod options;
 pub mod parallel;
 pub mod predictor;
 pub mod predictors;
+mod prewarm;
 mod reanalyze;
+mod resign;
 mod self_play;
+mod successors;
+mod training_shard;
 pub mod tree;
 pub mod time_control;
 pub mod pool;
 
 /* -------- Exports -------- */
 
+pub use self::final_status::*;
+pub use self::friendly_pass::*;
 pub use self::game_result::*;
+pub use self::genmove::*;
 pub use self::greedy_score::*;
+pub use self::prewarm::*;
 pub use self::self_play::*;
+pub use self::successors::*;
+pub use self::training_shard::*;
 pub use self::reanalyze::*;
+pub use self::resign::*;
 
 /* -------- Code -------- */
 
+use ordered_float::OrderedFloat;
 use rand::{thread_rng, Rng};
 use std::cell::UnsafeCell;
+use std::cmp::Reverse;
 
 use dg_go::utils::features::{self, HWC, Features};
 use dg_go::utils::symmetry;
-use dg_go::{Board, Color};
+use dg_go::{Board, Color, Point, recommended_komi};
 use self::options::{SearchOptions, ScoringSearch};
-use self::time_control::TimeStrategy;
+use self::time_control::{TimeStrategy, TimeStrategyResult};
 use self::tree::NodeTrace;
 use self::predictor::{Predictor, Prediction};
 use dg_utils::config;
@@ -71,7 +87,8 @@ use dg_utils::types::f16;
 use self::pool::*;
 
 /// Return the value and policy for the given board position, as the interpolation
-/// of their value for every symmetry.
+/// of their value for every symmetry. Returns `None` if the predictor did not
+/// return a response for every symmetry.
 ///
 /// # Arguments
 ///
@@ -81,47 +98,135 @@ use self::pool::*;
 /// * `to_move` - the color to evaluate for
 ///
 fn full_forward(predictor: &dyn Predictor, options: &Box<dyn SearchOptions + Sync>, board: &Board, to_move: Color) -> Option<(f32, Vec<f32>)> {
+    full_forward_aux(predictor, options, board, to_move, None)
+}
+
+/// The value and policy of a board position for a single symmetry, as
+/// returned by `full_forward_detailed`.
+pub struct SymmetryPrediction {
+    /// The symmetry that the features were transformed by.
+    pub transform: symmetry::Transform,
+
+    /// The winrate of this symmetry.
+    pub value: f32,
+
+    /// The normalized policy of this symmetry, in the identity orientation.
+    pub policy: Vec<f32>
+}
+
+/// Return the value and policy for the given board position like `full_forward`,
+/// in addition to the value and policy of each individual symmetry that went
+/// into them. This is intended for measuring how symmetry-invariant the neural
+/// network is. Returns `None` if the predictor did not return a response for
+/// every symmetry.
+///
+/// # Arguments
+///
+/// * `predictor` - the server to use for predictions
+/// * `options` -
+/// * `board` - the board position to evaluate
+/// * `to_move` - the color to evaluate for
+///
+pub fn full_forward_detailed(
+    predictor: &dyn Predictor,
+    options: &Box<dyn SearchOptions + Sync>,
+    board: &Board,
+    to_move: Color
+) -> Option<(f32, Vec<f32>, Vec<SymmetryPrediction>)>
+{
+    let mut details = vec! [];
+    let (value, policy) = full_forward_aux(predictor, options, board, to_move, Some(&mut details))?;
+
+    Some((value, policy, details))
+}
+
+/// Return the value and policy for the given board position, see `full_forward`.
+/// If `details` is given then the value and policy of each symmetry is added
+/// to it.
+///
+/// # Arguments
+///
+/// * `predictor` - the server to use for predictions
+/// * `options` -
+/// * `board` - the board position to evaluate
+/// * `to_move` - the color to evaluate for
+/// * `details` -
+///
+fn full_forward_aux(
+    predictor: &dyn Predictor,
+    options: &Box<dyn SearchOptions + Sync>,
+    board: &Board,
+    to_move: Color,
+    mut details: Option<&mut Vec<SymmetryPrediction>>
+) -> Option<(f32, Vec<f32>)>
+{
+    // the features read whose turn it is from the board, so make sure that it
+    // agrees with `to_move`
+    let mut board = board.clone();
+    board.set_to_move(to_move);
+    let board = &board;
+
     let (initial_policy, indices) = create_initial_policy(options, board, to_move);
+    let num_symmetries = options.root_symmetries().clamp(1, 8);
+    let weight = (num_symmetries as f32).recip();
     let mut policy = initial_policy.clone();
     let mut value = 0.0f32;
+    let mut add_response = |response: &Prediction, t: symmetry::Transform| {
+        let mut new_policy = initial_policy.clone();
+        add_valid_candidates(&mut new_policy, response.policy(), &indices, t);
+        scale_pass_prior(options, board, &mut new_policy);
+
+        if let Some(ref mut details) = details {
+            let mut policy = new_policy.clone();
+            normalize_policy(&mut policy, 1.0);
+
+            details.push(SymmetryPrediction { transform: t, value: response.winrate(), policy });
+        }
+
+        normalize_policy(&mut new_policy, weight);
+
+        value += response.winrate() * weight;
+        for i in 0..362 {
+            policy[i] += new_policy[i];
+        }
+    };
 
     // find out which symmetries has already been calculated, and which ones has not
-    let mut new_requests = Vec::with_capacity(8 * features::Default::size());
-    let mut new_symmetries = Vec::with_capacity(8);
+    let mut new_symmetries = Vec::with_capacity(num_symmetries);
 
-    for &t in &symmetry::ALL {
+    for &t in symmetry::ALL.iter().take(num_symmetries) {
         if let Some(new_response) = predictor.fetch(board, to_move, t) {
-            let mut new_policy = initial_policy.clone();
-            add_valid_candidates(&mut new_policy, new_response.policy(), &indices, t);
-            normalize_policy(&mut new_policy, 0.125);
-
-            value += new_response.winrate() * 0.125;
-            for i in 0..362 {
-                policy[i] += new_policy[i];
-            }
+            add_response(&new_response, t);
         } else {
-            let features = features::Default::new(&board).get_features::<HWC, f16>(to_move, t);
-            new_requests.extend_from_slice(&features);
             new_symmetries.push(t);
         }
     }
 
     // calculate any symmetries that were missing, add them to the cache, and then take the
-    // average of them
+    // average of them. If every symmetry was already cached then we do not need to compute
+    // any features at all.
     let batch_size = new_symmetries.len();
 
     if batch_size > 0 {
-        let new_responses = predictor.predict(&new_requests, batch_size);
+        let features = features::Default::new(&board);
+        let mut new_requests = Vec::with_capacity(batch_size * features::Default::size());
 
-        for (new_response, t) in new_responses.into_iter().zip(new_symmetries.into_iter()) {
-            let mut new_policy = initial_policy.clone();
-            add_valid_candidates(&mut new_policy, new_response.policy(), &indices, t);
-            normalize_policy(&mut new_policy, 0.125);
+        for &t in &new_symmetries {
+            new_requests.extend_from_slice(&features.get_features::<HWC, f16>(to_move, t));
+        }
 
-            value += new_response.winrate() * 0.125;
-            for i in 0..362 {
-                policy[i] += new_policy[i];
-            }
+        let new_responses = if let [t] = new_symmetries[..] {
+            predictor.predict_single_transformed(&new_requests, t).into_iter().collect()
+        } else {
+            predictor.predict_transformed(&new_requests, &new_symmetries)
+        };
+
+        if new_responses.len() != batch_size {
+            return None;
+        }
+
+        for (new_response, t) in new_responses.into_iter().zip(new_symmetries.into_iter()) {
+            add_response(&new_response, t);
             predictor.cache(board, to_move, t, new_response);
         }
     }
@@ -129,6 +234,132 @@ fn full_forward(predictor: &dyn Predictor, options: &Box<dyn SearchOptions + Syn
     Some((value, policy))
 }
 
+/// Return the value and policy for the given board position using `full_forward`,
+/// retrying up to `FORWARD_RETRIES` times if it fails. If every attempt fails then
+/// a uniform policy over all candidate moves, and a value of `0.5`, is returned
+/// instead.
+///
+/// # Arguments
+///
+/// * `predictor` - the server to use for predictions
+/// * `options` -
+/// * `board` - the board position to evaluate
+/// * `to_move` - the color to evaluate for
+///
+fn full_forward_or_uniform(predictor: &dyn Predictor, options: &Box<dyn SearchOptions + Sync>, board: &Board, to_move: Color) -> (f32, Vec<f32>) {
+    for _ in 0..=*config::FORWARD_RETRIES {
+        if let Some(result) = full_forward(predictor, options, board, to_move) {
+            return result;
+        }
+    }
+
+    let (mut policy, _indices) = create_initial_policy(options, board, to_move);
+    normalize_policy(&mut policy, 1.0);
+
+    (0.5, policy)
+}
+
+/// The winrate above which the root position is considered to be clearly won,
+/// and the score margin of each candidate move is taken into account.
+const CLEARLY_WON: f32 = 0.95;
+
+/// The number of candidate moves, with the largest prior, whose score margin
+/// is estimated when computing the score utility.
+const SCORE_UTILITY_CANDIDATES: usize = 8;
+
+/// The score margin, in points, at which the score utility of a candidate
+/// move is `tanh(1)` of its weight.
+const SCORE_UTILITY_SCALE: f32 = 20.0;
+
+/// Returns the utility of the score margin after each candidate move, or
+/// `None` if the score margin should not be taken into account. The score
+/// margin of the candidate moves with the largest prior is estimated by
+/// playing the resulting position to the end using the policy of the neural
+/// network, see `estimate_score_distribution`. The utility of every other
+/// candidate move is the smallest of the estimated ones.
+///
+/// The playouts are charged to the time budget of the search, so no further
+/// candidates are estimated once `time_strategy` has expired.
+///
+/// # Arguments
+///
+/// * `predictor` - the predictor to use for the playouts
+/// * `options` -
+/// * `time_strategy` - the time budget of the search
+/// * `root` - the root of the search tree, with the prior of each candidate
+/// * `board` - the board position at the root
+/// * `to_move` - the color to evaluate for
+/// * `value` - the winrate of the root for `to_move`
+///
+fn get_score_utility(
+    predictor: &dyn Predictor,
+    options: &(dyn SearchOptions + Sync),
+    time_strategy: &(dyn TimeStrategy + Sync),
+    root: &tree::Node,
+    board: &Board,
+    to_move: Color,
+    value: f32
+) -> Option<Box<[f32; 368]>>
+{
+    let prior = &root.prior;
+    let weight = options.score_utility_weight();
+
+    if weight <= 0.0 || value < CLEARLY_WON {
+        return None;
+    }
+
+    let mut candidates = (0..362).filter(|&i| prior[i].is_finite()).collect::<Vec<_>>();
+    candidates.sort_by_key(|&i| (Reverse(OrderedFloat(prior[i])), i));
+    candidates.truncate(SCORE_UTILITY_CANDIDATES);
+
+    if candidates.is_empty() {
+        return None;
+    }
+
+    let margins = candidates.iter()
+        .take_while(|_| {
+            match time_strategy.try_extend(root) {
+                TimeStrategyResult::NotExpired(_) | TimeStrategyResult::Extended => true,
+                _ => false
+            }
+        })
+        .map(|&i| {
+            let mut other = board.clone();
+
+            if i == 361 {
+                other.pass(to_move);
+            } else {
+                other.place(to_move, Point::from_packed_parts(i));
+            }
+
+            -estimate_score_distribution(predictor, &other, to_move.opposite(), 1).0
+        })
+        .collect::<Vec<_>>();
+
+    if margins.is_empty() {
+        return None;
+    }
+
+    // the utility is relative to the average margin of the candidates, so that
+    // it distinguishes between them even when every candidate wins by a lot
+    let mean = margins.iter().sum::<f32>() / margins.len() as f32;
+    let get_utility = |margin: f32| weight * ((margin - mean) / SCORE_UTILITY_SCALE).tanh();
+    let min_margin = margins.iter().cloned().fold(::std::f32::INFINITY, f32::min);
+    let mut utility = Box::new([0.0; 368]);
+
+    for i in 0..362 {
+        if prior[i].is_finite() {
+            utility[i] = get_utility(min_margin);
+        }
+    }
+
+    for (&i, &margin) in candidates.iter().zip(margins.iter()) {
+        utility[i] = get_utility(margin);
+    }
+
+    Some(utility)
+}
+
 /// Predicts the _best_ next move according to the given neural network when applied
 /// to a monte carlo tree search.
 ///
@@ -150,48 +381,78 @@ pub fn predict(
     starting_color: Color
 ) -> Option<(f32, usize, tree::Node)>
 {
-    let deterministic = options.deterministic();
-    let (starting_value, mut starting_policy) = full_forward(
+    let root_noise = options.root_noise();
+    let selection_temperature = options.selection_temperature();
+    let min_visits = options.min_visits_for_selection();
+    let (starting_value, mut starting_policy) = full_forward_or_uniform(
         pool.predictor(),
         &options,
         starting_point,
         starting_color
-    )?;
+    );
 
     // add some dirichlet noise to the root node of the search tree in order to increase
     // the entropy of the search and avoid overfitting to the prior value
-    if !deterministic {
+    if root_noise {
         dirichlet::add(&mut starting_policy[..362], 0.03);
     }
 
     // if we have a starting tree given, then re-use that tree (after some sanity
     // checks), otherwise we need to query the neural network about what the
-    // prior value should be at the root node.
+    // prior value should be at the root node. A starting tree for the wrong
+    // color is a bug in the tree re-use, but it is not worth crashing over since
+    // a fresh search is always correct.
+    let starting_tree = starting_tree.filter(|starting_tree| {
+        if starting_tree.to_move != starting_color {
+            eprintln!("Warning: discarding a starting tree for {}, when {} is to move", starting_tree.to_move, starting_color);
+            false
+        } else {
+            true
+        }
+    });
     let starting_tree = if let Some(mut starting_tree) = starting_tree {
-        assert_eq!(starting_tree.to_move, starting_color);
-
         // replace the prior value of the tree, since it was either:
         //
         // - calculated using only one symmetry.
         // - a pre-expanded pass move, which does not get a prior computed.
         //
         starting_tree.prior[0..362].clone_from_slice(&starting_policy[..362]);
+
+        // a pre-expanded pass move also has a placeholder value, which must be
+        // replaced before any of its children are visited
+        if starting_tree.total_count == 0 {
+            starting_tree.initial_value = starting_value;
+        }
+
         starting_tree
     } else {
         tree::Node::new(starting_color, starting_value, starting_policy)
     };
+    let tie_break = options.tiebreak();
+    let root_utility = get_score_utility(
+        pool.predictor(),
+        &*options,
+        &*time_strategy,
+        &starting_tree,
+        starting_point,
+        starting_color,
+        starting_value
+    );
 
     // enqueue this tree search
     let root = UnsafeCell::new(starting_tree);
-    pool.enqueue(root.get(), options, time_strategy, starting_point.clone())?;
+    let mut starting_board = starting_point.clone();
+    starting_board.set_to_move(starting_color);
+
+    pool.enqueue(root.get(), options, time_strategy, root_utility, starting_board)?;
 
     // choose the best move according to the search tree
     let root = UnsafeCell::into_inner(root);
-    let (value, index) = root.best(if !deterministic && starting_point.count() < 8 {
-        *config::TEMPERATURE
+    let (value, index) = root.best_with_min_visits(if starting_point.count() < 8 {
+        selection_temperature
     } else {
         0.0
-    });
+    }, min_visits, tie_break);
 
     #[cfg(feature = "trace-mcts")]
     eprintln!("{}", tree::to_sgf::<dg_go::utils::sgf::CGoban>(&root, starting_point, true));
@@ -202,7 +463,7 @@ pub fn predict(
 /// Returns a weighted random komi between `-7.5` to `7.5`, with the most common
 /// ones being `7.5`, `6.5`, and `0.5`.
 ///
-/// - 40% chance of `7.5`
+/// - 40% chance of `recommended_komi(19)`, i.e. `7.5`
 /// - 40% chance of `6.5`
 /// - 10% chance of `0.5`
 /// - 10% chance of a random komi between `-7.5` and `7.5`.
@@ -211,7 +472,7 @@ fn get_random_komi() -> f32 {
     let value = thread_rng().gen::<f32>();
 
     if value < 0.4 {
-        7.5
+        recommended_komi(19)
     } else if value < 0.8 {
         6.5
     } else if value < 0.9 {
@@ -225,11 +486,13 @@ fn get_random_komi() -> f32 {
 
 #[cfg(test)]
 mod tests {
-    use dg_go::{Board, Color};
+    use dg_go::{Board, Color, Point};
     use super::*;
 
-    use options::StandardDeterministicSearch;
-    use predictors::{RandomPredictor, NanPredictor};
+    use options::{StandardDeterministicSearch, TieBreak};
+    use predictors::{MockPredictor, RandomPredictor, NanPredictor};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
 
     #[test]
     fn valid_komi() {
@@ -244,7 +507,7 @@ mod tests {
 
     #[test]
     fn no_allowed_moves() {
-        let pool = Pool::with_capacity(Box::new(RandomPredictor::default()), 1);
+        let pool = Pool::with_capacity(Box::new(RandomPredictor::default()), 1, None);
         let mut root = tree::Node::new(Color::Black, 0.0, vec! [1.0; 362]);
 
         for i in 0..362 {
@@ -260,13 +523,394 @@ mod tests {
             Color::Black
         ).expect("could not predict a position");
 
-        assert_eq!(tree.best(0.0), (::std::f32::NEG_INFINITY, 361));
+        assert_eq!(tree.best(0.0, TieBreak::default()), (::std::f32::NEG_INFINITY, 361));
+    }
+
+    #[test]
+    fn wrong_color_starting_tree() {
+        let pool = Pool::with_capacity(Box::new(RandomPredictor::default()), 1, None);
+        let mut root = tree::Node::new(Color::White, 0.0, vec! [1.0; 362]);
+
+        for i in 0..362 {
+            root.disqualify(i);
+        }
+
+        let (_value, index, tree) = predict(
+            &pool,
+            Box::new(StandardDeterministicSearch::new()),
+            Box::new(time_control::RolloutLimit::new(100)),
+            Some(root),
+            &Board::new(7.5),
+            Color::Black
+        ).expect("could not predict a position");
+
+        assert_eq!(tree.to_move, Color::Black);
+        assert!(index < 361, "{}", index);
+    }
+
+    struct CappedSearch;
+
+    impl SearchOptions for CappedSearch {
+        fn policy_checker(&self, board: &Board, to_move: Color) -> Box<dyn options::PolicyChecker> {
+            StandardDeterministicSearch::new().policy_checker(board, to_move)
+        }
+
+        fn deterministic(&self) -> bool {
+            true
+        }
+
+        fn max_nodes(&self) -> usize {
+            10
+        }
+    }
+
+    #[test]
+    fn max_nodes() {
+        let (_value, _index, tree) = predict(
+            &Pool::with_capacity(Box::new(RandomPredictor::default()), 1, None),
+            Box::new(CappedSearch),
+            Box::new(time_control::RolloutLimit::new(100)),
+            None,
+            &Board::new(7.5),
+            Color::Black
+        ).expect("could not predict a position");
+
+        assert!(tree.size() <= 10, "{} <= 10", tree.size());
+        assert!(tree.total_count > 10, "{} > 10", tree.total_count);
+        assert_eq!(tree.vtotal_count, 0);
+    }
+
+    struct ScoreUtilitySearch;
+
+    impl SearchOptions for ScoreUtilitySearch {
+        fn policy_checker(&self, board: &Board, to_move: Color) -> Box<dyn options::PolicyChecker> {
+            StandardDeterministicSearch::new().policy_checker(board, to_move)
+        }
+
+        fn deterministic(&self) -> bool {
+            true
+        }
+
+        fn score_utility_weight(&self) -> f32 {
+            1.0
+        }
+    }
+
+    /// Returns a board where black has a single group with two eyes, at
+    /// `A19` and `A17`, so filling the corner eye loses the group. The rest
+    /// of the board is a white group that is also alive.
+    fn two_eyed_board() -> Board {
+        let mut board = Board::new(7.5);
+
+        for point in Point::all() {
+            if point.x() < 2 {
+                if point != Point::new(0, 0) && point != Point::new(0, 2) {
+                    board.place(Color::Black, point);
+                }
+            } else if point != Point::new(18, 18) && point != Point::new(18, 16) {
+                board.place(Color::White, point);
+            }
+        }
+
+        board
+    }
+
+    #[test]
+    fn score_utility() {
+        let options = ScoreUtilitySearch;
+        let board = two_eyed_board();
+        let mut prior = vec! [f32::NEG_INFINITY; 362];
+
+        prior[Point::new(0, 0).to_packed_index()] = 0.5;
+        prior[Point::new(0, 2).to_packed_index()] = 0.25;
+        prior[361] = 0.25;
+
+        let root = tree::Node::new(Color::Black, 0.99, prior);
+        let time_strategy = time_control::RolloutLimit::new(1);
+        let utility = get_score_utility(&MockPredictor::constant(1.0), &options, &time_strategy, &root, &board, Color::Black, 0.99)
+            .expect("could not get the score utility");
+
+        assert!(utility[361] > 0.0, "{}", utility[361]);
+        assert!(utility[Point::new(0, 0).to_packed_index()] < 0.0, "{}", utility[0]);
+        assert_eq!(utility[Point::new(3, 3).to_packed_index()], 0.0);
+        assert!(get_score_utility(&MockPredictor::constant(1.0), &options, &time_strategy, &root, &board, Color::Black, 0.5).is_none());
+    }
+
+    #[test]
+    fn score_utility_expired() {
+        let options = ScoreUtilitySearch;
+        let board = two_eyed_board();
+        let mut prior = vec! [f32::NEG_INFINITY; 362];
+
+        prior[Point::new(0, 0).to_packed_index()] = 0.5;
+        prior[361] = 0.5;
+
+        let root = tree::Node::new(Color::Black, 0.99, prior);
+        let time_strategy = time_control::RolloutLimit::new(0);
+
+        assert!(get_score_utility(&MockPredictor::constant(1.0), &options, &time_strategy, &root, &board, Color::Black, 0.99).is_none());
+    }
+
+    #[test]
+    fn score_utility_changes_move() {
+        let board = two_eyed_board();
+        let pool = Pool::with_capacity(Box::new(MockPredictor::constant(1.0)), 1, None);
+        let (_value, without_utility, _tree) = predict(
+            &pool,
+            Box::new(StandardDeterministicSearch::new()),
+            Box::new(time_control::RolloutLimit::new(30)),
+            None,
+            &board,
+            Color::Black
+        ).expect("could not predict a position");
+        let (_value, with_utility, _tree) = predict(
+            &pool,
+            Box::new(ScoreUtilitySearch),
+            Box::new(time_control::RolloutLimit::new(30)),
+            None,
+            &board,
+            Color::Black
+        ).expect("could not predict a position");
+
+        assert_ne!(without_utility, 361);
+        assert_eq!(with_utility, 361);
+    }
+
+    /// Returns a predictor that does not produce any response for the first
+    /// `num_failures` queries.
+    fn flaky_predictor(num_failures: usize) -> MockPredictor {
+        let num_failures = AtomicUsize::new(num_failures);
+
+        MockPredictor::new(move |_features, batch_size| {
+            if num_failures.fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| n.checked_sub(1)).is_ok() {
+                vec! []
+            } else {
+                vec! [Prediction::new(f16::from(0.5), vec! [f16::from(1.0); 362]); batch_size]
+            }
+        })
+    }
+
+    /// Returns a predictor with a transposition table, and the number of
+    /// calls to `predict` that it has received.
+    fn caching_predictor() -> (MockPredictor, Arc<AtomicUsize>) {
+        let num_predict = Arc::new(AtomicUsize::new(0));
+        let predictor = {
+            let num_predict = num_predict.clone();
+
+            MockPredictor::new(move |_features, batch_size| {
+                num_predict.fetch_add(1, Ordering::AcqRel);
+
+                (0..batch_size)
+                    .map(|i| Prediction::new(f16::from(0.125 * i as f32), vec! [f16::from(1.0); 362]))
+                    .collect()
+            })
+        };
+
+        (predictor.with_cache(), num_predict)
+    }
+
+    /// Returns a predictor whose value is the first feature of the first
+    /// vertex, which depends on the komi of the board.
+    fn komi_predictor() -> MockPredictor {
+        MockPredictor::new(|features, batch_size| {
+            (0..batch_size)
+                .map(|i| Prediction::new(features[i * features::Default::size()], vec! [f16::from(1.0); 362]))
+                .collect()
+        })
+    }
+
+    struct EnsembleSearch;
+
+    impl SearchOptions for EnsembleSearch {
+        fn policy_checker(&self, board: &Board, to_move: Color) -> Box<dyn options::PolicyChecker> {
+            StandardDeterministicSearch::new().policy_checker(board, to_move)
+        }
+
+        fn deterministic(&self) -> bool {
+            true
+        }
+
+        fn root_symmetries(&self) -> usize {
+            1
+        }
+
+        fn leaf_symmetries(&self) -> usize {
+            8
+        }
+    }
+
+    /// Returns a predictor, and the batch size of every query that it has
+    /// received.
+    fn batch_size_predictor() -> (MockPredictor, Arc<Mutex<Vec<usize>>>) {
+        let batch_sizes = Arc::new(Mutex::new(vec! []));
+        let predictor = {
+            let batch_sizes = batch_sizes.clone();
+
+            MockPredictor::new(move |_features, batch_size| {
+                batch_sizes.lock().expect("could not acquire lock").push(batch_size);
+
+                vec! [Prediction::new(f16::from(0.0), vec! [f16::from(1.0); 362]); batch_size]
+            })
+        };
+
+        (predictor, batch_sizes)
+    }
+
+    #[test]
+    fn root_and_leaf_symmetries() {
+        let (predictor, batch_sizes) = batch_size_predictor();
+        let pool = Pool::with_capacity(Box::new(predictor), 1, None);
+        let (_value, _index, tree) = predict(
+            &pool,
+            Box::new(EnsembleSearch),
+            Box::new(time_control::RolloutLimit::new(10)),
+            None,
+            &Board::new(7.5),
+            Color::Black
+        ).expect("could not predict a position");
+
+        let batch_sizes = batch_sizes.lock().expect("could not acquire lock");
+
+        assert!(tree.total_count >= 10, "{}", tree.total_count);
+        assert_eq!(batch_sizes[0], 1);
+        assert!(batch_sizes.len() > 1);
+        assert!(batch_sizes[1..].iter().all(|&batch_size| batch_size == 8), "{:?}", batch_sizes);
+    }
+
+    #[test]
+    fn full_forward_root_symmetries() {
+        let (predictor, batch_sizes) = batch_size_predictor();
+        let options: Box<dyn SearchOptions + Sync> = Box::new(EnsembleSearch);
+        let (value, policy) = full_forward(&predictor, &options, &Board::new(7.5), Color::Black).expect("could not evaluate board");
+
+        assert_eq!(*batch_sizes.lock().expect("could not acquire lock"), vec! [1]);
+        assert_eq!(value, 0.5);
+        assert!((policy.iter().filter(|p| p.is_finite()).sum::<f32>() - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn full_forward_detailed_symmetries() {
+        let (predictor, _num_predict) = caching_predictor();
+        let options: Box<dyn SearchOptions + Sync> = Box::new(StandardDeterministicSearch::new());
+        let board = Board::new(7.5);
+        let (value, policy, details) = full_forward_detailed(&predictor, &options, &board, Color::Black).expect("could not evaluate board");
+        let (expected_value, expected_policy) = full_forward(&caching_predictor().0, &options, &board, Color::Black).expect("could not evaluate board");
+
+        assert_eq!(value, expected_value);
+        assert_eq!(policy, expected_policy);
+        assert_eq!(details.len(), 8);
+
+        for (detail, &t) in details.iter().zip(symmetry::ALL.iter()) {
+            assert_eq!(detail.transform, t);
+            assert!((detail.policy.iter().filter(|p| p.is_finite()).sum::<f32>() - 1.0).abs() < 1e-3);
+        }
+
+        let mean = details.iter().map(|detail| detail.value).sum::<f32>() / 8.0;
+        assert!((mean - value).abs() < 1e-4, "{} != {}", mean, value);
+        assert!(details.iter().any(|detail| detail.value != details[0].value));
+    }
+
+    #[test]
+    fn full_forward_uses_komi() {
+        let options: Box<dyn SearchOptions + Sync> = Box::new(StandardDeterministicSearch::new());
+        let (low, _policy) = full_forward(&komi_predictor(), &options, &Board::new(0.5), Color::Black).expect("could not evaluate board");
+        let (high, _policy) = full_forward(&komi_predictor(), &options, &Board::new(7.5), Color::Black).expect("could not evaluate board");
+
+        assert_ne!(low, high);
+    }
+
+    #[test]
+    fn full_forward_all_cached() {
+        let (predictor, num_predict) = caching_predictor();
+        let options: Box<dyn SearchOptions + Sync> = Box::new(StandardDeterministicSearch::new());
+        let board = Board::new(7.5);
+        let first = full_forward(&predictor, &options, &board, Color::Black).expect("could not evaluate board");
+
+        assert_eq!(num_predict.load(Ordering::Acquire), 1);
+
+        let second = full_forward(&predictor, &options, &board, Color::Black).expect("could not evaluate board");
+
+        assert_eq!(num_predict.load(Ordering::Acquire), 1);
+        assert_eq!(first.0, second.0);
+        assert_eq!(first.1, second.1);
+    }
+
+    #[test]
+    fn full_forward_retry() {
+        let predictor = flaky_predictor(*config::FORWARD_RETRIES);
+        let options: Box<dyn SearchOptions + Sync> = Box::new(StandardDeterministicSearch::new());
+        let (value, _policy) = full_forward_or_uniform(&predictor, &options, &Board::new(7.5), Color::Black);
+
+        assert_eq!(value, 0.75);
+    }
+
+    #[test]
+    fn full_forward_uniform_fallback() {
+        let predictor = flaky_predictor(*config::FORWARD_RETRIES + 1);
+        let options: Box<dyn SearchOptions + Sync> = Box::new(StandardDeterministicSearch::new());
+        let (value, policy) = full_forward_or_uniform(&predictor, &options, &Board::new(7.5), Color::Black);
+        let num_finite = policy[..362].iter().filter(|p| p.is_finite()).count();
+
+        assert_eq!(value, 0.5);
+        assert!(num_finite > 0);
+
+        for p in policy[..362].iter().filter(|p| p.is_finite()) {
+            assert_eq!(*p, 1.0 / num_finite as f32);
+        }
+    }
+
+    /// Returns the tree of a search of the given board, after the tree has
+    /// been fast-forwarded through `num_passes` consecutive passes.
+    fn predict_after_passes(pool: &Pool, board: &Board, num_passes: usize) -> (f32, usize, tree::Node) {
+        let mut root = predict(
+            pool,
+            Box::new(StandardDeterministicSearch::new()),
+            Box::new(time_control::RolloutLimit::new(100)),
+            None,
+            board,
+            Color::Black
+        ).expect("could not predict a position").2;
+        let mut to_move = Color::Black;
+
+        for _ in 0..num_passes {
+            root = root.forward(361).expect("could not forward through a pass");
+            to_move = to_move.opposite();
+        }
+
+        assert_eq!(root.pass_count, num_passes as i16);
+
+        predict(
+            pool,
+            Box::new(StandardDeterministicSearch::new()),
+            Box::new(time_control::RolloutLimit::new(100)),
+            Some(root),
+            board,
+            to_move
+        ).expect("could not predict a position")
+    }
+
+    #[test]
+    fn forward_through_pass() {
+        let pool = Pool::with_capacity(Box::new(RandomPredictor::default()), 1, None);
+        let mut board = Board::new(7.5);
+        board.place(Color::Black, Point::new(3, 3));
+        board.place(Color::White, Point::new(15, 15));
+
+        for num_passes in 1..=3 {
+            let (value, index, tree) = predict_after_passes(&pool, &board, num_passes);
+
+            assert!(value.is_finite(), "{}", value);
+            assert!(index < 362, "{}", index);
+            assert!(tree.total_count > 0, "{}", tree.total_count);
+            assert!(tree.prior[..362].iter().all(|&p| !p.is_nan()));
+            assert!(tree.prior[..362].iter().any(|&p| p > 0.0));
+        }
     }
 
     #[test]
     fn no_finite_candidates() {
         let (value, index, root) = predict(
-            &Pool::with_capacity(Box::new(NanPredictor::default()), 1),
+            &Pool::with_capacity(Box::new(NanPredictor::default()), 1, None),
             Box::new(StandardDeterministicSearch::new()),
             Box::new(time_control::RolloutLimit::new(1600)),
             None,
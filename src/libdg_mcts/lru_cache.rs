@@ -14,6 +14,7 @@
 
 use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
 use std::ptr;
 
 #[derive(Debug)]
@@ -89,6 +90,28 @@ macro_rules! detach {
     });
 }
 
+/// Iterator over the entries of a `LruCache`, from the least recently accessed
+/// entry to the most recently accessed one.
+pub struct LruIter<'a, K: Clone + 'a, V: Clone + 'a> {
+    current: *const LruEntry<K, V>,
+    _marker: PhantomData<&'a LruEntry<K, V>>
+}
+
+impl<'a, K: Clone + 'a, V: Clone + 'a> Iterator for LruIter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.current.is_null() {
+            None
+        } else {
+            let entry = unsafe { &*self.current };
+            self.current = entry.prev;
+
+            Some((&entry.key, &entry.value))
+        }
+    }
+}
+
 impl<K: Clone + Hash + Eq, V: Clone> LruCache<K, V> {
     pub fn with_capacity(cap: usize) -> LruCache<K, V> {
         LruCache {
@@ -104,6 +127,16 @@ impl<K: Clone + Hash + Eq, V: Clone> LruCache<K, V> {
         self.entries.len()
     }
 
+    /// Returns an iterator over all entries in this cache, from the least
+    /// recently accessed to the most recently accessed. Inserting the entries
+    /// in this order into an empty cache preserves their order.
+    pub fn iter(&self) -> LruIter<'_, K, V> {
+        LruIter {
+            current: self.tail,
+            _marker: PhantomData
+        }
+    }
+
     pub fn get(&mut self, key: &K) -> Option<&V> {
         let key_ref = KeyRef { inner: key };
 
@@ -163,6 +196,19 @@ mod tests {
         assert_eq!(lru.len(), 1000);
     }
 
+    #[test]
+    fn iter_least_recent_first() {
+        let mut lru = LruCache::with_capacity(10);
+
+        for i in 0..4 { lru.insert(&i, 2 * i); }
+        lru.get(&1);
+
+        assert_eq!(
+            lru.iter().map(|(&k, &v)| (k, v)).collect::<Vec<_>>(),
+            vec! [(0, 0), (2, 4), (3, 6), (1, 2)]
+        );
+    }
+
     #[test]
     fn mixed_insert() {
         let mut lru = LruCache::with_capacity(10);
@@ -14,6 +14,7 @@
 
 use dg_go::utils::benson::BensonImpl;
 use dg_go::{Board, Color, Point, IsPartOf};
+use dg_utils::config;
 
 pub trait PolicyChecker {
     /// Returns true if the given move should be considered during search.
@@ -26,6 +27,25 @@ pub trait PolicyChecker {
     fn is_policy_candidate(&self, board: &Board, point: Point) -> bool;
 }
 
+/// How to break ties between root moves with the same number of visits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TieBreak {
+    /// Prefer the move with the largest winrate, and then the largest prior.
+    Winrate,
+
+    /// Prefer the move with the largest prior, and then the largest winrate.
+    Prior,
+
+    /// Prefer the move with the smallest packed index.
+    Index
+}
+
+impl Default for TieBreak {
+    fn default() -> Self {
+        TieBreak::Prior
+    }
+}
+
 pub trait SearchOptions {
     /// Returns the policy checker to use for the given `board`.
     ///
@@ -38,6 +58,92 @@ pub trait SearchOptions {
 
     /// Returns true if the search should be deterministic.
     fn deterministic(&self) -> bool;
+
+    /// Returns true if dirichlet noise should be added to the prior of the
+    /// root node. By default this is the case for non-deterministic searches.
+    fn root_noise(&self) -> bool {
+        !self.deterministic()
+    }
+
+    /// Returns the temperature to use when choosing which move to play at
+    /// the root during the opening, or `0.0` to always play the best move.
+    /// By default this is the case for deterministic searches.
+    fn selection_temperature(&self) -> f32 {
+        if self.deterministic() {
+            0.0
+        } else {
+            *config::TEMPERATURE
+        }
+    }
+
+    /// Returns the maximum number of nodes the search tree may contain before
+    /// the search stops expanding new nodes.
+    fn max_nodes(&self) -> usize {
+        *config::MAX_NODES
+    }
+
+    /// Returns the scale of the minimum number of visits each candidate move
+    /// at the root must receive. A candidate with prior `p` is always
+    /// selected if it has fewer than `sqrt(k * p * N)` visits, where `N` is
+    /// the total number of visits to the root. This keeps secondary
+    /// candidates from being starved during very deep searches. A value of
+    /// `0.0` disables the floor.
+    fn min_exploration_visits(&self) -> f32 {
+        *config::MIN_EXPLORATION_VISITS
+    }
+
+    /// Returns the factor to scale the prior of the passing move with, before
+    /// the policy is normalized. This makes it possible to discourage passing
+    /// moves, without forbidding them entirely.
+    ///
+    /// # Arguments
+    ///
+    /// * `board` -
+    ///
+    fn pass_prior_scale(&self, _board: &Board) -> f32 {
+        1.0
+    }
+
+    /// Returns the minimum number of visits the most visited candidate move at
+    /// the root must have before the result of the search is trusted. The
+    /// search is never terminated early before this is reached, and if it is
+    /// still not reached when the time runs out then the candidate move with
+    /// the largest prior value is played instead. A value of `0` disables
+    /// this.
+    fn min_visits_for_selection(&self) -> i32 {
+        *config::MIN_SELECTION_VISITS
+    }
+
+    /// Returns the weight of the score margin, relative to the winrate, of each
+    /// candidate move at the root when the position is clearly won. This makes
+    /// the search prefer moves that win by more, instead of moves that win by
+    /// the same probability but a smaller margin. A value of `0.0` preserves
+    /// pure winrate play.
+    fn score_utility_weight(&self) -> f32 {
+        *config::SCORE_UTILITY_WEIGHT
+    }
+
+    /// Returns the number of symmetries that the root position is evaluated
+    /// with, the prior and value of the root is the average over all of them.
+    /// This is clamped to between `1` and `8`.
+    fn root_symmetries(&self) -> usize {
+        8
+    }
+
+    /// Returns the number of (randomly chosen) symmetries that each leaf is
+    /// evaluated with, the prior and value of the leaf is the average over
+    /// all of them. This is clamped to between `1` and `8`, and any value
+    /// larger than `1` evaluates the leaf immediately, instead of batching it
+    /// together with the leaves of other workers.
+    fn leaf_symmetries(&self) -> usize {
+        1
+    }
+
+    /// Returns how to break ties between the candidate moves at the root that
+    /// have the same number of visits when choosing the best move.
+    fn tiebreak(&self) -> TieBreak {
+        TieBreak::default()
+    }
 }
 
 pub struct StandardPolicyChecker {
@@ -108,15 +214,19 @@ impl SearchOptions for StandardDeterministicSearch {
 
 pub struct ScoringPolicyChecker {
     is_valid: [bool; Point::MAX],
+    filled: FilledCounts,
+    forbid_first_line: bool,
     to_move: Color
 }
 
 impl ScoringPolicyChecker {
-    fn new(board: &Board, to_move: Color) -> ScoringPolicyChecker {
+    fn new(board: &Board, to_move: Color, forbid_first_line: bool) -> ScoringPolicyChecker {
         let benson_black = BensonImpl::new(board, Color::Black);
         let benson_white = BensonImpl::new(board, Color::White);
         let mut out = Self {
             is_valid: [false; Point::MAX],
+            filled: FilledCounts::new(board, to_move),
+            forbid_first_line,
             to_move: to_move
         };
 
@@ -126,6 +236,30 @@ impl ScoringPolicyChecker {
 
         out
     }
+
+    /// Returns true if the given move is on the first line, and neither
+    /// captures any stones nor connects to one of our own stones.
+    ///
+    /// # Arguments
+    ///
+    /// * `board` -
+    /// * `point` -
+    ///
+    fn is_bad_first_line(&self, board: &Board, point: Point) -> bool {
+        let (x, y) = (point.x(), point.y());
+
+        if x != 0 && x != 18 && y != 0 && y != 18 {
+            return false;
+        }
+
+        let is_connect = CROSS.iter().any(|&(dx, dy)| {
+            let other = point.offset(dx, dy);
+
+            board.is_part_of(other) && board.at(other) == Some(self.to_move)
+        });
+
+        !is_connect && !board.is_capture(self.to_move, point)
+    }
 }
 
 impl PolicyChecker for ScoringPolicyChecker {
@@ -133,28 +267,45 @@ impl PolicyChecker for ScoringPolicyChecker {
         point != Point::default() &&
             self.is_valid[point] &&
             board.is_valid(self.to_move, point) &&
-            !is_eye(&board, self.to_move, point)
+            !self.filled.is_eye(point) &&
+            !(self.forbid_first_line && self.is_bad_first_line(board, point))
     }
 }
 
 #[derive(Clone)]
-pub struct ScoringSearch;
+pub struct ScoringSearch {
+    forbid_first_line: bool
+}
 
 impl ScoringSearch {
     pub fn new() -> Self {
-        Self { }
+        Self { forbid_first_line: false }
+    }
+
+    /// Returns these options, where first line moves that neither capture any
+    /// stones nor connect to an existing stone are never considered if
+    /// `forbid_first_line` is true. This keeps the scoring playouts from
+    /// playing self-destructive moves along the edge.
+    ///
+    /// # Arguments
+    ///
+    /// * `forbid_first_line` -
+    ///
+    pub fn with_forbid_first_line(mut self, forbid_first_line: bool) -> Self {
+        self.forbid_first_line = forbid_first_line;
+        self
     }
 }
 
 impl Default for ScoringSearch {
     fn default() -> Self {
-        Self::new()
+        Self::new().with_forbid_first_line(*config::FORBID_FIRST_LINE)
     }
 }
 
 impl SearchOptions for ScoringSearch {
     fn policy_checker(&self, board: &Board, to_move: Color) -> Box<dyn PolicyChecker> {
-        Box::new(ScoringPolicyChecker::new(board, to_move))
+        Box::new(ScoringPolicyChecker::new(board, to_move, self.forbid_first_line))
     }
 
     fn deterministic(&self) -> bool {
@@ -162,54 +313,76 @@ impl SearchOptions for ScoringSearch {
     }
 }
 
-/// Returns true if the given vertex is is occupied by a stone of the same color.
-///
-/// # Arguments
-///
-/// * `board` -
-/// * `color` -
-/// * `point` -
-/// * `dx` -
-/// * `dy` -
-///
-fn is_vertex_filled(board: &Board, color: Color, point: Point, dx: i8, dy: i8) -> bool {
-    let other = point.offset(dx as isize, dy as isize);
-
-    board.is_part_of(other) && board.at(other) == Some(color)
+const CROSS: [(isize, isize); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+const DIAGONAL: [(isize, isize); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+
+/// The number of stones of a single color that are directly adjacent to, and
+/// diagonal to, each vertex of a board. Since these are computed once per
+/// board they make `is_eye` queries constant time.
+struct FilledCounts {
+    cross: [u8; Point::MAX],
+    diagonal: [u8; Point::MAX]
 }
 
-/// Returns true if the given move would fill ones own eye. An eye in this case
-/// is recognized as an empty spot that is surrounded by at least 7 stones of
-/// the same color. This will miss some _complicated_ eyes, but this is good
-/// enough for the heuristic.
-///
-/// # Arguments
-///
-/// * `board` -
-/// * `color` -
-/// * `point` -
-///
-fn is_eye(board: &Board, color: Color, point: Point) -> bool {
-    const CROSS: [(i8, i8); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
-    const DIAGONAL: [(i8, i8); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
-
-    let num_cross = CROSS.iter()
-        .filter(|(dx, dy)| is_vertex_filled(board, color, point, *dx, *dy))
-        .count();
-    let num_diagonal = DIAGONAL.iter()
-        .filter(|(dx, dy)| is_vertex_filled(board, color, point, *dx, *dy))
-        .count();
-
-    // distinguish between the three different cases, (i) an eye in the middle,
-    // (ii) an eye in along the edge, and (iii) an eye in the corner.
-    let (x, y) = (point.x(), point.y());
-
-    if (x == 0 || x == 18) && (y == 0 || y == 18) {
-        num_cross >= 2 && num_diagonal >= 1  // corner move
-    } else if x == 0 || x == 18 || y == 0 || y == 18 {
-        num_cross >= 3 && num_diagonal >= 2  // edge
-    } else {
-        num_cross >= 4 && num_diagonal >= 3
+impl FilledCounts {
+    /// Returns the filled neighbour counts of the stones of the given color.
+    ///
+    /// # Arguments
+    ///
+    /// * `board` -
+    /// * `color` -
+    ///
+    fn new(board: &Board, color: Color) -> Self {
+        let mut out = Self {
+            cross: [0; Point::MAX],
+            diagonal: [0; Point::MAX]
+        };
+
+        for point in Point::all().filter(|&point| board.at(point) == Some(color)) {
+            for &(dx, dy) in &CROSS {
+                let other = point.offset(dx, dy);
+
+                if board.is_part_of(other) {
+                    out.cross[other] += 1;
+                }
+            }
+
+            for &(dx, dy) in &DIAGONAL {
+                let other = point.offset(dx, dy);
+
+                if board.is_part_of(other) {
+                    out.diagonal[other] += 1;
+                }
+            }
+        }
+
+        out
+    }
+
+    /// Returns true if the given move would fill ones own eye. An eye in this
+    /// case is recognized as an empty spot that is surrounded by at least 7
+    /// stones of the same color. This will miss some _complicated_ eyes, but
+    /// this is good enough for the heuristic.
+    ///
+    /// # Arguments
+    ///
+    /// * `point` -
+    ///
+    fn is_eye(&self, point: Point) -> bool {
+        let num_cross = self.cross[point];
+        let num_diagonal = self.diagonal[point];
+
+        // distinguish between the three different cases, (i) an eye in the middle,
+        // (ii) an eye in along the edge, and (iii) an eye in the corner.
+        let (x, y) = (point.x(), point.y());
+
+        if (x == 0 || x == 18) && (y == 0 || y == 18) {
+            num_cross >= 2 && num_diagonal >= 1  // corner move
+        } else if x == 0 || x == 18 || y == 0 || y == 18 {
+            num_cross >= 3 && num_diagonal >= 2  // edge
+        } else {
+            num_cross >= 4 && num_diagonal >= 3
+        }
     }
 }
 
@@ -217,6 +390,55 @@ fn is_eye(board: &Board, color: Color, point: Point) -> bool {
 mod tests {
     use super::*;
 
+    struct NoiselessSearch;
+
+    impl SearchOptions for NoiselessSearch {
+        fn policy_checker(&self, _board: &Board, to_move: Color) -> Box<dyn PolicyChecker> {
+            Box::new(StandardPolicyChecker::new(to_move))
+        }
+
+        fn deterministic(&self) -> bool {
+            false
+        }
+
+        fn root_noise(&self) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn root_noise_and_temperature() {
+        assert!(StandardSearch::new().root_noise());
+        assert_eq!(StandardSearch::new().selection_temperature(), *config::TEMPERATURE);
+        assert!(!StandardDeterministicSearch::new().root_noise());
+        assert_eq!(StandardDeterministicSearch::new().selection_temperature(), 0.0);
+        assert!(!NoiselessSearch.root_noise());
+        assert_eq!(NoiselessSearch.selection_temperature(), *config::TEMPERATURE);
+    }
+
+    #[test]
+    fn forbid_first_line() {
+        let mut board = Board::new(0.5);
+        board.place(Color::Black, Point::new(3, 1));
+        board.place(Color::White, Point::new(9, 0));
+        board.place(Color::Black, Point::new(8, 0));
+        board.place(Color::Black, Point::new(9, 1));
+
+        let checker = ScoringSearch::new().policy_checker(&board, Color::Black);
+        assert!(checker.is_policy_candidate(&board, Point::new(5, 0)));
+
+        let checker = ScoringSearch::new().with_forbid_first_line(true).policy_checker(&board, Color::Black);
+        assert_eq!(ScoringSearch::default().forbid_first_line, *config::FORBID_FIRST_LINE);
+        assert!(!checker.is_policy_candidate(&board, Point::new(5, 0)));
+        assert!(checker.is_policy_candidate(&board, Point::new(3, 0)));  // connect
+        assert!(checker.is_policy_candidate(&board, Point::new(10, 0)));  // capture
+        assert!(checker.is_policy_candidate(&board, Point::new(5, 5)));
+    }
+
+    fn is_eye(board: &Board, color: Color, point: Point) -> bool {
+        FilledCounts::new(board, color).is_eye(point)
+    }
+
     #[test]
     fn corner() {
         let mut board = Board::new(0.5);
@@ -12,6 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hasher;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 
@@ -23,38 +25,100 @@ use dg_utils::types::f16;
 
 pub struct Batch<'a> {
     features: Vec<f16>,
-    events: Vec<Event>,
-    num_batches: &'a AtomicUsize
+    events: Vec<Vec<Event>>,
+    num_batches: &'a AtomicUsize,
+    batch_sizes: &'a [AtomicUsize]
 }
 
 impl<'a> Batch<'a> {
-    pub fn new(features: Vec<f16>, events: Vec<Event>, num_batches: &'a AtomicUsize) -> Self {
-        Self { features, events, num_batches }
+    pub fn new(features: Vec<f16>, events: Vec<Vec<Event>>, num_batches: &'a AtomicUsize, batch_sizes: &'a [AtomicUsize]) -> Self {
+        Self { features, events, num_batches, batch_sizes }
     }
 
+    /// Evaluate the unique features in this batch, and return every event in
+    /// the batch together with the response for its features.
+    ///
+    /// # Arguments
+    ///
+    /// * `server` -
+    ///
     pub fn forward(self, server: &Box<dyn Predictor + Sync>) -> (Vec<Event>, Vec<Prediction>) {
-        let responses = server.predict(&self.features, self.events.len());
+        let transforms = self.events.iter()
+            .map(|events| events[0].transformation)
+            .collect::<Vec<_>>();
+        let responses = server.predict_transformed(&self.features, &transforms);
         self.num_batches.fetch_sub(1, Ordering::AcqRel);
 
-        (self.events, responses)
+        let size_index = self.events.len().min(self.batch_sizes.len() - 1);
+        self.batch_sizes[size_index].fetch_add(1, Ordering::Relaxed);
+
+        // expand the responses for any events that were coalesced into the
+        // same features
+        let mut all_events = Vec::with_capacity(self.events.len());
+        let mut all_responses = Vec::with_capacity(self.events.len());
+
+        for (events, response) in self.events.into_iter().zip(responses.into_iter()) {
+            for event in events {
+                all_events.push(event);
+                all_responses.push(response.clone());
+            }
+        }
+
+        (all_events, all_responses)
     }
 }
 
 pub struct BatcherList {
-    /// The features gathered so far.
+    /// The unique features gathered so far.
     features: Vec<f16>,
 
-    /// The events gathered so far.
-    events: Vec<Event>,
+    /// The hash of each unique features gathered so far.
+    hashes: Vec<u64>,
+
+    /// The events gathered so far, grouped by their features.
+    events: Vec<Vec<Event>>,
 }
 
 impl BatcherList {
     fn new(max_batch_size: usize) -> Self {
         Self {
             features: Vec::with_capacity(2 * max_batch_size * features::Default::size()),
+            hashes: Vec::with_capacity(2 * max_batch_size),
             events: Vec::with_capacity(2 * max_batch_size)
         }
     }
+
+    /// Returns the index of the already pending features that are identical to
+    /// the given `features`, if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - the hash of `features`
+    /// * `features` -
+    ///
+    fn find(&self, hash: u64, features: &[f16]) -> Option<usize> {
+        let size = features::Default::size();
+
+        (0..self.hashes.len()).find(|&i| {
+            self.hashes[i] == hash && self.features[(i * size)..((i + 1) * size)] == *features
+        })
+    }
+}
+
+/// Returns the hash of the given features.
+///
+/// # Arguments
+///
+/// * `features` -
+///
+fn hash_features(features: &[f16]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+
+    for x in features {
+        hasher.write_u16(x.to_bits());
+    }
+
+    hasher.finish()
 }
 
 #[derive(Clone)]
@@ -70,6 +134,9 @@ pub struct Batcher {
 
     /// The maximum number of allowed batches to be live at the same time.
     max_batches: usize,
+
+    /// The number of batches that has been evaluated of each size.
+    batch_sizes: Arc<Vec<AtomicUsize>>,
 }
 
 impl Batcher {
@@ -80,14 +147,43 @@ impl Batcher {
             list: Arc::new(Mutex::new(BatcherList::new(max_batch_size))),
             num_batches: Arc::new(AtomicUsize::new(0)),
             max_batch_size: max_batch_size,
-            max_batches: max_batches
+            max_batches: max_batches,
+            batch_sizes: Arc::new((0..=max_batch_size).map(|_| AtomicUsize::new(0)).collect())
         }
     }
 
+    /// Add the given event to the list of pending events. If identical features
+    /// are already pending then the event is attached to those features instead
+    /// of them being evaluated twice.
+    ///
+    /// # Arguments
+    ///
+    /// * `event` -
+    /// * `features` -
+    ///
     pub fn push(&self, event: Event, features: Vec<f16>) {
+        let hash = hash_features(&features);
         let mut list = self.list.lock().expect("could not acquire batch list lock");
-        list.features.extend_from_slice(&features);
-        list.events.push(event);
+
+        if let Some(index) = list.find(hash, &features) {
+            list.events[index].push(event);
+        } else {
+            list.features.extend_from_slice(&features);
+            list.hashes.push(hash);
+            list.events.push(vec! [event]);
+        }
+    }
+
+    /// Returns the maximum size of a batch.
+    pub fn max_batch_size(&self) -> usize {
+        self.max_batch_size
+    }
+
+    /// Returns the number of batches that has been evaluated so far of each
+    /// size, where the element at index `i` is the number of batches with `i`
+    /// unique features.
+    pub fn batch_size_histogram(&self) -> Vec<usize> {
+        self.batch_sizes.iter().map(|count| count.load(Ordering::Relaxed)).collect()
     }
 
     pub fn push_and_get_batch(&self, event: Event, features: Vec<f16>) -> Option<Batch> {
@@ -109,16 +205,79 @@ impl Batcher {
             if size >= min_batch_size && self.num_batches.compare_exchange_weak(current, current + 1, Ordering::Acquire, Ordering::Relaxed).is_ok() {
                 let split_index = if size >= self.max_batch_size { size - self.max_batch_size } else { 0 };
 
-                Some(
-                    Batch::new(
-                        list.features.split_off(split_index * features::Default::size()),
-                        list.events.split_off(split_index),
-                        self.num_batches.as_ref()
-                    )
-                )
+                let features = list.features.split_off(split_index * features::Default::size());
+                let events = list.events.split_off(split_index);
+                list.hashes.truncate(split_index);
+
+                Some(Batch::new(features, events, self.num_batches.as_ref(), self.batch_sizes.as_ref()))
             } else {
                 None
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::options::StandardSearch;
+    use crate::predictors::MockPredictor;
+    use crate::time_control::RolloutLimit;
+    use crate::tree;
+    use super::super::event::EventKind;
+    use super::super::shared_context::SearchContext;
+    use super::*;
+    use dg_go::utils::symmetry;
+    use dg_go::{Board, Color};
+
+    #[test]
+    fn coalesce_identical_features() {
+        let mut root = tree::Node::new(Color::Black, 0.5, vec! [1.0; 362]);
+        let (tx, _rx) = crossbeam_channel::bounded(1);
+        let search_context = Arc::new(SearchContext::new(
+            0,
+            &mut root,
+            Box::new(StandardSearch::new()),
+            Box::new(RolloutLimit::new(1)),
+            None,
+            Board::new(7.5),
+            tx
+        ));
+        let event = Event {
+            kind: EventKind::Pending,
+            search_context: search_context.clone(),
+            board: Board::new(7.5),
+            transformation: symmetry::Transform::Identity,
+            trace: vec! []
+        };
+        let size = features::Default::size();
+        let batcher = Batcher::new(1);
+
+        batcher.push(event.clone(), vec! [f16::from(1.0); size]);
+        batcher.push(event.clone(), vec! [f16::from(0.0); size]);
+        batcher.push(event.clone(), vec! [f16::from(1.0); size]);
+
+        let num_features = Arc::new(AtomicUsize::new(0));
+        let server: Box<dyn Predictor + Sync> = Box::new({
+            let num_features = num_features.clone();
+
+            MockPredictor::new(move |_features, batch_size| {
+                num_features.fetch_add(batch_size, Ordering::AcqRel);
+
+                (0..batch_size)
+                    .map(|i| Prediction::new(f16::from(i as f32), vec! [f16::from(0.0); 362]))
+                    .collect()
+            })
+        });
+        let (events, responses) = batcher.get_batch(1).expect("could not get batch").forward(&server);
+        let values = responses.iter().map(|response| response.value()).collect::<Vec<_>>();
+
+        assert_eq!(num_features.load(Ordering::Acquire), 2);
+        assert_eq!(events.len(), 3);
+        assert_eq!(values, vec! [0.0, 0.0, 1.0]);
+
+        let histogram = batcher.batch_size_histogram();
+        assert_eq!(histogram.len(), batcher.max_batch_size() + 1);
+        assert_eq!(histogram[2], 1);
+        assert_eq!(histogram.iter().sum::<usize>(), 1);
+    }
+}
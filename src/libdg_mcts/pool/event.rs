@@ -16,11 +16,11 @@ use crate::{Predictor, Prediction, NodeTrace};
 use super::shared_context::SearchContext;
 use dg_go::utils::features::{self, HWC, Features};
 use dg_go::utils::symmetry;
-use dg_go::Board;
+use dg_go::{Board, Color};
 use dg_utils::types::f16;
 
 use rand::prelude::SliceRandom;
-use rand::thread_rng;
+use rand::Rng;
 use std::sync::Arc;
 
 #[derive(Clone)]
@@ -43,10 +43,30 @@ unsafe impl Send for Event {}
 unsafe impl Sync for Event {}
 
 impl Event {
-    pub fn predict(server: &Box<dyn Predictor + Sync>, search_context: Arc<SearchContext>, board: Board, trace: NodeTrace) -> Self {
-        let transformation = *symmetry::ALL.choose(&mut thread_rng()).unwrap();
+    pub fn predict<R: Rng>(
+        server: &Box<dyn Predictor + Sync>,
+        search_context: Arc<SearchContext>,
+        board: Board,
+        trace: NodeTrace,
+        rng: &mut R
+    ) -> Self
+    {
+        let num_symmetries = search_context.options.leaf_symmetries().clamp(1, 8);
         let &(_, last_move, _) = trace.last().unwrap();
         let to_move = last_move.opposite();
+
+        if num_symmetries > 1 {
+            let transformations = symmetry::ALL.choose_multiple(rng, num_symmetries).cloned().collect::<Vec<_>>();
+
+            if let Some(response) = predict_ensemble(&**server, &board, to_move, &transformations) {
+                let kind = EventKind::Insert(response);
+                let transformation = symmetry::Transform::Identity;
+
+                return Self { kind, search_context, board, transformation, trace };
+            }
+        }
+
+        let transformation = *symmetry::ALL.choose(rng).unwrap();
         let kind =
             if let Some(response) = server.fetch(&board, to_move, transformation) {
                 EventKind::Insert(response)
@@ -70,3 +90,58 @@ impl Event {
         (prev_kind, self)
     }
 }
+
+/// Returns the average prediction of the given board over all of the given
+/// symmetries, in the identity orientation. This is evaluated immediately
+/// instead of being batched, and returns `None` if the evaluation failed.
+///
+/// # Arguments
+///
+/// * `server` -
+/// * `board` -
+/// * `to_move` -
+/// * `transformations` - the symmetries to evaluate the board with
+///
+fn predict_ensemble(
+    server: &(dyn Predictor + Sync),
+    board: &Board,
+    to_move: Color,
+    transformations: &[symmetry::Transform]
+) -> Option<Prediction>
+{
+    let mut responses = Vec::with_capacity(transformations.len());
+    let mut new_transformations = Vec::with_capacity(transformations.len());
+
+    for &t in transformations {
+        if let Some(response) = server.fetch(board, to_move, t) {
+            responses.push(Prediction::with_transform(&response, t.inverse()));
+        } else {
+            new_transformations.push(t);
+        }
+    }
+
+    if !new_transformations.is_empty() {
+        let features = features::Default::new(board);
+        let mut features_list = Vec::with_capacity(new_transformations.len() * features::Default::size());
+
+        for &t in &new_transformations {
+            features_list.extend_from_slice(&features.get_features::<HWC, f16>(to_move, t));
+        }
+
+        let new_responses = if let [t] = new_transformations[..] {
+            server.predict_single_transformed(&features_list, t).into_iter().collect()
+        } else {
+            server.predict_transformed(&features_list, &new_transformations)
+        };
+
+        if new_responses.len() != new_transformations.len() {
+            return None;
+        }
+
+        for (response, t) in new_responses.into_iter().zip(new_transformations) {
+            responses.push(Prediction::with_transform(&response, t.inverse()));
+        }
+    }
+
+    Some(Prediction::average(&responses))
+}
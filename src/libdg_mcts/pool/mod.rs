@@ -20,4 +20,4 @@ mod shared_context;
 mod worker_thread;
 
 pub use self::policy_helper::*;
-pub use self::pool::Pool;
+pub use self::pool::{Pool, PoolHandle};
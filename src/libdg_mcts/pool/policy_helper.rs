@@ -103,6 +103,25 @@ pub fn add_valid_candidates(
     }
 }
 
+/// Scale the prior of the passing move in the given policy by
+/// `SearchOptions::pass_prior_scale`, unless passing is not a candidate move.
+///
+/// # Arguments
+///
+/// * `options` -
+/// * `board` -
+/// * `policy` - the policy to scale in-place
+///
+pub fn scale_pass_prior(
+    options: &Box<dyn SearchOptions + Sync>,
+    board: &Board,
+    policy: &mut [f32]
+) {
+    if policy[361].is_finite() {
+        policy[361] *= options.pass_prior_scale(board);
+    }
+}
+
 /// Normalize the given vector so that its elements sums to `sum_to`.
 ///
 /// # Arguments
@@ -132,3 +151,45 @@ pub fn normalize_policy(policy: &mut [f32], sum_to: f32) {
         debug_assert!(!policy[i].is_nan(), "found NaN at index {}, total sum = {}", i, policy_sum);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::options::{PolicyChecker, StandardDeterministicSearch};
+    use super::*;
+
+    struct NoEarlyPassSearch;
+
+    impl SearchOptions for NoEarlyPassSearch {
+        fn policy_checker(&self, board: &Board, to_move: Color) -> Box<dyn PolicyChecker> {
+            StandardDeterministicSearch::new().policy_checker(board, to_move)
+        }
+
+        fn deterministic(&self) -> bool {
+            true
+        }
+
+        fn pass_prior_scale(&self, board: &Board) -> f32 {
+            if board.count() < 200 { 0.0 } else { 1.0 }
+        }
+    }
+
+    #[test]
+    fn scale_pass_prior_early() {
+        let options: Box<dyn SearchOptions + Sync> = Box::new(NoEarlyPassSearch);
+        let mut policy = vec! [0.5; 362];
+
+        scale_pass_prior(&options, &Board::new(7.5), &mut policy);
+        assert_eq!(policy[361], 0.0);
+        assert_eq!(policy[360], 0.5);
+    }
+
+    #[test]
+    fn scale_pass_prior_not_candidate() {
+        let options: Box<dyn SearchOptions + Sync> = Box::new(NoEarlyPassSearch);
+        let mut policy = vec! [0.5; 362];
+        policy[361] = f32::NEG_INFINITY;
+
+        scale_pass_prior(&options, &Board::new(7.5), &mut policy);
+        assert_eq!(policy[361], f32::NEG_INFINITY);
+    }
+}
@@ -22,13 +22,16 @@ use dg_utils::config;
 use crossbeam_channel;
 use crossbeam_utils::Backoff;
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::ops::Deref;
 use std::sync::{Arc, Barrier, Mutex, RwLock};
 use std::thread::{self, JoinHandle};
 
 use super::shared_context::{SharedContext, SearchContext};
 use super::worker_thread::Worker;
 
-#[derive(Clone)]
+/// A pool of worker threads that evaluates search trees. The worker threads
+/// are terminated as soon as the pool is dropped, so if you need to share a
+/// pool between threads you should do so through a `PoolHandle`.
 pub struct Pool {
     shared_context: Arc<SharedContext>,
     searches_count: Arc<AtomicUsize>,
@@ -39,12 +42,22 @@ pub struct Pool {
 
 impl Pool {
     pub fn new(predictor: Box<dyn Predictor + Sync>) -> Self {
-        Self::with_capacity(predictor, *config::NUM_THREADS)
+        Self::with_capacity(predictor, *config::NUM_THREADS, None)
     }
 
-    pub fn with_capacity(predictor: Box<dyn Predictor + Sync>, capacity: usize) -> Self {
+    /// Returns a pool with `capacity` worker threads. If a `seed` is given then
+    /// each worker seeds its random number generator from it, which makes the
+    /// search reproducible for a fixed number of worker threads.
+    ///
+    /// # Arguments
+    ///
+    /// * `predictor` -
+    /// * `capacity` -
+    /// * `seed` -
+    ///
+    pub fn with_capacity(predictor: Box<dyn Predictor + Sync>, capacity: usize, seed: Option<u64>) -> Self {
         let out = Self {
-            shared_context: Arc::new(SharedContext::new(predictor)),
+            shared_context: Arc::new(SharedContext::new(predictor, seed)),
             searches_count: Arc::new(AtomicUsize::new(0)),
             searches: Arc::new(RwLock::new(Vec::with_capacity(8))),
             handles: Arc::new(Mutex::new(Vec::with_capacity(64))),
@@ -56,6 +69,21 @@ impl Pool {
     }
 }
 
+/// A cheap to clone, and thread-safe, handle to a `Pool`. The worker threads
+/// of the pool are kept alive until the last handle has been dropped.
+#[derive(Clone)]
+pub struct PoolHandle {
+    pool: Arc<Pool>
+}
+
+impl Deref for PoolHandle {
+    type Target = Pool;
+
+    fn deref(&self) -> &Self::Target {
+        self.pool.as_ref()
+    }
+}
+
 impl Drop for Pool {
     fn drop(&mut self) {
         self.shared_context.is_running.store(false, Ordering::Release);
@@ -82,6 +110,18 @@ impl Pool {
         }
     }
 
+    /// Returns a handle to this pool that can be cloned and shared between
+    /// threads.
+    pub fn into_handle(self) -> PoolHandle {
+        PoolHandle { pool: Arc::new(self) }
+    }
+
+    /// Returns the number of batches that has been evaluated by this pool of
+    /// each size, see `Batcher::batch_size_histogram`.
+    pub fn batch_size_histogram(&self) -> Vec<usize> {
+        self.shared_context.batcher.batch_size_histogram()
+    }
+
     /// Returns the predictor that this pool encapsule.
     pub fn predictor(&self) -> &dyn Predictor {
         self.shared_context.predictor.as_ref()
@@ -92,13 +132,15 @@ impl Pool {
     /// returns. The `root` is modified in-place.
     ///
     /// After this function returns the `root` is guaranteed to not be modified
-    /// by any worker thread anymore.
+    /// by any worker thread anymore. Returns the number of nodes in the search
+    /// tree when the search finished.
     ///
     /// # Arguments
     ///
     /// * `root` -
     /// * `options` -
     /// * `time_strategy` -
+    /// * `root_utility` -
     /// * `starting_point` -
     ///
     pub fn enqueue(
@@ -106,8 +148,9 @@ impl Pool {
         root: *mut tree::Node,
         options: Box<dyn SearchOptions + Sync>,
         time_strategy: Box<dyn TimeStrategy + Sync>,
+        root_utility: Option<Box<[f32; 368]>>,
         starting_point: Board
-    ) -> Option<()>
+    ) -> Option<usize>
     {
         // add this board position to the worker pool, and **make sure** to drop
         // the write-lock :-)
@@ -118,6 +161,7 @@ impl Pool {
                 root,
                 options,
                 time_strategy,
+                root_utility,
                 starting_point,
                 tx
             )
@@ -129,7 +173,7 @@ impl Pool {
         self.ensure_threads();
 
         // wait for the worker pool to finish their work
-        let result = rx.recv().ok();
+        let result = rx.recv().ok().map(|_| search_context.num_nodes.load(Ordering::Acquire));
         drop(rx);
 
         // wait until everyone has dropped the `search_context` from their
@@ -146,5 +190,94 @@ impl Pool {
 
 #[cfg(test)]
 mod tests {
-    // pass
+    use crate::options::StandardSearch;
+    use crate::predictor::Prediction;
+    use crate::predictors::{MockPredictor, RandomPredictor};
+    use crate::time_control::RolloutLimit;
+    use dg_go::Color;
+    use dg_utils::types::f16;
+    use std::sync::atomic::AtomicBool;
+    use std::time::Duration;
+    use super::*;
+
+    /// Returns a predictor that takes a long time to evaluate each batch, so
+    /// that the workers can probe much faster than the positions are evaluated.
+    fn slow_predictor() -> MockPredictor {
+        MockPredictor::new(|_features, batch_size| {
+            thread::sleep(Duration::from_millis(2));
+
+            vec! [Prediction::new(f16::from(0.0), vec! [f16::from(1.0 / 362.0); 362]); batch_size]
+        }).with_max_num_threads(4)
+    }
+
+    fn assert_send_sync<T: Send + Sync>() {
+        // pass
+    }
+
+    #[test]
+    fn handle_is_send_sync() {
+        assert_send_sync::<Pool>();
+        assert_send_sync::<PoolHandle>();
+    }
+
+    #[test]
+    fn handle_outlives_clone() {
+        let handle = Pool::with_capacity(Box::new(RandomPredictor::default()), 1, None).into_handle();
+        let other = handle.clone();
+
+        thread::spawn(move || drop(other)).join().expect("could not join thread");
+
+        assert!(handle.shared_context.is_running.load(Ordering::Acquire));
+        assert_eq!(handle.predictor().max_num_threads(), RandomPredictor::default().max_num_threads());
+    }
+
+    #[test]
+    fn max_queue_depth() {
+        let mut shared_context = SharedContext::new(Box::new(slow_predictor()), None);
+        shared_context.max_queue_depth = 3;
+
+        let pool = Pool {
+            shared_context: Arc::new(shared_context),
+            searches_count: Arc::new(AtomicUsize::new(0)),
+            searches: Arc::new(RwLock::new(Vec::with_capacity(8))),
+            handles: Arc::new(Mutex::new(Vec::with_capacity(64))),
+            capacity: 8
+        };
+        pool.ensure_threads();
+
+        // sample the number of positions in flight while the search is running
+        let is_done = Arc::new(AtomicBool::new(false));
+        let monitor = {
+            let shared_context = pool.shared_context.clone();
+            let is_done = is_done.clone();
+
+            thread::spawn(move || {
+                let mut max_in_flight = 0;
+
+                while !is_done.load(Ordering::Acquire) {
+                    max_in_flight = max_in_flight.max(shared_context.num_in_flight.load(Ordering::Acquire));
+                    thread::yield_now();
+                }
+
+                max_in_flight
+            })
+        };
+
+        let mut root = tree::Node::new(Color::Black, 0.5, vec! [1.0 / 362.0; 362]);
+        let num_nodes = pool.enqueue(
+            &mut root,
+            Box::new(StandardSearch::new()),
+            Box::new(RolloutLimit::new(100)),
+            None,
+            Board::new(7.5)
+        );
+        is_done.store(true, Ordering::Release);
+
+        let max_in_flight = monitor.join().expect("could not join monitor thread");
+
+        assert!(num_nodes.is_some());
+        assert!(root.total_count >= 100, "{}", root.total_count);
+        assert_eq!(max_in_flight, 3);
+        assert_eq!(pool.shared_context.num_in_flight.load(Ordering::Acquire), 0);
+    }
 }
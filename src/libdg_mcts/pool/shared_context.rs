@@ -17,11 +17,14 @@ use crate::options::SearchOptions;
 use crate::time_control::TimeStrategy;
 use crate::tree;
 use crate::predictor::Predictor;
+use dg_utils::config;
 use super::batch::Batcher;
 use super::event::Event;
 
 use concurrent_queue::ConcurrentQueue;
 use crossbeam_channel::Sender;
+use rand::rngs::SmallRng;
+use rand::SeedableRng;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
 ///
@@ -30,8 +33,11 @@ pub struct SearchContext {
     pub root: *mut tree::Node,
     pub options: Box<dyn SearchOptions + Sync>,
     pub time_strategy: Box<dyn TimeStrategy + Sync>,
+    pub root_utility: Option<Box<[f32; 368]>>,
     pub starting_point: Board,
-    pub response_channel: Sender<()>
+    pub response_channel: Sender<()>,
+    pub num_nodes: AtomicUsize,
+    pub max_nodes: usize
 }
 
 unsafe impl Send for SearchContext {}  // because of `UnsafeCell`
@@ -43,12 +49,27 @@ impl SearchContext {
         root: *mut tree::Node,
         options: Box<dyn SearchOptions + Sync>,
         time_strategy: Box<dyn TimeStrategy + Sync>,
+        root_utility: Option<Box<[f32; 368]>>,
         starting_point: Board,
         response_channel: Sender<()>
     ) -> Self
     {
+        let num_nodes = AtomicUsize::new(unsafe { (*root).size() });
+        let max_nodes = options.max_nodes();
+
         Self {
-            id, root, options, time_strategy, starting_point, response_channel
+            id, root, options, time_strategy, root_utility, starting_point, response_channel, num_nodes, max_nodes
+        }
+    }
+
+    /// Reserve space for one more node in the search tree, returning `false`
+    /// if the search tree is already at its maximum size.
+    pub fn try_reserve_node(&self) -> bool {
+        if self.num_nodes.fetch_add(1, Ordering::AcqRel) < self.max_nodes {
+            true
+        } else {
+            self.num_nodes.fetch_sub(1, Ordering::AcqRel);
+            false
         }
     }
 }
@@ -59,23 +80,78 @@ impl SearchContext {
 pub struct SharedContext {
     pub is_running: AtomicBool,
     pub num_running: AtomicUsize,
+    pub num_probing: AtomicUsize,
+    pub num_in_flight: AtomicUsize,
     pub event_queue: ConcurrentQueue<Event>,
     pub predictor: Box<dyn Predictor + Sync>,
     pub batcher: Batcher,
+    pub seed: Option<u64>,
+    pub max_queue_depth: usize,
 }
 
 impl SharedContext {
-    pub fn new(predictor: Box<dyn Predictor + Sync>) -> Self {
+    pub fn new(predictor: Box<dyn Predictor + Sync>, seed: Option<u64>) -> Self {
         let max_num_threads = predictor.max_num_threads();
 
         Self {
             is_running: AtomicBool::new(true),
             num_running: AtomicUsize::new(0),
+            num_probing: AtomicUsize::new(0),
+            num_in_flight: AtomicUsize::new(0),
             event_queue: ConcurrentQueue::unbounded(),
             predictor: predictor,
-            batcher: Batcher::new(max_num_threads)
+            batcher: Batcher::new(max_num_threads),
+            seed: seed,
+            max_queue_depth: *config::MAX_QUEUE_DEPTH
+        }
+    }
+
+    /// Returns the random number generator to use for the worker with the
+    /// given identifier. If this context has a seed then the generator is
+    /// seeded from `seed ^ worker_id`, otherwise it is seeded from entropy.
+    ///
+    /// # Arguments
+    ///
+    /// * `worker_id` -
+    ///
+    pub fn worker_rng(&self, worker_id: usize) -> SmallRng {
+        match self.seed {
+            Some(seed) => SmallRng::seed_from_u64(seed ^ worker_id as u64),
+            None => SmallRng::from_entropy()
+        }
+    }
+
+    /// Returns the minimum size of a partial batch that an idle worker should
+    /// evaluate. This scales with the number of other workers that are
+    /// currently probing, since they are likely to add to the batch soon.
+    pub fn idle_batch_size(&self) -> usize {
+        let num_others = self.num_probing.load(Ordering::Acquire).saturating_sub(1);
+        let batch_size = 1 + (*config::IDLE_BATCH_FRACTION * num_others as f32) as usize;
+
+        batch_size.min(self.batcher.max_batch_size())
+    }
+}
+
+impl SharedContext {
+    /// Reserve space for one more position that has been probed but not yet
+    /// inserted into its search tree, returning `false` if there are already
+    /// `max_queue_depth` such positions. This counts the positions in the
+    /// event queue, the positions pending in the batcher, and the positions
+    /// that are being evaluated. Every successful reservation must be
+    /// released with `release_in_flight`.
+    pub fn try_reserve_in_flight(&self) -> bool {
+        if self.num_in_flight.fetch_add(1, Ordering::AcqRel) < self.max_queue_depth || self.max_queue_depth == 0 {
+            true
+        } else {
+            self.num_in_flight.fetch_sub(1, Ordering::AcqRel);
+            false
         }
     }
+
+    /// Release space reserved by `try_reserve_in_flight`.
+    pub fn release_in_flight(&self) {
+        self.num_in_flight.fetch_sub(1, Ordering::AcqRel);
+    }
 }
 
 impl Drop for SharedContext {
@@ -84,3 +160,63 @@ impl Drop for SharedContext {
         assert_eq!(self.num_running.load(Ordering::Acquire), 0);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::predictors::RandomPredictor;
+    use rand::Rng;
+    use super::*;
+
+    #[test]
+    fn idle_batch_size() {
+        let shared_context = SharedContext::new(Box::new(RandomPredictor::default()), None);
+        assert_eq!(shared_context.idle_batch_size(), 1);
+
+        shared_context.num_probing.store(1, Ordering::Release);
+        assert_eq!(shared_context.idle_batch_size(), 1);
+
+        shared_context.num_probing.store(5, Ordering::Release);
+        assert_eq!(shared_context.idle_batch_size(), 3);
+
+        shared_context.num_probing.store(1000, Ordering::Release);
+        assert_eq!(shared_context.idle_batch_size(), shared_context.batcher.max_batch_size());
+
+        shared_context.is_running.store(false, Ordering::Release);
+    }
+
+    #[test]
+    fn try_reserve_in_flight() {
+        let mut shared_context = SharedContext::new(Box::new(RandomPredictor::default()), None);
+
+        shared_context.max_queue_depth = 2;
+        assert!(shared_context.try_reserve_in_flight());
+        assert!(shared_context.try_reserve_in_flight());
+        assert!(!shared_context.try_reserve_in_flight());
+        assert_eq!(shared_context.num_in_flight.load(Ordering::Acquire), 2);
+
+        shared_context.release_in_flight();
+        assert!(shared_context.try_reserve_in_flight());
+
+        shared_context.max_queue_depth = 0;
+        assert!(shared_context.try_reserve_in_flight());
+        assert!(shared_context.try_reserve_in_flight());
+        assert_eq!(shared_context.num_in_flight.load(Ordering::Acquire), 4);
+
+        shared_context.is_running.store(false, Ordering::Release);
+    }
+
+    #[test]
+    fn seeded_worker_rng() {
+        let shared_context = SharedContext::new(Box::new(RandomPredictor::default()), Some(1234));
+        let sample = |worker_id| {
+            let mut rng = shared_context.worker_rng(worker_id);
+
+            (0..8).map(|_| rng.gen::<u32>()).collect::<Vec<_>>()
+        };
+
+        assert_eq!(sample(0), sample(0));
+        assert_ne!(sample(0), sample(1));
+
+        shared_context.is_running.store(false, Ordering::Release);
+    }
+}
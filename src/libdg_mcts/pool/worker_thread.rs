@@ -19,6 +19,9 @@ use super::event::{Event, EventKind};
 use super::policy_helper::*;
 use super::shared_context::{SharedContext, SearchContext};
 
+use crossbeam_utils::Backoff;
+use dg_utils::config;
+use rand::rngs::SmallRng;
 use std::sync::atomic::Ordering;
 use std::sync::{Arc, Barrier, RwLock};
 use std::thread;
@@ -26,11 +29,14 @@ use std::thread;
 enum TryProbeResult {
     Done { to_remove: usize },
     Quit,
-    Retry { next_index: usize }
+    Full,
+    Retry { next_index: usize },
+    Conflict { next_index: usize }
 }
 
 pub struct Worker {
-    shared_context: Arc<SharedContext>
+    shared_context: Arc<SharedContext>,
+    worker_id: usize
 }
 
 impl Drop for Worker {
@@ -41,10 +47,10 @@ impl Drop for Worker {
 
 impl Worker {
     pub fn new(shared_context: Arc<SharedContext>, has_started: Arc<Barrier>) -> Self {
-        shared_context.num_running.fetch_add(1, Ordering::AcqRel);
+        let worker_id = shared_context.num_running.fetch_add(1, Ordering::AcqRel);
         has_started.wait();
 
-        Self { shared_context }
+        Self { shared_context, worker_id }
     }
 
     pub fn run(&self, searches: Arc<RwLock<Vec<Arc<SearchContext>>>>) {
@@ -52,7 +58,11 @@ impl Worker {
         let event_queue = &self.shared_context.event_queue;
         let is_running = &self.shared_context.is_running;
         let predictor = &self.shared_context.predictor;
+        let mut rng = self.shared_context.worker_rng(self.worker_id);
+        let backoff = Backoff::new();
         let mut index = 0;
+        let mut is_drain = true;
+        let mut num_conflicts = 0;
 
         'outer: loop {
             match event_queue.pop().map(|event| event.into_pending()).ok() {
@@ -61,15 +71,42 @@ impl Worker {
                         break 'outer;
                     }
 
-                    match self.try_probe(&searches, index) {
+                    let num_probing = &self.shared_context.num_probing;
+                    num_probing.fetch_add(1, Ordering::AcqRel);
+                    let result = self.try_probe(&searches, index, is_drain, &mut rng);
+                    num_probing.fetch_sub(1, Ordering::AcqRel);
+
+                    match result {
                         TryProbeResult::Retry { next_index } => {
-                            index = next_index
+                            index = next_index;
+                            is_drain = false;
+                            num_conflicts = 0;
+                            backoff.reset();
+                        },
+                        TryProbeResult::Conflict { next_index } => {
+                            index = next_index;
+                            is_drain = true;
+                            num_conflicts += 1;
+
+                            // if every worker keeps conflicting on the same node, then
+                            // back off to give the worker evaluating it a chance to
+                            // finish instead of stalling the search.
+                            if num_conflicts > *config::MAX_CONFLICTS {
+                                backoff.snooze();
+                            }
                         },
                         TryProbeResult::Quit => {
                             index = 0;
+                            is_drain = true;
                             thread::yield_now();
-                        }
+                        },
+                        TryProbeResult::Full => {
+                            is_drain = true;
+                            backoff.snooze();
+                        },
                         TryProbeResult::Done { to_remove } => {
+                            is_drain = true;
+
                             let mut searches_guard = searches.write().expect("could not acquire write lock");
 
                             if let Some(index) = searches_guard.iter().position(|search_context| search_context.id == to_remove) {
@@ -99,17 +136,25 @@ impl Worker {
                     }
                 },
                 Some((EventKind::Insert(response), event)) => {
-                    let options = &event.search_context.options;
+                    let search_context = &event.search_context;
+                    let options = &search_context.options;
                     let &(_, last_move, _) = event.trace.last().unwrap();
                     let to_move = last_move.opposite();
                     let (mut policy, indices) = create_initial_policy(options, &event.board, to_move);
                     add_valid_candidates(&mut policy, response.policy(), &indices, event.transformation);
+                    scale_pass_prior(options, &event.board, &mut policy);
                     normalize_policy(&mut policy, 1.0);
 
                     unsafe {
-                        global_rwlock::read(|| { tree::insert(&event.trace, to_move, response.winrate(), policy) });
+                        if search_context.try_reserve_node() {
+                            global_rwlock::read(|| { tree::insert(&event.trace, to_move, response.winrate(), policy) });
+                        } else {
+                            global_rwlock::read(|| { tree::backup(&event.trace, to_move, response.winrate()) });
+                        }
                         predictor.cache(&event.board, to_move, event.transformation, response);
                     }
+
+                    self.shared_context.release_in_flight();
                 },
                 Some((EventKind::Pending, _)) => {
                     unreachable!();
@@ -118,18 +163,36 @@ impl Worker {
         }
     }
 
+    /// Evaluate any pending partial batch, and then try to probe the search
+    /// tree at the given index for something new to evaluate.
+    ///
+    /// Unless `is_drain` is set, partial batches that other workers are likely
+    /// to add to soon are not evaluated. This should be set if this worker
+    /// could not find anything to probe last time, since it might otherwise be
+    /// waiting for the very evaluations it is not performing.
+    ///
+    /// # Arguments
+    ///
+    /// * `searches` -
+    /// * `index` -
+    /// * `is_drain` -
+    /// * `rng` -
+    ///
     fn try_probe(
         &self,
         searches: &Arc<RwLock<Vec<Arc<SearchContext>>>>,
-        mut index: usize
+        mut index: usize,
+        is_drain: bool,
+        rng: &mut SmallRng
     ) -> TryProbeResult
     {
         let predictor = &self.shared_context.predictor;
+        let min_batch_size = if is_drain { 1 } else { self.shared_context.idle_batch_size() };
 
         loop {
             // evaluate anything in the queue so far
             let event_responses = self.shared_context.batcher
-                .get_batch(1)
+                .get_batch(min_batch_size)
                 .map(|batch| batch.forward(predictor));
 
             if let Some((events, responses)) = event_responses {
@@ -146,24 +209,35 @@ impl Worker {
                 drop(searches);
 
                 let root = unsafe { &mut *search_context.root };
-                if global_rwlock::read(|| { time_control::is_done(root, &search_context.time_strategy) }) {
+                let min_visits = search_context.options.min_visits_for_selection();
+                if global_rwlock::read(|| { time_control::is_done(root, &search_context.time_strategy, min_visits) }) {
                     return TryProbeResult::Done { to_remove: search_context.id };
                 }
 
+                // apply back-pressure if too many positions are already in
+                // flight, they will be drained by the workers before anything
+                // new is probed
+                if !self.shared_context.try_reserve_in_flight() {
+                    return TryProbeResult::Full;
+                }
+
                 // probe the board if there has been an update since we last encountered
                 // a conflict (or more than 1 ms has passed for deadlock reasons).
                 let mut board = search_context.starting_point.clone();
-                let probe = unsafe { global_rwlock::read(|| { tree::probe(root, &mut board) }) };
+                let min_exploration_visits = search_context.options.min_exploration_visits();
+                let probe = unsafe { global_rwlock::read(|| { tree::probe_with(root, &mut board, min_exploration_visits, search_context.root_utility.as_deref()) }) };
 
                 return match probe {
                     ProbeResult::Found(trace) => {
-                        self.shared_context.event_queue.push(Event::predict(predictor, search_context, board, trace)).ok().expect("could not push to event queue");
+                        self.shared_context.event_queue.push(Event::predict(predictor, search_context, board, trace, rng)).ok().expect("could not push to event queue");
                         TryProbeResult::Retry { next_index: index + 1 }
                     },
                     ProbeResult::Conflict => {
-                        TryProbeResult::Retry { next_index: index + 1 }
+                        self.shared_context.release_in_flight();
+                        TryProbeResult::Conflict { next_index: index + 1 }
                     },
                     ProbeResult::NoResult => {
+                        self.shared_context.release_in_flight();
                         TryProbeResult::Done { to_remove: search_context.id }
                     }
                 }
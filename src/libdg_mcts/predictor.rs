@@ -14,17 +14,37 @@
 
 use dg_go::utils::symmetry::Transform;
 use dg_go::{Board, Color, Point};
+use dg_utils::config;
 use dg_utils::types::f16;
 
+use std::io;
+use std::path::Path;
+
 #[derive(Clone)]
 pub struct Prediction {
     value: f16,
-    policy: Vec<f16>
+    policy: Vec<f16>,
+    value_embedding: Option<Vec<f16>>
 }
 
 impl Prediction {
     pub fn new(value: f16, policy: Vec<f16>) -> Self {
-        Self { value, policy }
+        Self { value, policy, value_embedding: None }
+    }
+
+    /// Returns this prediction with the given value embedding, which should
+    /// be in NHWC order with one or more channels for each of the `361`
+    /// points.
+    ///
+    /// # Arguments
+    ///
+    /// * `value_embedding` - the activations before the final layer of the
+    ///   value head
+    ///
+    pub fn with_value_embedding(self, value_embedding: Vec<f16>) -> Self {
+        debug_assert_eq!(value_embedding.len() % 361, 0);
+
+        Self { value_embedding: Some(value_embedding), ..self }
     }
 
     pub fn with_transform(other: &Self, transform: Transform) -> Self {
@@ -37,12 +57,53 @@ impl Prediction {
         }
         remapped_policy[Point::default().to_packed_index()] = other.policy[Point::default().to_packed_index()];
 
+        let remapped_embedding = other.value_embedding.as_ref().map(|embedding| {
+            let num_channels = embedding.len() / 361;
+            let mut remapped = vec! [f16::from(0.0); embedding.len()];
+
+            for i in Point::all() {
+                let src = i.to_packed_index() * num_channels;
+                let dst = t_table[i].to_packed_index() * num_channels;
+
+                remapped[dst..(dst + num_channels)].copy_from_slice(&embedding[src..(src + num_channels)]);
+            }
+
+            remapped
+        });
+
         Self {
             value: other.value,
-            policy: remapped_policy
+            policy: remapped_policy,
+            value_embedding: remapped_embedding
         }
     }
 
+    /// Returns the average value and policy of the given predictions, which
+    /// should all be in the same orientation. The average does not have a
+    /// value embedding.
+    ///
+    /// # Arguments
+    ///
+    /// * `predictions` - the predictions to average
+    ///
+    pub fn average(predictions: &[Prediction]) -> Self {
+        debug_assert!(!predictions.is_empty());
+
+        let weight = (predictions.len() as f32).recip();
+        let mut value = 0.0f32;
+        let mut policy = vec! [0.0f32; 362];
+
+        for prediction in predictions {
+            value += weight * prediction.value();
+
+            for (p, &q) in policy.iter_mut().zip(prediction.policy.iter()) {
+                *p += weight * f32::from(q);
+            }
+        }
+
+        Self::new(f16::from(value), policy.into_iter().map(f16::from).collect())
+    }
+
     pub fn value(&self) -> f32 {
         f32::from(self.value)
     }
@@ -54,6 +115,15 @@ impl Prediction {
     pub fn policy(&self) -> Vec<f32> {
         self.policy.iter().map(|&x| f32::from(x)).collect()
     }
+
+    /// Returns the activations before the final layer of the value head,
+    /// which can be used as a learned embedding of the position. This is
+    /// only available if the predictor was asked to compute it.
+    pub fn value_embedding(&self) -> Option<Vec<f32>> {
+        self.value_embedding.as_ref().map(|embedding| {
+            embedding.iter().map(|&x| f32::from(x)).collect()
+        })
+    }
 }
 
 pub trait Predictor : Send {
@@ -61,7 +131,42 @@ pub trait Predictor : Send {
     /// `predict`.
     fn max_num_threads(&self) -> usize;
 
-    /// Retrieve the value and policy from the transposition table.
+    /// Returns an identifier of the model that this predictor evaluates
+    /// positions with, such as a checksum of the network weights, or `None`
+    /// if there is no such model.
+    fn identifier(&self) -> Option<String> {
+        None
+    }
+
+    /// Save every entry in the transposition table to the given file, so that
+    /// it can be restored with `load_cache` in a later run. By default this
+    /// predictor has no transposition table that can be saved.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - the file to save the transposition table to
+    ///
+    fn save_cache(&self, _path: &Path) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::Other, "predictor has no prediction cache"))
+    }
+
+    /// Add every entry in the given file, as written by `save_cache`, to the
+    /// transposition table and returns the number of entries that were
+    /// loaded. By default this predictor has no transposition table that can
+    /// be loaded.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - the file to load the transposition table from
+    ///
+    fn load_cache(&self, _path: &Path) -> io::Result<usize> {
+        Err(io::Error::new(io::ErrorKind::Other, "predictor has no prediction cache"))
+    }
+
+    /// Retrieve the value and policy from the transposition table. Since the
+    /// features depend on the recent move history, implementations should
+    /// only return a hit for boards with the same relevant history and not
+    /// just the same stones.
     ///
     /// # Arguments
     ///
@@ -89,12 +194,138 @@ pub trait Predictor : Send {
     /// * `features` - the features to query
     ///
     fn predict(&self, features: &[f16], batch_size: usize) -> Vec<Prediction>;
+
+    /// Returns the result of the given query, which must contain the features
+    /// of exactly one position. Returns `None` if the predictor did not
+    /// produce a response.
+    ///
+    /// # Arguments
+    ///
+    /// * `features` - the features of the position to query
+    ///
+    fn predict_single(&self, features: &[f16]) -> Option<Prediction> {
+        self.predict_single_transformed(features, Transform::Identity)
+    }
+
+    /// Returns the result of the given query, which must contain the features
+    /// of exactly one position computed using the given symmetry. Returns
+    /// `None` if the predictor did not produce a response.
+    ///
+    /// # Arguments
+    ///
+    /// * `features` - the features of the position to query
+    /// * `transform` - the symmetry of the features
+    ///
+    fn predict_single_transformed(&self, features: &[f16], transform: Transform) -> Option<Prediction> {
+        self.predict_transformed(features, &[transform]).into_iter().next()
+    }
+
+    /// Returns the result of the given query, where `transforms` contains the
+    /// symmetry that was used to compute each of the features in the batch.
+    /// By default the symmetries are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `features` - the features to query
+    /// * `transforms` - the symmetry of each of the features
+    ///
+    fn predict_transformed(&self, features: &[f16], transforms: &[Transform]) -> Vec<Prediction> {
+        self.predict(features, transforms.len())
+    }
+
+    /// Returns the result of the given query, including the value embedding
+    /// of each prediction. Implementations that cannot compute the value
+    /// embedding should not override this, by default it is the result of
+    /// `predict` without any embedding.
+    ///
+    /// # Arguments
+    ///
+    /// * `features` - the features to query
+    /// * `batch_size` - the number of features in the query
+    ///
+    fn predict_with_value_embedding(&self, features: &[f16], batch_size: usize) -> Vec<Prediction> {
+        self.predict(features, batch_size)
+    }
+
+    /// Returns only the value of the given query. Implementations that can
+    /// skip computing the policy should override this, by default it is
+    /// the value of `predict`.
+    ///
+    /// # Arguments
+    ///
+    /// * `features` - the features to query
+    /// * `batch_size` - the number of features in the query
+    ///
+    fn predict_value(&self, features: &[f16], batch_size: usize) -> Vec<f32> {
+        self.predict(features, batch_size).iter()
+            .map(|prediction| prediction.value())
+            .collect()
+    }
+}
+
+/// Load the transposition table of the given predictor from the file given by
+/// `config::CACHE_FILE`, if any. A missing or incompatible file is not an
+/// error, the predictor will just start with an empty transposition table.
+///
+/// # Arguments
+///
+/// * `predictor` - the predictor whose transposition table to load
+///
+pub fn load_cache_file(predictor: &dyn Predictor) {
+    if let Some(ref path) = *config::CACHE_FILE {
+        if let Err(reason) = predictor.load_cache(Path::new(path)) {
+            eprintln!("Warning: could not load prediction cache from `{}`: {}", path, reason);
+        }
+    }
+}
+
+/// Save the transposition table of the given predictor to the file given by
+/// `config::CACHE_FILE`, if any.
+///
+/// # Arguments
+///
+/// * `predictor` - the predictor whose transposition table to save
+///
+pub fn save_cache_file(predictor: &dyn Predictor) {
+    if let Some(ref path) = *config::CACHE_FILE {
+        if let Err(reason) = predictor.save_cache(Path::new(path)) {
+            eprintln!("Warning: could not save prediction cache to `{}`: {}", path, reason);
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use dg_go::utils::features;
+    use predictors::RandomPredictor;
     use super::*;
 
+    #[test]
+    fn predict_value() {
+        let features = vec! [f16::from(0.0); 2 * features::Default::size()];
+        let values = RandomPredictor.predict_value(&features, 2);
+
+        assert_eq!(values.len(), 2);
+        assert!(values.iter().all(|v| v.is_finite()));
+    }
+
+    #[test]
+    fn save_and_load_cache_without_cache() {
+        let path = Path::new("/dev/null");
+
+        assert!(RandomPredictor.save_cache(path).is_err());
+        assert!(RandomPredictor.load_cache(path).is_err());
+    }
+
+    #[test]
+    fn predict_single() {
+        let features = vec! [f16::from(0.0); features::Default::size()];
+        let prediction = RandomPredictor.predict_single(&features).expect("could not predict position");
+
+        assert!(prediction.value().is_finite());
+        assert!(prediction.policy().iter().all(|p| p.is_finite()));
+    }
+
     #[test]
     fn check_with_transform() {
         let original = Prediction::new(
@@ -106,4 +337,43 @@ mod tests {
         assert_eq!(original.policy()[0], Prediction::with_transform(&original, Transform::Rot180).policy()[360]);
         assert_eq!(original.policy()[361], Prediction::with_transform(&original, Transform::Rot180).policy()[361]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn value_embedding() {
+        let prediction = Prediction::new(f16::from(0.0), vec! [f16::from(0.0); 362]);
+        assert!(prediction.value_embedding().is_none());
+
+        // channel `c` of point `i` is `2 * i + c`, in NHWC order
+        let embedding = (0..722).map(|i| f16::from(i as f32)).collect::<Vec<_>>();
+        let prediction = prediction.with_value_embedding(embedding);
+        let rotated = Prediction::with_transform(&prediction, Transform::Rot180);
+        let rotated = rotated.value_embedding().expect("no value embedding");
+
+        assert_eq!(prediction.value_embedding().map(|e| e.len()), Some(722));
+        assert_eq!(rotated[2 * 360], 0.0);
+        assert_eq!(rotated[2 * 360 + 1], 1.0);
+        assert_eq!(rotated[0], 720.0);
+        assert_eq!(rotated[1], 721.0);
+        assert_eq!(rotated[2 * 180], 360.0);
+        assert_eq!(rotated[2 * 180 + 1], 361.0);
+    }
+
+    #[test]
+    fn predict_with_value_embedding_default() {
+        let features = vec! [f16::from(0.0); features::Default::size()];
+        let predictions = RandomPredictor.predict_with_value_embedding(&features, 1);
+
+        assert_eq!(predictions.len(), 1);
+        assert!(predictions[0].value_embedding().is_none());
+    }
+
+    #[test]
+    fn average() {
+        let a = Prediction::new(f16::from(0.5), vec! [f16::from(1.0); 362]);
+        let b = Prediction::new(f16::from(-0.5), vec! [f16::from(0.5); 362]);
+        let average = Prediction::average(&[a, b]);
+
+        assert_eq!(average.value(), 0.0);
+        assert_eq!(average.policy(), vec! [0.75; 362]);
+    }
+}
@@ -0,0 +1,136 @@
+// Copyright 2021 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{Predictor, Prediction};
+use dg_go::{utils::symmetry, Board, Color};
+use dg_utils::types::f16;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+type PredictFn = dyn Fn(&[f16], usize) -> Vec<Prediction> + Send + Sync;
+type PredictValueFn = dyn Fn(&[f16], usize) -> Vec<f32> + Send + Sync;
+type CacheTable = Vec<(u64, Color, symmetry::Transform, Prediction)>;
+
+/// A predictor for tests whose `predict` is the given closure. By default it
+/// has no transposition table, but it counts the number of calls to `cache`.
+pub struct MockPredictor {
+    predict: Box<PredictFn>,
+    predict_value: Option<Box<PredictValueFn>>,
+    max_num_threads: usize,
+    cache_table: Option<Mutex<CacheTable>>,
+    num_cached: AtomicUsize
+}
+
+impl MockPredictor {
+    /// Returns a predictor that returns the result of `predict` for every
+    /// query.
+    ///
+    /// # Arguments
+    ///
+    /// * `predict` - the features and batch size of a query to its result
+    ///
+    pub fn new<F>(predict: F) -> Self
+        where F: Fn(&[f16], usize) -> Vec<Prediction> + Send + Sync + 'static
+    {
+        Self {
+            predict: Box::new(predict),
+            predict_value: None,
+            max_num_threads: 1,
+            cache_table: None,
+            num_cached: AtomicUsize::new(0)
+        }
+    }
+
+    /// Returns a predictor that returns the same value and a uniform policy for
+    /// every position.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - the value of every position
+    ///
+    pub fn constant(value: f32) -> Self {
+        Self::new(move |_features, batch_size| {
+            vec! [Prediction::new(f16::from(value), vec! [f16::from(1.0); 362]); batch_size]
+        })
+    }
+
+    /// Returns this predictor, where `predict_value` returns the result of the
+    /// given closure instead of the value of `predict`.
+    ///
+    /// # Arguments
+    ///
+    /// * `predict_value` - the features and batch size of a query to its values
+    ///
+    pub fn with_predict_value<F>(self, predict_value: F) -> Self
+        where F: Fn(&[f16], usize) -> Vec<f32> + Send + Sync + 'static
+    {
+        Self { predict_value: Some(Box::new(predict_value)), ..self }
+    }
+
+    /// Returns this predictor, with the given maximum number of parallel calls
+    /// into `predict`.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_num_threads` -
+    ///
+    pub fn with_max_num_threads(self, max_num_threads: usize) -> Self {
+        Self { max_num_threads, ..self }
+    }
+
+    /// Returns this predictor, with a transposition table that is keyed by the
+    /// zobrist hash of the board, the player to move, and the symmetry.
+    pub fn with_cache(self) -> Self {
+        Self { cache_table: Some(Mutex::new(vec! [])), ..self }
+    }
+
+    /// Returns the number of times `cache` has been called.
+    pub fn num_cached(&self) -> usize {
+        self.num_cached.load(Ordering::Acquire)
+    }
+}
+
+impl Predictor for MockPredictor {
+    fn max_num_threads(&self) -> usize {
+        self.max_num_threads
+    }
+
+    fn fetch(&self, board: &Board, to_move: Color, symmetry: symmetry::Transform) -> Option<Prediction> {
+        let cache_table = self.cache_table.as_ref()?.lock().expect("could not acquire lock");
+        let key = (board.zobrist_hash(), to_move, symmetry);
+
+        cache_table.iter()
+            .find(|&&(hash, color, t, _)| (hash, color, t) == key)
+            .map(|(_, _, _, response)| response.clone())
+    }
+
+    fn cache(&self, board: &Board, to_move: Color, symmetry: symmetry::Transform, response: Prediction) {
+        self.num_cached.fetch_add(1, Ordering::AcqRel);
+
+        if let Some(ref cache_table) = self.cache_table {
+            cache_table.lock().expect("could not acquire lock").push((board.zobrist_hash(), to_move, symmetry, response));
+        }
+    }
+
+    fn predict(&self, features: &[f16], batch_size: usize) -> Vec<Prediction> {
+        (self.predict)(features, batch_size)
+    }
+
+    fn predict_value(&self, features: &[f16], batch_size: usize) -> Vec<f32> {
+        match self.predict_value {
+            Some(ref predict_value) => predict_value(features, batch_size),
+            None => self.predict(features, batch_size).iter().map(|prediction| prediction.value()).collect()
+        }
+    }
+}
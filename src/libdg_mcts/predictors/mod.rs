@@ -13,14 +13,20 @@
 // limitations under the License.
 
 #[cfg(test)] mod fake;
+#[cfg(test)] mod mock;
 #[cfg(test)] mod nan;
 mod nn;
 mod random;
+mod sanitizing;
+mod teeing;
 
 #[cfg(test)] pub use self::fake::*;
+#[cfg(test)] pub use self::mock::*;
 #[cfg(test)] pub use self::nan::*;
 pub use self::nn::*;
 pub use self::random::*;
+pub use self::sanitizing::*;
+pub use self::teeing::*;
 
 /// The default predictor that should be used.
 pub type DefaultPredictor = NnPredictor;
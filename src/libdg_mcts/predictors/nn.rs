@@ -15,34 +15,239 @@
 use predictor::{Predictor, Prediction};
 use lru_cache::LruCache;
 use dg_go::utils::symmetry::Transform;
-use dg_go::{Board, Color};
+use dg_go::{Board, Color, Point};
 use dg_cuda::Device;
 use dg_nn::{self as nn, Network};
+use dg_go::utils::features;
+use dg_utils::config;
 use dg_utils::types::f16;
 
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::sync::Mutex;
 
-/// The maximum number of entries to be stored in the transposition table
-/// before we need to remove the least recently used one.
-const MAX_CACHE_SIZE: usize = 200_000;
-
+/// The key of the prediction cache.
+///
+/// The features of a board depend on more than just the stones on it, since
+/// they also encode the last two moves played and the komi. So two boards that
+/// are `position_eq` but were reached using a different move order, or that
+/// differ by a pass, can have different network outputs, and must therefore
+/// _not_ share cache entries. Super-ko that depends on moves older than this
+/// is not part of the key.
 #[derive(Clone, Hash, PartialEq, Eq)]
 struct BoardTuple {
     board_hash: u64,
+    history: [Point; 2],
+    komi: u32,
     to_move: Color
 }
 
 impl BoardTuple {
     fn new(board: &Board, to_move: Color) -> Self {
+        let mut history = [Point::default(); 2];
+
+        for (i, point) in board.history_with_passes().take(2).enumerate() {
+            history[i] = point;
+        }
+
         Self {
             board_hash: board.zobrist_hash(),
+            history: history,
+            komi: board.komi().to_bits(),
             to_move: to_move
         }
     }
 }
 
+/// The magic bytes at the start of a saved prediction cache.
+const CACHE_MAGIC: &[u8; 4] = b"DGPC";
+
+/// The version of the saved prediction cache format.
+const CACHE_VERSION: u32 = 2;
+
+fn read_u16<R: Read>(reader: &mut R) -> io::Result<u16> {
+    let mut buf = [0; 2];
+    reader.read_exact(&mut buf)?;
+    Ok(u16::from_le_bytes(buf))
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+/// Write every entry of the given cache to `writer`. The layout (all integers
+/// are little endian, and all floating point numbers are the bits of an `f16`)
+/// is:
+///
+/// - `4 x u8` the magic bytes `DGPC`
+/// - `u32` the version of the format
+/// - `u32` the number of features, which identifies the feature set
+/// - `u32` the length of the network identifier
+/// - `N x u8` the network identifier, which identifies the weights
+/// - `u32` the number of entries
+/// - for each entry, from the least recently used to the most recently used:
+///   - `u64` the zobrist hash of the board
+///   - `2 x u16` the two most recent moves (packed indices), including passes
+///   - `u32` the bits of the komi
+///   - `u8` the color to move, `0` for black and `1` for white
+///   - `u16` the value
+///   - `362 x u16` the policy
+///
+/// # Arguments
+///
+/// * `cache` - the cache to write
+/// * `identifier` - the identifier of the network that produced the entries
+/// * `writer` - the writer to write the cache to
+///
+fn write_cache<W: Write>(cache: &LruCache<BoardTuple, Prediction>, identifier: &str, writer: &mut W) -> io::Result<()> {
+    check_identifier(identifier)?;
+
+    let entries = cache.iter().collect::<Vec<_>>();
+
+    writer.write_all(CACHE_MAGIC)?;
+    writer.write_all(&CACHE_VERSION.to_le_bytes())?;
+    writer.write_all(&(features::Default::num_features() as u32).to_le_bytes())?;
+    writer.write_all(&(identifier.len() as u32).to_le_bytes())?;
+    writer.write_all(identifier.as_bytes())?;
+    writer.write_all(&(entries.len() as u32).to_le_bytes())?;
+
+    for (key, response) in entries {
+        writer.write_all(&key.board_hash.to_le_bytes())?;
+        for point in &key.history {
+            writer.write_all(&(point.to_packed_index() as u16).to_le_bytes())?;
+        }
+        writer.write_all(&key.komi.to_le_bytes())?;
+        writer.write_all(&[if key.to_move == Color::Black { 0 } else { 1 }])?;
+        writer.write_all(&f16::from(response.value()).to_bits().to_le_bytes())?;
+
+        for x in response.policy().into_iter().take(362) {
+            writer.write_all(&f16::from(x).to_bits().to_le_bytes())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns an error if the given network identifier is empty, since it can
+/// then not be used to tell networks apart (see `Network::identifier`).
+///
+/// # Arguments
+///
+/// * `identifier` - the identifier of the network
+///
+fn check_identifier(identifier: &str) -> io::Result<()> {
+    if identifier.is_empty() {
+        Err(io::Error::new(io::ErrorKind::InvalidInput, "network has no identifier"))
+    } else {
+        Ok(())
+    }
+}
+
+/// Read entries written by `write_cache` from `reader` into the given cache,
+/// and returns the number of entries that were read. If the file was written
+/// with a different format version, with a different feature set, or by a
+/// different network, then nothing is read and an error is returned.
+///
+/// # Arguments
+///
+/// * `cache` - the cache to add the entries to
+/// * `identifier` - the identifier of the network that will use the entries
+/// * `reader` - the reader to read the entries from
+///
+fn read_cache<R: Read>(cache: &mut LruCache<BoardTuple, Prediction>, identifier: &str, reader: &mut R) -> io::Result<usize> {
+    check_identifier(identifier)?;
+
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+
+    if &magic != CACHE_MAGIC {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "not a prediction cache"));
+    }
+
+    let version = read_u32(reader)?;
+    let num_features = read_u32(reader)?;
+
+    if version != CACHE_VERSION || num_features as usize != features::Default::num_features() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "incompatible prediction cache version"));
+    }
+
+    let mut other_identifier = vec! [0; read_u32(reader)? as usize];
+    reader.read_exact(&mut other_identifier)?;
+
+    if other_identifier != identifier.as_bytes() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "prediction cache was saved by a different network"));
+    }
+
+    let num_entries = read_u32(reader)? as usize;
+    let mut entries = Vec::with_capacity(num_entries.min(*config::CACHE_SIZE));
+
+    for _ in 0..num_entries {
+        let board_hash = read_u64(reader)?;
+        let history = [
+            Point::from_packed_parts(read_u16(reader)? as usize),
+            Point::from_packed_parts(read_u16(reader)? as usize)
+        ];
+        let komi = read_u32(reader)?;
+        let mut to_move = [0; 1];
+        reader.read_exact(&mut to_move)?;
+        let to_move = if to_move[0] == 0 { Color::Black } else { Color::White };
+        let value = f16::from_bits(read_u16(reader)?);
+        let mut policy = Vec::with_capacity(362);
+
+        for _ in 0..362 {
+            policy.push(f16::from_bits(read_u16(reader)?));
+        }
+
+        entries.push((BoardTuple { board_hash, history, komi, to_move }, Prediction::new(value, policy)));
+    }
+
+    for (key, response) in entries {
+        cache.insert(&key, response);
+    }
+
+    Ok(num_entries)
+}
+
+/// The number of threads that evaluates batches on each device.
+const THREADS_PER_DEVICE: usize = 2;
+
+/// Returns the number of equally sized sub-batches that a batch of the given
+/// size should be split into. A full batch is never split, since it already
+/// keeps the GPU busy on its own, and any smaller batch is split into the
+/// largest divisor of `batch_size` that is not larger than `num_stream_sets`.
+///
+/// # Arguments
+///
+/// * `batch_size` - the size of the batch to split
+/// * `max_batch_size` - the size of a full batch
+/// * `num_stream_sets` - the number of stream sets in each workspace
+///
+fn num_sub_batches(batch_size: usize, max_batch_size: usize, num_stream_sets: usize) -> usize {
+    if batch_size >= max_batch_size {
+        return 1;
+    }
+
+    (1..=num_stream_sets.min(batch_size)).rev()
+        .find(|&n| batch_size % n == 0)
+        .unwrap_or(1)
+}
+
+/// A predictor that evaluates positions using the neural network, with a
+/// transposition table of at most `config::CACHE_SIZE` entries. Clones of this
+/// predictor share the same transposition table, as do all searches that use
+/// the same `Pool`, so during self-play every game that is played in parallel
+/// shares it.
 #[derive(Clone)]
 pub struct NnPredictor {
     cache_table: Arc<Mutex<LruCache<BoardTuple, Prediction>>>,
@@ -53,17 +258,73 @@ pub struct NnPredictor {
 impl Default for NnPredictor {
     fn default() -> Self {
         let network = Network::new().expect("could not load network weights");
-        let cache_table = Arc::new(Mutex::new(LruCache::with_capacity(MAX_CACHE_SIZE + 1)));
+        let cache_table = Arc::new(Mutex::new(LruCache::with_capacity(*config::CACHE_SIZE + 1)));
         let count = Arc::new(AtomicUsize::new(0));
+        let predictor = Self { cache_table, network, count };
+
+        predictor.reserve();
+        predictor
+    }
+}
+
+impl NnPredictor {
+    /// Pre-allocate the device memory of the workspaces that the threads of
+    /// this predictor use for full batches on every device, so that the
+    /// allocator does not need to grow in the middle of a game.
+    fn reserve(&self) {
+        let original_device = Device::default();
+        let batch_size = *config::BATCH_SIZE;
+        let num_workspaces = if *config::MAX_WORKSPACES > 0 {
+            THREADS_PER_DEVICE.min(*config::MAX_WORKSPACES)
+        } else {
+            THREADS_PER_DEVICE
+        };
+
+        for device in Device::all().expect("could not find any compatible devices") {
+            device.set_current().expect("could not set the device for the current thread");
+
+            if let Err(reason) = self.network.reserve(batch_size, num_workspaces) {
+                eprintln!("Warning: could not reserve device memory: {:?}", reason);
+            }
+        }
 
-        Self { cache_table, network, count }
+        original_device.set_current().expect("could not set the device for the current thread");
+    }
+
+    /// Set the current device of this thread, in a round-robin fashion over
+    /// all available devices.
+    fn set_current_device(&self) {
+        let devices = Device::all().expect("could not find any compatible devices");
+        let index = self.count.fetch_add(1, Ordering::Relaxed) % devices.len();
+        devices[index].set_current().expect("could not set the device for the current thread");
     }
 }
 
 impl Predictor for NnPredictor {
     fn max_num_threads(&self) -> usize {
         let num_devices = Device::all().expect("could not find any compatible devices").len();
-        2 * num_devices
+        THREADS_PER_DEVICE * num_devices
+    }
+
+    fn identifier(&self) -> Option<String> {
+        Some(self.network.identifier().to_string())
+    }
+
+    fn save_cache(&self, path: &Path) -> io::Result<()> {
+        check_identifier(self.network.identifier())?;  // before truncating the file
+
+        let cache_table = self.cache_table.lock().expect("could not acquire cache table lock");
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        write_cache(&cache_table, self.network.identifier(), &mut writer)?;
+        writer.flush()
+    }
+
+    fn load_cache(&self, path: &Path) -> io::Result<usize> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut cache_table = self.cache_table.lock().expect("could not acquire cache table lock");
+
+        read_cache(&mut cache_table, self.network.identifier(), &mut reader)
     }
 
     fn fetch(&self, board: &Board, to_move: Color, symmetry: Transform) -> Option<Prediction> {
@@ -84,26 +345,187 @@ impl Predictor for NnPredictor {
     fn predict(&self, features_list: &[f16], batch_size: usize) -> Vec<Prediction> {
         assert!(batch_size > 0);
 
-        let devices = Device::all().expect("could not find any compatible devices");
-        let index = self.count.fetch_add(1, Ordering::Relaxed) % devices.len();
-        devices[index].set_current().expect("could not set the device for the current thread");
+        self.set_current_device();
+
+        // split a batch of a few concurrent small requests into independent
+        // sub-batches, so that they can overlap on the stream sets of a single
+        // workspace
+        let network = &self.network;
+        let num_batches = num_sub_batches(batch_size, *config::BATCH_SIZE, *config::NUM_STREAM_SETS);
+        let sub_batch_size = batch_size / num_batches;
+        let result = network.get_workspace(sub_batch_size).and_then(|mut workspace| {
+            let batches = features_list.chunks(sub_batch_size * features::Default::size()).collect::<Vec<_>>();
+            let mut responses = Vec::with_capacity(batch_size);
+
+            for outputs in nn::forward_batches(&mut workspace, &batches, nn::OutputSet::All)? {
+                let (value_list, policy_list) = outputs.unwrap();
+                let policy_iter = policy_list.chunks(362).map(|p| p.to_vec());
+
+                responses.extend(
+                    value_list
+                        .into_iter()
+                        .zip(policy_iter)
+                        .map(|(value, policy)| Prediction::new(value, policy))
+                );
+            }
+
+            Ok(responses)
+        });
+
+        result.expect("could not run neural network")
+    }
+
+    fn predict_with_value_embedding(&self, features_list: &[f16], batch_size: usize) -> Vec<Prediction> {
+        assert!(batch_size > 0);
+
+        self.set_current_device();
 
-        //
         let network = &self.network;
         let result = network.get_workspace(batch_size).and_then(|mut workspace| {
-            let outputs = nn::forward(&mut workspace, features_list)?;
+            let outputs = nn::forward_with(&mut workspace, features_list, nn::OutputSet::AllWithValueEmbedding)?;
+            let embedding_list = outputs.value_embedding().map(|v| v.to_vec()).unwrap_or_default();
+            let embedding_size = embedding_list.len() / batch_size;
             let (value_list, policy_list) = outputs.unwrap();
             let policy_iter = policy_list.chunks(362).map(|p| p.to_vec());
+            let responses = value_list
+                .into_iter()
+                .zip(policy_iter)
+                .map(|(value, policy)| Prediction::new(value, policy));
+
+            if embedding_size == 0 {
+                // the network does not have a value embedding
+                return Ok(responses.collect());
+            }
+
+            let embedding_iter = embedding_list.chunks(embedding_size).map(|e| e.to_vec());
 
             Ok(
-                value_list
-                    .into_iter()
-                    .zip(policy_iter)
-                    .map(|(value, policy)| Prediction::new(value, policy))
+                responses
+                    .zip(embedding_iter)
+                    .map(|(prediction, embedding)| prediction.with_value_embedding(embedding))
                     .collect()
             )
         });
 
         result.expect("could not run neural network")
     }
+
+    fn predict_value(&self, features_list: &[f16], batch_size: usize) -> Vec<f32> {
+        assert!(batch_size > 0);
+
+        self.set_current_device();
+
+        let network = &self.network;
+        let result = network.get_workspace(batch_size).and_then(|mut workspace| {
+            let outputs = nn::forward_with(&mut workspace, features_list, nn::OutputSet::Value)?;
+            let (value_list, _) = outputs.unwrap();
+
+            Ok(value_list.into_iter().map(f32::from).collect())
+        });
+
+        result.expect("could not run neural network")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_cache() -> LruCache<BoardTuple, Prediction> {
+        let mut cache = LruCache::with_capacity(10);
+        let mut board = Board::new(7.5);
+        board.place(Color::Black, Point::new(3, 3));
+
+        cache.insert(
+            &BoardTuple::new(&board, Color::White),
+            Prediction::new(f16::from(0.25), (0..362).map(|i| f16::from(i as f32 / 362.0)).collect())
+        );
+        cache.insert(
+            &BoardTuple::new(&Board::new(0.5), Color::Black),
+            Prediction::new(f16::from(-0.5), vec! [f16::from(0.0); 362])
+        );
+        cache
+    }
+
+    #[test]
+    fn sub_batches() {
+        assert_eq!(num_sub_batches(1, 16, 2), 1);
+        assert_eq!(num_sub_batches(2, 16, 2), 2);
+        assert_eq!(num_sub_batches(7, 16, 2), 1);
+        assert_eq!(num_sub_batches(12, 16, 8), 6);
+        assert_eq!(num_sub_batches(8, 16, 0), 1);
+        assert_eq!(num_sub_batches(8, 16, 1), 1);
+        assert_eq!(num_sub_batches(16, 16, 2), 1);
+    }
+
+    #[test]
+    fn key_includes_passes() {
+        let mut board = Board::new(7.5);
+        board.place(Color::Black, Point::new(3, 3));
+        board.place(Color::White, Point::new(15, 15));
+
+        let mut other = board.clone();
+        other.pass(Color::Black);
+        other.pass(Color::White);
+
+        assert!(BoardTuple::new(&board, Color::Black) != BoardTuple::new(&other, Color::Black));
+    }
+
+    #[test]
+    fn save_and_load_cache() {
+        let original = get_cache();
+        let mut bytes = vec! [];
+        write_cache(&original, "abcdef", &mut bytes).expect("could not write cache");
+
+        let mut other = LruCache::with_capacity(10);
+        assert_eq!(read_cache(&mut other, "abcdef", &mut bytes.as_slice()).expect("could not read cache"), 2);
+
+        let original_entries = original.iter().collect::<Vec<_>>();
+        let other_entries = other.iter().collect::<Vec<_>>();
+
+        assert_eq!(original_entries.len(), other_entries.len());
+
+        for ((a_key, a_value), (b_key, b_value)) in original_entries.into_iter().zip(other_entries) {
+            assert!(a_key == b_key);
+            assert_eq!(a_value.value(), b_value.value());
+            assert_eq!(a_value.policy(), b_value.policy());
+        }
+    }
+
+    #[test]
+    fn load_incompatible_cache() {
+        let mut bytes = vec! [];
+        write_cache(&get_cache(), "abcdef", &mut bytes).expect("could not write cache");
+        bytes[8] = bytes[8].wrapping_add(1);  // the number of features
+
+        let mut other = LruCache::with_capacity(10);
+
+        assert!(read_cache(&mut other, "abcdef", &mut bytes.as_slice()).is_err());
+        assert_eq!(other.iter().count(), 0);
+    }
+
+    #[test]
+    fn load_cache_from_other_network() {
+        let mut bytes = vec! [];
+        write_cache(&get_cache(), "abcdef", &mut bytes).expect("could not write cache");
+
+        let mut other = LruCache::with_capacity(10);
+
+        assert!(read_cache(&mut other, "fedcba", &mut bytes.as_slice()).is_err());
+        assert!(read_cache(&mut other, "abc", &mut bytes.as_slice()).is_err());
+        assert_eq!(other.iter().count(), 0);
+    }
+
+    #[test]
+    fn save_and_load_cache_without_identifier() {
+        let mut bytes = vec! [];
+        assert!(write_cache(&get_cache(), "", &mut bytes).is_err());
+        assert!(bytes.is_empty());
+
+        write_cache(&get_cache(), "abcdef", &mut bytes).expect("could not write cache");
+        let mut other = LruCache::with_capacity(10);
+
+        assert!(read_cache(&mut other, "", &mut bytes.as_slice()).is_err());
+        assert_eq!(other.iter().count(), 0);
+    }
 }
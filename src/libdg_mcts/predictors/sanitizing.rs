@@ -0,0 +1,172 @@
+// Copyright 2021 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use crate::{Predictor, Prediction};
+use dg_go::{utils::symmetry, Board, Color};
+use dg_utils::types::f16;
+
+use std::io;
+use std::path::Path;
+
+/// An implementation of `Predictor` that forwards every query to another
+/// predictor, and replaces any non-finite values in the responses with safe
+/// defaults. A non-finite value becomes an even position (a winrate of
+/// `0.5`), and a non-finite policy or value embedding becomes `0.0`. This
+/// guards the search tree against an occasional bad forward pass.
+pub struct SanitizingPredictor<P: Predictor> {
+    inner: P
+}
+
+impl<P: Predictor> SanitizingPredictor<P> {
+    pub fn new(inner: P) -> Self {
+        Self { inner }
+    }
+}
+
+/// Returns the given prediction with all non-finite values replaced, or the
+/// original prediction if it did not contain any non-finite values.
+///
+/// # Arguments
+///
+/// * `prediction` - the prediction to sanitize
+///
+fn sanitize(prediction: Prediction) -> Prediction {
+    let value = prediction.value();
+    let policy = prediction.policy();
+    let value_embedding = prediction.value_embedding();
+
+    let is_finite = value.is_finite()
+        && policy.iter().all(|p| p.is_finite())
+        && value_embedding.iter().flatten().all(|v| v.is_finite());
+
+    if is_finite {
+        prediction
+    } else {
+        eprintln!("Warning: replacing non-finite values in a prediction");
+
+        let sanitized = Prediction::new(
+            f16::from(if value.is_finite() { value } else { 0.0 }),
+            policy.into_iter().map(|p| f16::from(if p.is_finite() { p } else { 0.0 })).collect()
+        );
+
+        if let Some(value_embedding) = value_embedding {
+            sanitized.with_value_embedding(
+                value_embedding.into_iter().map(|v| f16::from(if v.is_finite() { v } else { 0.0 })).collect()
+            )
+        } else {
+            sanitized
+        }
+    }
+}
+
+impl<P: Predictor> Predictor for SanitizingPredictor<P> {
+    fn max_num_threads(&self) -> usize {
+        self.inner.max_num_threads()
+    }
+
+    fn identifier(&self) -> Option<String> {
+        self.inner.identifier()
+    }
+
+    fn save_cache(&self, path: &Path) -> io::Result<()> {
+        self.inner.save_cache(path)
+    }
+
+    fn load_cache(&self, path: &Path) -> io::Result<usize> {
+        self.inner.load_cache(path)
+    }
+
+    fn fetch(&self, board: &Board, to_move: Color, symmetry: symmetry::Transform) -> Option<Prediction> {
+        self.inner.fetch(board, to_move, symmetry)
+    }
+
+    fn cache(&self, board: &Board, to_move: Color, symmetry: symmetry::Transform, response: Prediction) {
+        self.inner.cache(board, to_move, symmetry, response)
+    }
+
+    fn predict(&self, features: &[f16], batch_size: usize) -> Vec<Prediction> {
+        self.inner.predict(features, batch_size).into_iter().map(sanitize).collect()
+    }
+
+    fn predict_transformed(&self, features: &[f16], transforms: &[symmetry::Transform]) -> Vec<Prediction> {
+        self.inner.predict_transformed(features, transforms).into_iter().map(sanitize).collect()
+    }
+
+    fn predict_with_value_embedding(&self, features: &[f16], batch_size: usize) -> Vec<Prediction> {
+        self.inner.predict_with_value_embedding(features, batch_size).into_iter().map(sanitize).collect()
+    }
+
+    fn predict_value(&self, features: &[f16], batch_size: usize) -> Vec<f32> {
+        self.inner.predict_value(features, batch_size).into_iter()
+            .map(|value| if value.is_finite() { value } else { 0.0 })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dg_go::utils::features;
+    use predictors::{NanPredictor, RandomPredictor};
+    use super::*;
+
+    #[test]
+    fn sanitize_non_finite() {
+        let predictor = SanitizingPredictor::new(NanPredictor);
+        let features = vec! [f16::from(0.0); 2 * features::Default::size()];
+        let responses = predictor.predict(&features, 2);
+
+        assert_eq!(responses.len(), 2);
+
+        for response in &responses {
+            assert_eq!(response.value(), 0.0);
+            assert!(response.policy().iter().all(|&p| p == 0.0));
+        }
+    }
+
+    #[test]
+    fn sanitize_nan_value() {
+        let prediction = Prediction::new(f16::from(f32::NAN), vec! [f16::from(0.5); 362]);
+        let sanitized = sanitize(prediction);
+
+        assert_eq!(sanitized.winrate(), 0.5);
+        assert!(sanitized.policy().iter().all(|&p| p == 0.5));
+    }
+
+    #[test]
+    fn sanitize_nan_value_embedding() {
+        let mut embedding = vec! [f16::from(1.0); 361];
+        embedding[7] = f16::from(f32::NAN);
+        embedding[8] = f16::from(f32::INFINITY);
+
+        let prediction = Prediction::new(f16::from(0.0), vec! [f16::from(0.5); 362])
+            .with_value_embedding(embedding);
+        let sanitized = sanitize(prediction).value_embedding().expect("no value embedding");
+
+        assert_eq!(sanitized.len(), 361);
+        assert_eq!(sanitized[7], 0.0);
+        assert_eq!(sanitized[8], 0.0);
+        assert!(sanitized.iter().enumerate().all(|(i, &v)| i == 7 || i == 8 || v == 1.0));
+    }
+
+    #[test]
+    fn keep_finite() {
+        let predictor = SanitizingPredictor::new(RandomPredictor);
+        let features = vec! [f16::from(0.0); features::Default::size()];
+        let response = predictor.predict_single(&features).expect("could not predict position");
+
+        assert!(response.value().is_finite());
+        assert!(response.policy().iter().all(|p| p.is_finite()));
+    }
+}
@@ -0,0 +1,184 @@
+// Copyright 2021 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use crate::{Predictor, Prediction};
+use dg_go::utils::features;
+use dg_go::{utils::symmetry, Board, Color};
+use dg_utils::types::f16;
+
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// An implementation of `Predictor` that forwards every query to another
+/// predictor, and writes the features and responses of every query to a
+/// binary sink. This is useful for distilling the network into another
+/// model.
+///
+/// Each batch is written as follows (all integers are little endian, and
+/// floating point numbers are stored as the bits of an `f16`):
+///
+/// - `u32` the number of elements in the batch
+/// - for each element in the batch:
+///   - `u8` the symmetry used to compute the features (as an index into
+///     `symmetry::ALL`)
+///   - `features::Default::size() x u16` the features
+///   - `u16` the value
+///   - `362 x u16` the policy, in the same symmetry as the features
+///
+pub struct TeeingPredictor<P: Predictor, W: Write + Send> {
+    inner: P,
+    sink: Mutex<W>
+}
+
+impl<P: Predictor, W: Write + Send> TeeingPredictor<P, W> {
+    pub fn new(inner: P, sink: W) -> Self {
+        Self { inner, sink: Mutex::new(sink) }
+    }
+
+    /// Returns the sink of this predictor.
+    pub fn into_sink(self) -> W {
+        self.sink.into_inner().expect("could not acquire sink lock")
+    }
+
+    /// Write the given batch to the sink.
+    ///
+    /// # Arguments
+    ///
+    /// * `features` -
+    /// * `transforms` -
+    /// * `responses` -
+    ///
+    fn write_batch(&self, features: &[f16], transforms: &[symmetry::Transform], responses: &[Prediction]) {
+        let feature_size = features::Default::size();
+        let mut out = Vec::with_capacity(4 + transforms.len() * (1 + 2 * feature_size + 2 + 2 * 362));
+
+        out.extend_from_slice(&(responses.len() as u32).to_le_bytes());
+
+        for (i, response) in responses.iter().enumerate() {
+            out.push(transforms.get(i).map(|&t| t as u8).unwrap_or(0));
+
+            for x in &features[(i * feature_size)..((i + 1) * feature_size)] {
+                out.extend_from_slice(&x.to_bits().to_le_bytes());
+            }
+
+            out.extend_from_slice(&f16::from(response.value()).to_bits().to_le_bytes());
+
+            for &x in response.policy().iter().take(362) {
+                out.extend_from_slice(&f16::from(x).to_bits().to_le_bytes());
+            }
+        }
+
+        let mut sink = self.sink.lock().expect("could not acquire sink lock");
+
+        if let Err(reason) = sink.write_all(&out) {
+            eprintln!("Error: {:?}", reason);
+        }
+    }
+}
+
+impl<P: Predictor, W: Write + Send> Predictor for TeeingPredictor<P, W> {
+    fn max_num_threads(&self) -> usize {
+        self.inner.max_num_threads()
+    }
+
+    fn identifier(&self) -> Option<String> {
+        self.inner.identifier()
+    }
+
+    fn save_cache(&self, path: &Path) -> io::Result<()> {
+        self.inner.save_cache(path)
+    }
+
+    fn load_cache(&self, path: &Path) -> io::Result<usize> {
+        self.inner.load_cache(path)
+    }
+
+    fn fetch(&self, board: &Board, to_move: Color, symmetry: symmetry::Transform) -> Option<Prediction> {
+        self.inner.fetch(board, to_move, symmetry)
+    }
+
+    fn cache(&self, board: &Board, to_move: Color, symmetry: symmetry::Transform, response: Prediction) {
+        self.inner.cache(board, to_move, symmetry, response)
+    }
+
+    fn predict(&self, features: &[f16], batch_size: usize) -> Vec<Prediction> {
+        self.predict_transformed(features, &vec! [symmetry::Transform::Identity; batch_size])
+    }
+
+    fn predict_transformed(&self, features: &[f16], transforms: &[symmetry::Transform]) -> Vec<Prediction> {
+        let responses = self.inner.predict_transformed(features, transforms);
+        self.write_batch(features, transforms, &responses);
+
+        responses
+    }
+
+    fn predict_with_value_embedding(&self, features: &[f16], batch_size: usize) -> Vec<Prediction> {
+        let responses = self.inner.predict_with_value_embedding(features, batch_size);
+        self.write_batch(features, &vec! [symmetry::Transform::Identity; batch_size], &responses);
+
+        responses
+    }
+
+    fn predict_value(&self, features: &[f16], batch_size: usize) -> Vec<f32> {
+        // there is no policy to record, so value-only queries are not written
+        // to the sink
+        self.inner.predict_value(features, batch_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use predictors::{MockPredictor, RandomPredictor};
+    use super::*;
+
+    #[test]
+    fn write_batch() {
+        let predictor = TeeingPredictor::new(RandomPredictor, vec! []);
+        let features = vec! [f16::from(1.0); 2 * features::Default::size()];
+        let transforms = [symmetry::Transform::Identity, symmetry::Transform::Rot90];
+        let responses = predictor.predict_transformed(&features, &transforms);
+        let sink = predictor.into_sink();
+
+        assert_eq!(responses.len(), 2);
+        assert_eq!(sink.len(), 4 + 2 * (1 + 2 * features::Default::size() + 2 + 2 * 362));
+        assert_eq!(&sink[0..4], &2u32.to_le_bytes());
+        assert_eq!(sink[4], 0);
+        assert_eq!(sink[4 + 1 + 2 * features::Default::size() + 2 + 2 * 362], symmetry::Transform::Rot90 as u8);
+    }
+
+    #[test]
+    fn write_single_transformed() {
+        let predictor = TeeingPredictor::new(RandomPredictor, vec! []);
+        let features = vec! [f16::from(1.0); features::Default::size()];
+        let response = predictor.predict_single_transformed(&features, symmetry::Transform::Rot90);
+        let sink = predictor.into_sink();
+
+        assert!(response.is_some());
+        assert_eq!(&sink[0..4], &1u32.to_le_bytes());
+        assert_eq!(sink[4], symmetry::Transform::Rot90 as u8);
+    }
+
+    #[test]
+    fn forward_predict_value() {
+        let value_predictor = MockPredictor::constant(0.0)
+            .with_predict_value(|_features, batch_size| vec! [1.0; batch_size]);
+        let predictor = TeeingPredictor::new(value_predictor, vec! []);
+        let features = vec! [f16::from(1.0); 2 * features::Default::size()];
+
+        assert_eq!(predictor.predict_value(&features, 2), vec! [1.0, 1.0]);
+        assert!(predictor.into_sink().is_empty());
+    }
+}
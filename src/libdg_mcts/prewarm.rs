@@ -0,0 +1,101 @@
+// Copyright 2021 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use dg_go::utils::sgf::{get_komi_from_sgf, Sgf};
+use dg_go::{DEFAULT_KOMI, Point};
+use super::options::SearchOptions;
+use super::predictor::Predictor;
+use super::full_forward;
+
+/// Evaluate every position in the given SGF files so that their predictions,
+/// for all symmetries, are stored in the cache of `predictor`. This avoids
+/// any latency on positions that are likely to occur, such as common
+/// openings. Each SGF is replayed until its first illegal move, or parse
+/// error. Returns the number of positions that were evaluated.
+///
+/// # Arguments
+///
+/// * `predictor` - the predictor whose cache to fill
+/// * `options` - the search options to evaluate the positions with
+/// * `sgfs` - the SGF files to replay
+///
+pub fn prewarm(predictor: &dyn Predictor, options: Box<dyn SearchOptions + Sync>, sgfs: &[&str]) -> usize {
+    let mut count = 0;
+
+    for sgf in sgfs {
+        let komi = get_komi_from_sgf(sgf).unwrap_or(DEFAULT_KOMI);
+        let mut last = None;
+
+        for entry in Sgf::new(sgf.as_bytes(), komi) {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(_) => break
+            };
+
+            if full_forward(predictor, &options, &entry.board, entry.color).is_some() {
+                count += 1;
+            }
+
+            last = Some(entry);
+        }
+
+        // the position after the final move is not part of any entry
+        if let Some(entry) = last {
+            let mut board = entry.board;
+
+            if entry.point != Point::default() {
+                board.place(entry.color, entry.point);
+            } else {
+                board.pass(entry.color);
+            }
+
+            if full_forward(predictor, &options, &board, entry.color.opposite()).is_some() {
+                count += 1;
+            }
+        }
+    }
+
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use options::StandardSearch;
+    use predictors::{MockPredictor, RandomPredictor};
+    use super::*;
+
+    /// Returns a predictor with random predictions, that counts the number of
+    /// calls to `cache`.
+    fn counting_predictor() -> MockPredictor {
+        MockPredictor::new(|features, batch_size| RandomPredictor.predict(features, batch_size))
+    }
+
+    #[test]
+    fn prewarm_all_positions() {
+        let predictor = counting_predictor();
+        let count = prewarm(&predictor, Box::new(StandardSearch::new()), &["(;KM[7.5];B[dp];W[dd])", "(;B[pd];W[])"]);
+
+        assert_eq!(count, 6);
+        assert_eq!(predictor.num_cached(), 6 * 8);
+    }
+
+    #[test]
+    fn prewarm_stops_at_illegal_move() {
+        let predictor = counting_predictor();
+        let count = prewarm(&predictor, Box::new(StandardSearch::new()), &["(;B[dp];W[dp];B[dd])"]);
+
+        assert_eq!(count, 2);
+    }
+}
@@ -12,22 +12,20 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use dg_utils::config;
+use dg_utils::{config, gzip};
 use dg_go::utils::sgf::{self, Sgf};
 use dg_go::{Board, Color, Point};
-use super::{GameResult, Played, predict, greedy_score};
-use super::pool::Pool;
+use super::{GameResult, Played, predict, full_forward, greedy_score, get_setup_as_sgf, tree};
+use super::pool::{Pool, PoolHandle};
 use super::predictors::DefaultPredictor;
 use super::time_control::RolloutLimit;
-use options::StandardSearch;
+use options::{SearchOptions, StandardSearch};
 
 use crossbeam_channel;
 use rand::{Rng, thread_rng};
-use std::fs::File;
 use std::sync::mpsc;
-use std::io::{BufRead, BufReader};
+use std::io::BufRead;
 use std::thread;
-use std::sync::Arc;
 
 struct Candidate {
     board: Board,
@@ -62,7 +60,47 @@ fn collect_candidates_from_line(content: &str, komi: f32) -> Vec<Candidate> {
     candidates
 }
 
-/// Reanalyze a given `candidate`.
+/// Returns the winrate, from the perspective of the player to move, of the
+/// move that was actually played in the given `candidate`. If the search did
+/// not visit the played move then it is evaluated using the neural network.
+///
+/// # Arguments
+///
+/// * `pool` -
+/// * `candidate` -
+/// * `tree` - the search tree of the candidate
+///
+fn get_played_winrate(
+    pool: &Pool,
+    candidate: &Candidate,
+    tree: &tree::Node
+) -> Option<f32>
+{
+    let index = if candidate.point == Point::default() {
+        361
+    } else {
+        candidate.point.to_packed_index()
+    };
+
+    if tree.with(index, |child| child.count()) > 0 {
+        Some(tree.with(index, |child| child.value()))
+    } else {
+        let options: Box<dyn SearchOptions + Sync> = Box::new(StandardSearch::new());
+        let mut board = candidate.board.clone();
+
+        if candidate.point != Point::default() {
+            board.place(candidate.to_move, candidate.point);
+        } else {
+            board.pass(candidate.to_move);
+        }
+
+        full_forward(pool.predictor(), &options, &board, candidate.to_move.opposite())
+            .map(|(value, _)| 1.0 - value)
+    }
+}
+
+/// Reanalyze a given `candidate`, annotating the played move with the winrate
+/// it loses compared to the best move according to the search.
 ///
 /// # Arguments
 ///
@@ -84,7 +122,13 @@ fn reanalyze_single_candidate(
     );
 
     result.map(|(value, _, tree)| {
-        Played::from_mcts(candidate.to_move, candidate.point, value, &tree)
+        let played = Played::from_mcts(candidate.to_move, candidate.point, value, &tree);
+        let (best_winrate, _) = tree.best(0.0, StandardSearch::new().tiebreak());
+
+        match get_played_winrate(pool, candidate, &tree) {
+            Some(played_winrate) => played.with_winrate_loss(best_winrate - played_winrate),
+            None => played
+        }
     })
 }
 
@@ -118,8 +162,12 @@ fn reanalyze_single_line(
 {
     if let Ok(komi) = sgf::get_komi_from_sgf(&content) {
         let candidates = collect_candidates_from_line(&content, komi);
-        let mut board = Board::new(komi);
-        let mut sgf = String::new();
+
+        // start from the setup position of the game, if it has one
+        let mut board = candidates.first()
+            .map(|cand| cand.board.clone())
+            .unwrap_or_else(|| Board::new(komi));
+        let mut sgf = get_setup_as_sgf(&board);
 
         for cand in &candidates {
             let analyze = is_good_candidate(cand);
@@ -130,8 +178,12 @@ fn reanalyze_single_line(
                 sgf += &format!("{}", Played::fixed(cand.to_move, cand.point));
             }
 
-            assert!(board.is_valid(cand.to_move, cand.point));
-            board.place(cand.to_move, cand.point);
+            if cand.point == Point::default() {
+                board.pass(cand.to_move);
+            } else {
+                assert!(board.is_valid(cand.to_move, cand.point));
+                board.place(cand.to_move, cand.point);
+            }
         }
 
         if sgf::is_scored(&content) {
@@ -155,6 +207,7 @@ fn reanalyze_single_line(
 }
 
 /// Read each line in the provided file, and send it over the provided `channel`.
+/// The file is decompressed on the fly if it is gzip compressed.
 ///
 /// # Arguments
 ///
@@ -162,8 +215,8 @@ fn reanalyze_single_line(
 /// * `sender` -
 ///
 fn parse_single_file(file: String, sender: crossbeam_channel::Sender<String>) {
-    if let Ok(f) = File::open(file) {
-        for line in BufReader::new(&f).lines() {
+    if let Ok(f) = gzip::open(file) {
+        for line in f.lines() {
             if let Ok(line) = line {
                 if sender.send(line).is_err() {
                     break;
@@ -194,9 +247,9 @@ fn spawn_file_workers(files: &[String]) -> crossbeam_channel::Receiver<String> {
 
 pub fn reanalyze(
     files: &[String]
-) -> (mpsc::Receiver<GameResult>, Arc<Pool>)
+) -> (mpsc::Receiver<GameResult>, PoolHandle)
 {
-    let pool = Arc::new(Pool::new(Box::new(DefaultPredictor::default())));
+    let pool = Pool::new(Box::new(DefaultPredictor::default())).into_handle();
     let lines = spawn_file_workers(files);
 
     // spawn the worker threads that generate the self-play games
@@ -210,7 +263,7 @@ pub fn reanalyze(
 
         thread::spawn(move || {
             for line in &lines {
-                if let Some(result) = reanalyze_single_line(pool.as_ref(), line) {
+                if let Some(result) = reanalyze_single_line(&pool, line) {
                     sender.send(result).unwrap();
                 }
             }
@@ -222,6 +275,7 @@ pub fn reanalyze(
 
 #[cfg(test)]
 mod tests {
+    use predictors::RandomPredictor;
     use super::*;
 
     #[test]
@@ -238,4 +292,32 @@ mod tests {
         assert_eq!(actual[3].to_move, Color::White);
         assert_eq!(actual[3].point, Point::new(3, 3));
     }
+
+    #[test]
+    fn keep_setup_position() {
+        let pool = Pool::with_capacity(Box::new(RandomPredictor::default()), 1, None);
+        let game = reanalyze_single_line(&pool, "(;GM[1]KM[7.5]RE[W+Resign]AB[dd]AW[pp]PL[W];W[dp];B[])".to_string())
+            .expect("could not reanalyze game");
+
+        match game {
+            GameResult::Resign(ref sgf, ref board, winner, _) => {
+                assert!(sgf.starts_with("AB[dd]AW[pp]PL[W];W[dp]"), "{}", sgf);
+                assert_eq!(board.at(Point::new(3, 3)), Some(Color::Black));
+                assert_eq!(board.at(Point::new(15, 15)), Some(Color::White));
+                assert_eq!(board.at(Point::new(3, 15)), Some(Color::White));
+                assert_eq!(winner, Color::White);
+            },
+            _ => panic!("expected a resigned game")
+        }
+    }
+
+    #[test]
+    fn annotate_winrate_loss() {
+        let pool = Pool::with_capacity(Box::new(RandomPredictor::default()), 1, None);
+        let candidates = collect_candidates_from_line(&"(;B[aa])", 7.5);
+        let played = reanalyze_single_candidate(&pool, &candidates[0]).expect("could not reanalyze candidate");
+
+        assert!(played.to_string().contains("VL["), "{}", played);
+        assert!(played.to_string().contains("C[Winrate loss: "), "{}", played);
+    }
 }
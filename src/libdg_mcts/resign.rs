@@ -0,0 +1,300 @@
+// Copyright 2021 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use dg_utils::config::{self, ResignMetric};
+use tree;
+
+/// What to look at when deciding whether a position is lost.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ResignCriterion {
+    /// The winrate below which a position is considered lost.
+    Value(f32),
+
+    /// The estimated score margin below which a position is considered lost.
+    Score(f32),
+
+    /// Both the winrate and the estimated score margin must fall below their
+    /// respective threshold for a position to be considered lost.
+    Both(f32, f32)
+}
+
+impl Default for ResignCriterion {
+    fn default() -> Self {
+        match *config::RESIGN_CRITERION {
+            ResignMetric::Value => ResignCriterion::Value(*config::RESIGN_THRESHOLD),
+            ResignMetric::Score => ResignCriterion::Score(*config::RESIGN_SCORE_THRESHOLD),
+            ResignMetric::Both => ResignCriterion::Both(*config::RESIGN_THRESHOLD, *config::RESIGN_SCORE_THRESHOLD)
+        }
+    }
+}
+
+impl ResignCriterion {
+    /// Returns whether this criterion requires an estimate of the score to
+    /// decide whether the given evaluation is lost. For `Both` the score is
+    /// only needed if the value is already considered lost.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - the value of the neural network
+    /// * `best_value` - the winrate of the best move according to the search
+    ///
+    pub fn needs_score(&self, value: f32, best_value: f32) -> bool {
+        match *self {
+            ResignCriterion::Value(_) => false,
+            ResignCriterion::Score(_) => true,
+            ResignCriterion::Both(value_threshold, _) => is_value_lost(value, best_value, value_threshold)
+        }
+    }
+
+    /// Returns whether the given evaluation is considered lost.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - the value of the neural network
+    /// * `best_value` - the winrate of the best move according to the search
+    /// * `score` - the estimated score margin, if available
+    ///
+    fn is_lost(&self, value: f32, best_value: f32, score: Option<f32>) -> bool {
+        let is_value_lost = |threshold: f32| is_value_lost(value, best_value, threshold);
+        let is_score_lost = |threshold: f32| {
+            score.map(|score| score.is_finite() && score < threshold).unwrap_or(false)
+        };
+
+        match *self {
+            ResignCriterion::Value(threshold) => is_value_lost(threshold),
+            ResignCriterion::Score(threshold) => is_score_lost(threshold),
+            ResignCriterion::Both(value_threshold, score_threshold) => {
+                is_value_lost(value_threshold) && is_score_lost(score_threshold)
+            }
+        }
+    }
+}
+
+/// Returns whether both the value of the neural network and the winrate of the
+/// best move are below the given threshold.
+///
+/// # Arguments
+///
+/// * `value` -
+/// * `best_value` -
+/// * `threshold` -
+///
+fn is_value_lost(value: f32, best_value: f32, threshold: f32) -> bool {
+    value.is_finite() && value < threshold &&
+    best_value.is_finite() && best_value < threshold
+}
+
+/// Decides whether a player should resign, based on both the value of the
+/// neural network and the result of the search. To avoid resigning because of
+/// a single noisy evaluation the game must be judged to be lost for several
+/// consecutive moves.
+pub struct ResignController {
+    criterion: ResignCriterion,
+    min_visits: i32,
+    num_consecutive: usize,
+    count: usize
+}
+
+impl Default for ResignController {
+    fn default() -> Self {
+        Self::new(
+            ResignCriterion::default(),
+            *config::RESIGN_MIN_VISITS,
+            *config::RESIGN_CONSECUTIVE
+        )
+    }
+}
+
+impl ResignController {
+    /// Returns a resign controller with the given parameters.
+    ///
+    /// # Arguments
+    ///
+    /// * `criterion` - when a position is considered lost
+    /// * `min_visits` - the minimum number of visits for the best move
+    /// * `num_consecutive` - the number of consecutive lost positions
+    ///
+    pub fn new(criterion: ResignCriterion, min_visits: i32, num_consecutive: usize) -> Self {
+        Self {
+            criterion,
+            min_visits,
+            num_consecutive: num_consecutive.max(1),
+            count: 0
+        }
+    }
+
+    /// Returns the criterion used by this controller.
+    pub fn criterion(&self) -> ResignCriterion {
+        self.criterion
+    }
+
+    /// Update the state of this controller with the evaluation of the current
+    /// move, and returns whether the player should resign.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - the value of the neural network
+    /// * `best_value` - the winrate of the best move according to the search
+    /// * `best_visits` - the number of visits of the best move
+    ///
+    pub fn update(&mut self, value: f32, best_value: f32, best_visits: i32) -> bool {
+        self.update_with_score(value, best_value, best_visits, None)
+    }
+
+    /// Update the state of this controller with the evaluation of the current
+    /// move, including an estimate of the score margin, and returns whether
+    /// the player should resign.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - the value of the neural network
+    /// * `best_value` - the winrate of the best move according to the search
+    /// * `best_visits` - the number of visits of the best move
+    /// * `score` - the estimated score margin of the player, if available
+    ///
+    pub fn update_with_score(&mut self, value: f32, best_value: f32, best_visits: i32, score: Option<f32>) -> bool {
+        let is_lost =
+            self.criterion.is_lost(value, best_value, score) &&
+            best_visits >= self.min_visits;
+
+        if is_lost {
+            self.count += 1;
+        } else {
+            self.count = 0;
+        }
+
+        self.should_resign()
+    }
+
+    /// Update the state of this controller with the given search tree, and
+    /// returns whether the player should resign.
+    ///
+    /// # Arguments
+    ///
+    /// * `tree` - the search tree of the current move
+    /// * `index` - the move that was picked from the search tree
+    /// * `score` - the estimated score margin of the player, if available
+    ///
+    pub fn update_from_tree(&mut self, tree: &tree::Node, index: usize, score: Option<f32>) -> bool {
+        let (best_value, best_visits) = tree.with(index, |child| (child.value(), child.count()));
+
+        self.update_with_score(tree.initial_value, best_value, best_visits, score)
+    }
+
+    /// Update the state of this controller with a move that was played using
+    /// only the policy of the neural network, and returns whether the player
+    /// should resign. Since there is no search to agree with, the value of the
+    /// neural network is trusted on its own.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - the value of the neural network
+    /// * `score` - the estimated score margin of the player, if available
+    ///
+    pub fn update_from_policy(&mut self, value: f32, score: Option<f32>) -> bool {
+        self.update_with_score(value, value, self.min_visits, score)
+    }
+
+    /// Returns whether the player should resign.
+    pub fn should_resign(&self) -> bool {
+        self.count >= self.num_consecutive
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_noisy_evaluation() {
+        let mut resign = ResignController::new(ResignCriterion::Value(0.05), 100, 2);
+
+        assert!(!resign.update(0.01, 0.01, 200));
+        assert!(!resign.update(0.50, 0.01, 200));
+        assert!(!resign.update(0.01, 0.01, 200));
+    }
+
+    #[test]
+    fn consecutive_evaluations() {
+        let mut resign = ResignController::new(ResignCriterion::Value(0.05), 100, 2);
+
+        assert!(!resign.update(0.01, 0.01, 200));
+        assert!(resign.update(0.02, 0.03, 200));
+        assert!(resign.should_resign());
+    }
+
+    #[test]
+    fn value_and_search_disagree() {
+        let mut resign = ResignController::new(ResignCriterion::Value(0.05), 100, 1);
+
+        assert!(!resign.update(0.01, 0.50, 200));
+        assert!(!resign.update(0.50, 0.01, 200));
+        assert!(resign.update(0.01, 0.01, 200));
+    }
+
+    #[test]
+    fn too_few_visits() {
+        let mut resign = ResignController::new(ResignCriterion::Value(0.05), 100, 1);
+
+        assert!(!resign.update(0.01, 0.01, 99));
+        assert!(resign.update(0.01, 0.01, 100));
+    }
+
+    #[test]
+    fn non_finite_values() {
+        let mut resign = ResignController::new(ResignCriterion::Value(0.05), 100, 1);
+
+        assert!(!resign.update(::std::f32::NAN, 0.01, 200));
+        assert!(!resign.update(0.01, ::std::f32::NEG_INFINITY, 200));
+    }
+
+    #[test]
+    fn score_criterion() {
+        let mut resign = ResignController::new(ResignCriterion::Score(-10.0), 100, 1);
+
+        assert!(!resign.update(0.01, 0.01, 200));
+        assert!(!resign.update_with_score(0.50, 0.50, 200, Some(-5.0)));
+        assert!(resign.update_with_score(0.50, 0.50, 200, Some(-15.0)));
+        assert!(!resign.update_with_score(0.50, 0.50, 99, Some(-15.0)));
+    }
+
+    #[test]
+    fn both_criterion() {
+        let mut resign = ResignController::new(ResignCriterion::Both(0.05, -10.0), 100, 1);
+
+        assert!(!resign.update_with_score(0.01, 0.01, 200, Some(-5.0)));
+        assert!(!resign.update_with_score(0.50, 0.50, 200, Some(-15.0)));
+        assert!(!resign.update_with_score(0.01, 0.01, 200, None));
+        assert!(resign.update_with_score(0.01, 0.01, 200, Some(-15.0)));
+    }
+
+    #[test]
+    fn policy_only() {
+        let mut resign = ResignController::new(ResignCriterion::Value(0.05), 100, 2);
+
+        assert!(!resign.update(0.01, 0.01, 200));
+        assert!(resign.update_from_policy(0.01, None));
+        assert!(!resign.update_from_policy(0.50, None));
+        assert!(!resign.should_resign());
+    }
+
+    #[test]
+    fn needs_score() {
+        assert!(!ResignCriterion::Value(0.05).needs_score(0.01, 0.01));
+        assert!(ResignCriterion::Score(-10.0).needs_score(0.5, 0.5));
+        assert!(ResignCriterion::Both(0.05, -10.0).needs_score(0.01, 0.01));
+        assert!(!ResignCriterion::Both(0.05, -10.0).needs_score(0.5, 0.01));
+        assert!(!ResignCriterion::Both(0.05, -10.0).needs_score(0.01, 0.5));
+    }
+}
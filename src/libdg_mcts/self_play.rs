@@ -14,27 +14,73 @@
 
 use dg_go::utils::score::Score;
 use dg_go::utils::sgf::{CGoban, SgfCoordinate};
-use dg_go::{Board, Color, Point};
+use dg_go::utils::symmetry;
+use dg_go::{Board, Color, Point, recommended_komi};
 use dg_utils::{b85, config};
-use super::{predict, full_forward, tree, GameResult, get_random_komi};
+use super::{predict, full_forward, tree, GameResult, ResignController, greedy_score, estimate_score_distribution, get_random_komi};
 use super::asm::sum_finite_f32;
-use super::choose::choose;
-use super::pool::Pool;
-use super::predictors::DefaultPredictor;
-use super::time_control::{TimeStrategy, RolloutLimit};
-use options::{SearchOptions, StandardSearch, ScoringSearch};
-
+use super::choose::{argmax_stable, choose_with_rng};
+use super::pool::{Pool, PoolHandle};
+use super::predictor::{Predictor, load_cache_file};
+use super::predictors::{DefaultPredictor, SanitizingPredictor};
+use super::time_control::{TimeStrategy, RolloutLimit, GameClock};
+use options::{SearchOptions, StandardSearch, StandardDeterministicSearch, ScoringSearch, TieBreak};
+
+use rand::seq::SliceRandom;
 use rand::{Rng, thread_rng};
+use std::collections::HashMap;
 use std::fmt::{self, Display, Formatter};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::mpsc::{sync_channel, Receiver};
 use std::sync::Arc;
 use std::thread;
+use std::time::{Duration, Instant};
 use ordered_float::OrderedFloat;
 
 /// The momentum to use when updating the moving average of the winrate.
 const MOMENTUM: f32 = 0.2;
 
+/// The smallest temperature that is ever used to select a move using only the
+/// policy, since a temperature of zero is a division by zero.
+const MIN_POLICY_TEMPERATURE: f32 = 0.01;
+
+/// The number of self-play games where resigning was disabled, but where one of
+/// the players would have resigned.
+static NUM_RESIGN_CHECKS: AtomicUsize = AtomicUsize::new(0);
+
+/// The number of games in `NUM_RESIGN_CHECKS` that were won by the player that
+/// would have resigned.
+static NUM_FALSE_RESIGNS: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns the number of self-play games that would have been resigned by the
+/// losing player, but were played until the end since resigning was disabled
+/// for them (see `SELF_PLAY_NO_RESIGN_FRACTION`), and how many of those were
+/// won by the player that would have resigned.
+pub fn false_resign_count() -> (usize, usize) {
+    (
+        NUM_RESIGN_CHECKS.load(Ordering::Acquire),
+        NUM_FALSE_RESIGNS.load(Ordering::Acquire)
+    )
+}
+
+/// Record the result of a game where resigning was disabled, see
+/// `false_resign_count`.
+///
+/// # Arguments
+///
+/// * `would_resign` - the first player that would have resigned, if any
+/// * `result` - the result of the game
+///
+fn record_resign_check(would_resign: Option<Color>, result: &GameResult) {
+    if let Some(color) = would_resign {
+        NUM_RESIGN_CHECKS.fetch_add(1, Ordering::AcqRel);
+
+        if result.winner() == Some(color) {
+            NUM_FALSE_RESIGNS.fetch_add(1, Ordering::AcqRel);
+        }
+    }
+}
+
 /// An moving average of values.
 struct MovingAverage {
     average: f32,
@@ -58,6 +104,32 @@ impl MovingAverage {
     }
 }
 
+/// Counts how many times each whole-board position has occurred during a
+/// game, in order to detect long cycles (such as triple ko) that the
+/// super-ko history is too short to prevent.
+struct RepetitionCounter {
+    counts: HashMap<(u64, Color), usize>
+}
+
+impl RepetitionCounter {
+    fn new() -> Self {
+        Self { counts: HashMap::new() }
+    }
+
+    /// Records the current position of the given board, and returns the
+    /// number of times it has occurred so far.
+    ///
+    /// # Arguments
+    ///
+    /// * `board` -
+    ///
+    fn update(&mut self, board: &Board) -> usize {
+        let count = self.counts.entry((board.zobrist_hash(), board.to_move())).or_insert(0);
+        *count += 1;
+        *count
+    }
+}
+
 /// Returns the skewness of the given vector as defined by Pearson's moment
 /// coefficient of skewness [1].
 ///
@@ -96,6 +168,10 @@ pub struct Played {
     explain: String,
     softmax: Vec<f32>,
     prior_point: Point,
+    confidence: Option<f32>,
+    winrate_loss: Option<f32>,
+    time_left: Option<f32>,
+    is_symmetric: bool,
 }
 
 impl Played {
@@ -107,7 +183,11 @@ impl Played {
             num_rollout: 0,
             explain: String::new(),
             softmax: vec! [],
-            prior_point: Point::default()
+            prior_point: Point::default(),
+            confidence: None,
+            winrate_loss: None,
+            time_left: None,
+            is_symmetric: false
         }
     }
 
@@ -119,7 +199,11 @@ impl Played {
             num_rollout: 0,
             explain: String::new(),
             softmax: vec! [],
-            prior_point: Point::default()
+            prior_point: Point::default(),
+            confidence: None,
+            winrate_loss: None,
+            time_left: None,
+            is_symmetric: false
         }
     }
 
@@ -132,9 +216,11 @@ impl Played {
     {
         let (_, prior_index) = tree.prior();
         let prior_point = Point::from_packed_parts(prior_index);
-        let softmax = tree.softmax();
-        let explain = tree::to_pretty(tree).to_string();
-        let num_rollout = tree.size();
+        let softmax = tree.visit_target(*config::TARGET_TEMPERATURE);
+        let explain = tree::to_pretty(tree, TieBreak::default()).to_string();
+        let num_rollout = tree.visit_count();
+        let num_visits = tree.with(point.to_packed_index(), |child| child.count());
+        let confidence = Some(num_visits as f32 / num_rollout.max(1) as f32);
         let value = Some(value);
 
         Self {
@@ -145,6 +231,10 @@ impl Played {
             explain,
             softmax,
             prior_point,
+            confidence,
+            winrate_loss: None,
+            time_left: None,
+            is_symmetric: false,
         }
     }
 
@@ -168,6 +258,49 @@ impl Played {
             explain,
             softmax,
             prior_point,
+            confidence: None,
+            winrate_loss: None,
+            time_left: None,
+            is_symmetric: false,
+        }
+    }
+
+    /// Returns this move annotated with how much worse its winrate is compared
+    /// to the best move according to the search.
+    ///
+    /// # Arguments
+    ///
+    /// * `winrate_loss` - the winrate of the best move minus the winrate of
+    ///   the played move
+    ///
+    pub fn with_winrate_loss(self, winrate_loss: f32) -> Self {
+        Self {
+            winrate_loss: Some(winrate_loss),
+            ..self
+        }
+    }
+
+    /// Returns this move annotated with the time that the player who made it
+    /// has left on their clock.
+    ///
+    /// # Arguments
+    ///
+    /// * `time_left` - the remaining time in seconds
+    ///
+    pub fn with_time_left(self, time_left: f32) -> Self {
+        Self {
+            time_left: Some(time_left),
+            ..self
+        }
+    }
+
+    /// Returns this move annotated as having been played in a symmetric
+    /// position, where the symmetric candidate moves were eliminated before
+    /// the search.
+    pub fn with_symmetric(self) -> Self {
+        Self {
+            is_symmetric: true,
+            ..self
         }
     }
 
@@ -189,7 +322,11 @@ impl Display for Played {
         write!(f, ";{}[{}]", self.to_move, CGoban::to_sgf(self.point))?;
 
         if !self.explain.is_empty() {
-            write!(f, "C[{}]", self.explain.replace("\n", "\r"))?;
+            if let Some(winrate_loss) = self.winrate_loss {
+                write!(f, "C[Winrate loss: {:.1}%\r{}]", 100.0 * winrate_loss, self.explain.replace("\n", "\r"))?;
+            } else {
+                write!(f, "C[{}]", self.explain.replace("\n", "\r"))?;
+            }
         }
 
         if self.prior_point != Point::default() {
@@ -206,29 +343,93 @@ impl Display for Played {
         }
 
         if let Some(value) = self.normalized_win_rate() {
-            write!(f, "V[{:.4}]", value)
-        } else {
-            Ok(())
+            write!(f, "V[{:.4}]", value)?;
+        }
+
+        if let Some(confidence) = self.confidence {
+            write!(f, "VC[{:.4}]", confidence)?;
         }
+
+        if let Some(winrate_loss) = self.winrate_loss {
+            write!(f, "VL[{:.4}]", winrate_loss)?;
+        }
+
+        if let Some(time_left) = self.time_left {
+            write!(f, "{}L[{:.1}]", self.to_move, time_left)?;
+        }
+
+        if self.is_symmetric {
+            write!(f, "SY[]")?;
+        }
+
+        Ok(())
     }
 }
 
 /// An AI-player in a game.
 struct Player {
     winrate: MovingAverage,
+    resign: ResignController,
     root: Option<tree::Node>,
     color: Color,
+    policy_temperature: f32,
+    clock: Option<GameClock>,
+    deterministic: bool,
 }
 
 impl Player {
-    fn new(color: Color) -> Self {
+    fn new(color: Color, board: &Board, deterministic: bool) -> Self {
         Self {
             winrate: MovingAverage::new(0.5, MOMENTUM),
+            resign: ResignController::default(),
             root: None,
             color: color,
+            policy_temperature: *config::POLICY_TEMPERATURE,
+            clock: config::SGF_MAIN_TIME.map(|main_time| GameClock::new(main_time, 0.0, board.count(), board)),
+            deterministic: deterministic,
         }
     }
 
+    /// Spend the given time on this players game clock, and returns the main
+    /// time that remains afterwards. Returns `None` if this player does not
+    /// have a clock (see `SGF_MAIN_TIME`).
+    ///
+    /// # Arguments
+    ///
+    /// * `elapsed` - the time spent on the move
+    /// * `board` - the current board position
+    ///
+    fn spend_time(&mut self, elapsed: Duration, board: &Board) -> Option<f32> {
+        let clock = self.clock.as_ref()?.after_move(elapsed, board);
+        let main_time = clock.main_time();
+
+        self.clock = Some(clock);
+        Some(main_time)
+    }
+
+    /// Returns the search options to use for a position where passing is
+    /// allowed, which do not add any noise if this player is deterministic.
+    fn standard_search(&self) -> Box<dyn SearchOptions + Sync> {
+        if self.deterministic {
+            Box::new(StandardDeterministicSearch::new())
+        } else {
+            Box::new(StandardSearch::new())
+        }
+    }
+
+    /// Returns the temperature to use for the next move that is played using
+    /// only the policy, and then anneal it according to
+    /// `POLICY_TEMPERATURE_DECAY` and `POLICY_TEMPERATURE_MIN`. The returned
+    /// temperature is always at least `MIN_POLICY_TEMPERATURE`.
+    fn next_policy_temperature(&mut self) -> f32 {
+        let temperature = self.policy_temperature;
+
+        self.policy_temperature = (temperature * *config::POLICY_TEMPERATURE_DECAY)
+            .max(*config::POLICY_TEMPERATURE_MIN);
+
+        temperature.max(MIN_POLICY_TEMPERATURE)
+    }
+
     /// Returns the number of rollouts to perform for the current winrate. This
     /// will be a value between `*config::NUM_ROLLOUT` and 10% of it.
     fn num_rollout(&self) -> usize {
@@ -251,7 +452,7 @@ impl Player {
         if !allow_pass {
             let (value, index, tree) = predict(
                 pool,
-                Box::new(ScoringSearch::new()),
+                Box::new(ScoringSearch::default()),
                 time_strategy,
                 self.root.take().map(|mut n| {
                     n.disqualify(361);
@@ -265,7 +466,7 @@ impl Player {
         } else {
             predict(
                 pool,
-                Box::new(StandardSearch::new()),
+                self.standard_search(),
                 time_strategy,
                 self.root.take(),
                 &board,
@@ -363,14 +564,18 @@ impl Player {
                     Played::from_mcts(self.color, point, value, &tree)
                 };
 
+            let best_value = tree.with(index, |child| child.value());
+            let score = self.estimate_score_for_resign(board, pool, tree.initial_value, best_value);
+
             self.winrate.update(value);
+            self.resign.update_from_tree(&tree, index, score);
             self.root = tree::Node::forward(tree, index);
 
             Some(played)
         } else {
             let search_options: Box<dyn SearchOptions + Sync> =
                 if allow_pass {
-                    Box::new(StandardSearch::default())
+                    self.standard_search()
                 } else {
                     Box::new(ScoringSearch::default())
                 };
@@ -379,12 +584,14 @@ impl Player {
                 policy[361] = ::std::f32::NEG_INFINITY;
             }
 
-            let index = choose(
-                &policy.iter().map(|&x| OrderedFloat(x as f64)).collect::<Vec<_>>(),
-                0.5,
-                1.0 / *config::TEMPERATURE as f64,
-                thread_rng().gen::<f64>()
-            ).map(|(i, _)| i).unwrap_or(361);
+            let temperature = self.next_policy_temperature();
+            let weights = policy.iter().map(|&x| OrderedFloat(x as f64)).collect::<Vec<_>>();
+            let index =
+                if self.deterministic {
+                    argmax_stable(&weights, &vec! [(); weights.len()])
+                } else {
+                    choose_with_rng(&weights, 0.5, 1.0 / temperature as f64, &mut thread_rng()).map(|(i, _)| i)
+                }.unwrap_or(361);
 
             debug_assert!(0.0 <= value && value <= 1.0, "{}", value);
             debug_assert!(index < 362, "{}", index);
@@ -398,13 +605,36 @@ impl Player {
                     Played::from_forward(self.color, point, value, policy)
                 };
 
+            let score = self.estimate_score_for_resign(board, pool, value, value);
+
             self.winrate.update(value);
+            self.resign.update_from_policy(value, score);
             self.forward(point);
 
             Some(played)
         }
     }
 
+    /// Returns the estimated score margin of the given board, if resigning is
+    /// enabled and the resign criterion needs it for the given evaluation. The
+    /// game is only played out when the criterion actually needs it, since it
+    /// is fairly expensive.
+    ///
+    /// # Arguments
+    ///
+    /// * `board` -
+    /// * `pool` -
+    /// * `value` - the value of the neural network
+    /// * `best_value` - the winrate of the best move according to the search
+    ///
+    fn estimate_score_for_resign(&self, board: &Board, pool: &Pool, value: f32, best_value: f32) -> Option<f32> {
+        if *config::SELF_PLAY_RESIGN && self.resign.criterion().needs_score(value, best_value) {
+            Some(estimate_score_distribution(pool.predictor(), board, self.color, 1).0)
+        } else {
+            None
+        }
+    }
+
     fn forward(&mut self, point: Point) {
         if let Some(tree) = self.root.take() {
             self.root = tree::Node::forward(tree, point.to_packed_index());
@@ -412,42 +642,131 @@ impl Player {
     }
 }
 
+/// Returns true if the given board is symmetric over any symmetry other than
+/// the identity, in which case symmetric candidate moves are eliminated before
+/// the search.
+///
+/// # Arguments
+///
+/// * `board` -
+///
+fn is_symmetric(board: &Board) -> bool {
+    symmetry::ALL.iter()
+        .any(|&t| t != symmetry::Transform::Identity && symmetry::is_symmetric(board, t))
+}
+
+/// Returns the stones on the given board as SGF setup properties (`AB` and
+/// `AW`), and the player to move (`PL`). Returns an empty string for an empty
+/// board with black to move.
+///
+/// # Arguments
+///
+/// * `board` -
+///
+pub fn get_setup_as_sgf(board: &Board) -> String {
+    let stones = |color: Color| {
+        Point::all()
+            .filter(|&point| board.at(point) == Some(color))
+            .map(|point| format!("[{}]", CGoban::to_sgf(point)))
+            .collect::<String>()
+    };
+    let black = stones(Color::Black);
+    let white = stones(Color::White);
+    let mut out = String::new();
+
+    if !black.is_empty() {
+        out += &format!("AB{}", black);
+    }
+
+    if !white.is_empty() {
+        out += &format!("AW{}", white);
+    }
+
+    if !out.is_empty() || board.to_move() != Color::Black {
+        out += &format!("PL[{}]", board.to_move());
+    }
+
+    out
+}
+
 /// Play a game against the engine and return the result of the game.
 ///
 /// # Arguments
 ///
-/// * `pool` - the pool to use during evaluation
-/// * `num_parallel` - the number of games that are being played in parallel
+/// * `pools` - the pool to use during evaluation for black, and white
 /// * `ex_it` - whether to enable with expert iteration
+/// * `deterministic` - whether to play without any noise or randomness
+/// * `starting_point` - the position to start the game from
 ///
 fn self_play_one(
-    pool: &Pool,
-    ex_it: bool
+    pools: [&Pool; 2],
+    ex_it: bool,
+    deterministic: bool,
+    starting_point: Board
 ) -> Option<GameResult>
 {
-    let mut board = Board::new(get_random_komi());
-    let mut sgf = String::new();
+    let mut board = starting_point;
+    let mut sgf = get_setup_as_sgf(&board);
     let mut pass_count = 0;
+    let mut repetitions = RepetitionCounter::new();
+    let allow_resign = *config::SELF_PLAY_RESIGN && thread_rng().gen::<f32>() >= *config::SELF_PLAY_NO_RESIGN_FRACTION;
+    let mut would_resign = None;
 
     let mut players: Vec<Player> = vec! [
-        Player::new(Color::Black),
-        Player::new(Color::White)
+        Player::new(Color::Black, &board, deterministic),
+        Player::new(Color::White, &board, deterministic)
     ];
 
+    if board.to_move() == Color::White {
+        players.reverse();
+    }
+
     while board.count() < 722 {
         let allow_pass = board.is_scorable();
-        let played = players[0].predict(&mut board, allow_pass, ex_it, pool)?;
+        let pool = if players[0].color == Color::Black { pools[0] } else { pools[1] };
+        let start_time = Instant::now();
+        let mut played = players[0].predict(&mut board, allow_pass, ex_it, pool)?;
+
+        if let Some(time_left) = players[0].spend_time(start_time.elapsed(), &board) {
+            played = played.with_time_left(time_left);
+        }
+
+        if *config::SGF_SYMMETRY && is_symmetric(&board) {
+            played = played.with_symmetric();
+        }
+
+        if *config::SELF_PLAY_RESIGN && players[0].resign.should_resign() {
+            if allow_resign {
+                let winner = players[0].color.opposite();
+
+                return Some(GameResult::Resign(sgf, board, winner, played.value.unwrap_or(0.0)))
+            } else if would_resign.is_none() {
+                would_resign = Some(players[0].color);
+            }
+        }
+
         sgf += &format!("{}", played);
 
         if played.point == Point::default() {  // passing move
             pass_count += 1;
+            board.pass(players[0].color);
 
             if pass_count >= 2 && board.is_scorable() {
-                return Some(GameResult::Ended(sgf, board))
+                let result = GameResult::Ended(sgf, board);
+
+                record_resign_check(would_resign, &result);
+                return Some(result)
             }
         } else {
             pass_count = 0;
             board.place(players[0].color, played.point);
+
+            if repetitions.update(&board) >= *config::MAX_REPETITIONS {
+                let result = GameResult::Ended(sgf, board);
+
+                record_resign_check(would_resign, &result);
+                return Some(result)
+            }
         }
 
         // swap whose turn it is to place a stone
@@ -455,27 +774,69 @@ fn self_play_one(
         players.reverse();
     }
 
-    Some(GameResult::Ended(sgf, board))
+    // the game reached the move cap, so the board is probably not settled. Play
+    // it out greedily before scoring it so that the result is not a raw count of
+    // a messy board. The moves of the greedy playout did not get any search, so
+    // they are not part of the game record
+    let pool = if players[0].color == Color::Black { pools[0] } else { pools[1] };
+    let (greedy_board, _) = greedy_score(pool.predictor(), &board, players[0].color);
+
+    let result = GameResult::Capped(sgf, greedy_board);
+
+    record_resign_check(would_resign, &result);
+    Some(result)
 }
 
 /// Play games against the engine and return the result of the games
-/// over the channel.
+/// over the channel. Every game is played using the same `Pool`, so they all
+/// share the evaluation cache of its predictor (see `config::CACHE_SIZE`).
 ///
 /// # Arguments
 ///
 /// * `network` - the neural network to use during evaluation
 /// * `num_games` - the number of games to generate
+/// * `concurrency` - the number of games to play in parallel, typically
+///   `config::NUM_GAMES`
 /// * `ex_it` - whether to enable with expert iteration
 ///
 pub fn self_play(
     num_games: usize,
+    concurrency: usize,
+    ex_it: bool
+) -> (Receiver<GameResult>, PoolHandle)
+{
+    self_play_from(&[], num_games, concurrency, ex_it)
+}
+
+/// Play games against the engine, where each game starts from a position
+/// that is sampled uniformly from `starting_positions`, and return the result
+/// of the games over the channel. The stones of the starting position are
+/// recorded as setup properties in the SGF of each game. If no starting
+/// positions are given then every game starts from an empty board, like
+/// `self_play`.
+///
+/// # Arguments
+///
+/// * `starting_positions` - the positions to start the games from
+/// * `num_games` - the number of games to generate
+/// * `concurrency` - the number of games to play in parallel, typically
+///   `config::NUM_GAMES`
+/// * `ex_it` - whether to enable with expert iteration
+///
+pub fn self_play_from(
+    starting_positions: &[Board],
+    num_games: usize,
+    concurrency: usize,
     ex_it: bool
-) -> (Receiver<GameResult>, Arc<Pool>)
+) -> (Receiver<GameResult>, PoolHandle)
 {
-    let pool = Arc::new(Pool::new(Box::new(DefaultPredictor::default())));
+    let pool = Pool::new(Box::new(SanitizingPredictor::new(DefaultPredictor::default()))).into_handle();
+    load_cache_file(pool.predictor());
+
+    let starting_positions = Arc::new(starting_positions.to_vec());
 
     // spawn the worker threads that generate the self-play games
-    let num_parallel = num_games.min(*config::NUM_GAMES);
+    let num_parallel = num_games.min(concurrency.max(1));
     let (sender, receiver) = sync_channel(3 * num_parallel);
     let processed = Arc::new(AtomicUsize::new(0));
 
@@ -483,10 +844,15 @@ pub fn self_play(
         let processed = processed.clone();
         let sender = sender.clone();
         let pool = pool.clone();
+        let starting_positions = starting_positions.clone();
 
         thread::spawn(move || {
             while processed.fetch_add(1, Ordering::AcqRel) < num_games {
-                if let Some(result) = self_play_one(pool.as_ref(), ex_it) {
+                let starting_point = starting_positions.choose(&mut thread_rng())
+                    .cloned()
+                    .unwrap_or_else(|| Board::new(get_random_komi()));
+
+                if let Some(result) = self_play_one([&pool, &pool], ex_it, false, starting_point) {
                     if sender.send(result).is_err() {
                         break
                     }
@@ -498,12 +864,235 @@ pub fn self_play(
     (receiver, pool)
 }
 
+/// The winner of a game played during `play_match`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MatchWinner {
+    A,
+    B,
+    Draw
+}
+
+impl MatchWinner {
+    /// Returns which network won, given the color of the winner and the color
+    /// that network `a` played.
+    ///
+    /// # Arguments
+    ///
+    /// * `winner` - the color of the winner, or `None` for a draw
+    /// * `a_color` - the color that network `a` played
+    ///
+    fn from_color(winner: Option<Color>, a_color: Color) -> Self {
+        match winner {
+            Some(color) if color == a_color => MatchWinner::A,
+            Some(_) => MatchWinner::B,
+            None => MatchWinner::Draw
+        }
+    }
+}
+
+/// The result of a game played during `play_match`.
+pub struct MatchResult {
+    /// The game that was played.
+    pub game: GameResult,
+
+    /// The color that network `a` played during this game.
+    pub a_color: Color,
+
+    /// The network that won the game.
+    pub winner: MatchWinner
+}
+
+/// Returns a board where the first `num_moves` moves have been sampled from
+/// the policy of the given predictor, or `None` if the predictor failed.
+///
+/// # Arguments
+///
+/// * `predictor` - the predictor to sample the moves from
+/// * `num_moves` - the number of moves to sample
+///
+fn get_random_opening(predictor: &dyn Predictor, num_moves: usize) -> Option<Board> {
+    let search_options: Box<dyn SearchOptions + Sync> = Box::new(StandardSearch::new());
+    let mut board = Board::new(recommended_komi(19));
+
+    for _ in 0..num_moves {
+        let to_move = board.to_move();
+        let (_, policy) = full_forward(predictor, &search_options, &board, to_move)?;
+        let weights = policy[..361].iter().map(|&x| OrderedFloat(x as f64)).collect::<Vec<_>>();
+        let (index, _) = choose_with_rng(&weights, 0.5, 1.0, &mut thread_rng())?;
+
+        board.place(to_move, Point::from_packed_parts(index));
+    }
+
+    Some(board)
+}
+
+/// Play games between two networks and return the result of the games over the
+/// channel. Network `a` plays black in every even game, and white in every
+/// odd game. Each pair of games starts from the same opening, which is
+/// sampled from the policy of one of the networks (see `MATCH_OPENING_MOVES`),
+/// and is otherwise played with the same komi and without any noise, so that
+/// the result only depends on the strength of the networks.
+///
+/// # Arguments
+///
+/// * `net_a` - the first network
+/// * `net_b` - the second network
+/// * `num_games` - the number of games to play
+///
+pub fn play_match(
+    net_a: Box<dyn Predictor + Sync>,
+    net_b: Box<dyn Predictor + Sync>,
+    num_games: usize
+) -> (Receiver<MatchResult>, [PoolHandle; 2])
+{
+    let pool_a = Pool::new(net_a).into_handle();
+    let pool_b = Pool::new(net_b).into_handle();
+
+    // spawn the worker threads that play the games, each worker plays both
+    // games of a pair so that they can share the same opening
+    let num_pairs = (num_games + 1) / 2;
+    let num_parallel = num_pairs.min(*config::NUM_GAMES);
+    let (sender, receiver) = sync_channel(6 * num_parallel);
+    let processed = Arc::new(AtomicUsize::new(0));
+
+    for _ in 0..num_parallel {
+        let processed = processed.clone();
+        let sender = sender.clone();
+        let pool_a = pool_a.clone();
+        let pool_b = pool_b.clone();
+
+        thread::spawn(move || {
+            'pairs: loop {
+                let pair_index = processed.fetch_add(1, Ordering::AcqRel);
+                if pair_index >= num_pairs {
+                    break
+                }
+
+                let opening_pool = if pair_index % 2 == 0 { &*pool_a } else { &*pool_b };
+                let opening = match get_random_opening(opening_pool.predictor(), *config::MATCH_OPENING_MOVES) {
+                    Some(opening) => opening,
+                    None => continue
+                };
+
+                for game_index in (2 * pair_index)..(2 * pair_index + 2).min(num_games) {
+                    let (a_color, pools) =
+                        if game_index % 2 == 0 {
+                            (Color::Black, [&*pool_a, &*pool_b])
+                        } else {
+                            (Color::White, [&*pool_b, &*pool_a])
+                        };
+
+                    if let Some(game) = self_play_one(pools, false, true, opening.clone()) {
+                        let winner = MatchWinner::from_color(game.winner(), a_color);
+
+                        if sender.send(MatchResult { game, a_color, winner }).is_err() {
+                            break 'pairs
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    (receiver, [pool_a, pool_b])
+}
+
 #[cfg(test)]
 mod tests {
     use ::options::StandardDeterministicSearch;
-    use ::predictors::FakePredictor;
+    use ::predictors::{FakePredictor, MockPredictor, RandomPredictor};
+    use ::Prediction;
+    use dg_go::utils::features::{self, Features, HWC};
+    use dg_go::utils::symmetry::Transform;
+    use dg_go::utils::sgf::Sgf;
+    use dg_utils::types::f16;
     use super::*;
 
+    #[test]
+    fn policy_temperature() {
+        let mut player = Player::new(Color::Black, &Board::new(7.5), false);
+
+        assert_eq!(player.next_policy_temperature(), *config::POLICY_TEMPERATURE);
+        assert_eq!(player.next_policy_temperature(), *config::POLICY_TEMPERATURE * *config::POLICY_TEMPERATURE_DECAY);
+
+        player.policy_temperature = 0.0;
+        assert_eq!(player.next_policy_temperature(), MIN_POLICY_TEMPERATURE);
+        assert_eq!(player.next_policy_temperature(), config::POLICY_TEMPERATURE_MIN.max(MIN_POLICY_TEMPERATURE));
+    }
+
+    #[test]
+    fn setup_of_empty_board() {
+        assert_eq!(get_setup_as_sgf(&Board::new(7.5)), "");
+    }
+
+    #[test]
+    fn setup_of_position() {
+        let mut board = Board::new(7.5);
+        board.place(Color::Black, Point::new(3, 3));
+        board.place(Color::White, Point::new(15, 15));
+        board.place(Color::Black, Point::new(15, 3));
+
+        assert_eq!(get_setup_as_sgf(&board), "AB[dd][pd]AW[pp]PL[W]");
+    }
+
+    #[test]
+    fn setup_round_trip() {
+        let starting_point = Board::from_diagram(7.5, "
+            . . . . .
+            . X O . .
+            X O . O .
+            . X O . .
+            . . . . .
+        ").expect("could not parse diagram");
+        let mut board = starting_point.clone();
+        board.place(Color::White, Point::new(3, 3));
+        board.place(Color::Black, Point::new(15, 15));
+
+        let game = GameResult::Ended(format!("{};W[dd];B[pp]", get_setup_as_sgf(&starting_point)), board.clone());
+        let sgf = game.to_sgf(None);
+        let entries = Sgf::new(sgf.as_bytes(), 7.5)
+            .map(|entry| entry.ok().expect("could not replay game"))
+            .collect::<Vec<_>>();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].color, Color::White);
+        assert!(entries[0].board.position_eq(&starting_point));
+        assert!(entries[1].board.position_eq(&{
+            let mut board = starting_point.clone();
+            board.place(Color::White, Point::new(3, 3));
+            board
+        }));
+
+        let features = |board: &Board| {
+            features::Default::new(board).get_features::<HWC, f32>(board.to_move(), Transform::Identity)
+        };
+
+        assert_eq!(entries[0].board.count(), starting_point.count());
+        assert_eq!(features(&entries[0].board), features(&starting_point));
+    }
+
+    #[test]
+    fn repetition_counter() {
+        let mut board = Board::new(7.5);
+        let mut repetitions = RepetitionCounter::new();
+
+        assert_eq!(repetitions.update(&board), 1);
+        board.place(Color::Black, Point::new(3, 3));
+        assert_eq!(repetitions.update(&board), 1);
+
+        board.set_to_move(Color::Black);
+        assert_eq!(repetitions.update(&board), 1);
+        board.set_to_move(Color::White);
+        assert_eq!(repetitions.update(&board), 2);
+    }
+
+    #[test]
+    fn match_winner() {
+        assert_eq!(MatchWinner::from_color(Some(Color::Black), Color::Black), MatchWinner::A);
+        assert_eq!(MatchWinner::from_color(Some(Color::Black), Color::White), MatchWinner::B);
+        assert_eq!(MatchWinner::from_color(None, Color::White), MatchWinner::Draw);
+    }
+
     #[test]
     fn moving_average() {
         let mut avg = MovingAverage::new(0.5, 0.2);
@@ -552,7 +1141,7 @@ mod tests {
 
     #[test]
     fn played_from_mcts() {
-        let server = Pool::with_capacity(Box::new(FakePredictor::new(1, 0.6)), 1);
+        let server = Pool::with_capacity(Box::new(FakePredictor::new(1, 0.6)), 1, None);
         let board = Board::new(0.5);
         let (value, index, tree) =
             predict(
@@ -571,6 +1160,94 @@ mod tests {
         assert!(played.contains("TR[ba]"), "{}", played);
         assert!(played.contains("V["), "{}", played);  // exact value depends on the shape of the rollouts, so we cannot check
         assert!(played.contains("P["), "{}", played);  // exact policy depends on the number of rollouts, so we cannot check
+        assert!(played.contains("VC["), "{}", played);
+    }
+
+    /// Returns the midpoint between the stones of the current player in the
+    /// given features, if there are exactly two of them.
+    fn midpoint(features: &[f16]) -> Option<usize> {
+        let num_features = features::Default::num_features();
+        let stones = Point::all()
+            .filter(|point| f32::from(features[num_features * point.to_packed_index() + 5]) > 0.5)
+            .collect::<Vec<_>>();
+
+        if stones.len() == 2 {
+            Some(Point::new(
+                (stones[0].x() + stones[1].x()) / 2,
+                (stones[0].y() + stones[1].y()) / 2
+            ).to_packed_index())
+        } else {
+            None
+        }
+    }
+
+    /// Returns a predictor that puts all of its policy on the midpoint between
+    /// the stones of the current player, as seen in the (possibly transformed)
+    /// features, if there are exactly two of them. Since the midpoint is
+    /// preserved by every symmetry, the policy is mis-oriented if it is not at
+    /// the midpoint of the un-transformed board.
+    fn midpoint_predictor() -> MockPredictor {
+        MockPredictor::new(|features_list, batch_size| {
+            features_list.chunks(features::Default::size())
+                .take(batch_size)
+                .map(|features| {
+                    let mut policy = vec! [f16::from(1.0); 362];
+
+                    if let Some(midpoint) = midpoint(features) {
+                        policy = vec! [f16::from(0.0); 362];
+                        policy[midpoint] = f16::from(1.0);
+                    }
+
+                    Prediction::new(f16::from(0.0), policy)
+                })
+                .collect()
+        })
+    }
+
+    fn argmax(policy: &[f32]) -> Point {
+        let index = (0..policy.len())
+            .max_by_key(|&i| OrderedFloat(policy[i]))
+            .unwrap();
+
+        Point::from_packed_parts(index)
+    }
+
+    #[test]
+    fn full_forward_policy_is_identity_oriented() {
+        let options: Box<dyn SearchOptions + Sync> = Box::new(StandardDeterministicSearch);
+        let mut board = Board::new(7.5);
+        board.place(Color::Black, Point::new(3, 3));
+        board.place(Color::Black, Point::new(3, 5));
+
+        let (_, policy) = full_forward(&midpoint_predictor(), &options, &board, Color::Black).unwrap();
+
+        assert_eq!(argmax(&policy), Point::new(3, 4));
+    }
+
+    #[test]
+    fn played_softmax_is_identity_oriented() {
+        let server = Pool::with_capacity(Box::new(midpoint_predictor()), 1, None);
+        let mut board = Board::new(7.5);
+        board.place(Color::Black, Point::new(3, 3));
+        board.place(Color::Black, Point::new(3, 5));
+
+        for _ in 0..8 {
+            let (value, index, tree) =
+                predict(
+                    &server,
+                    Box::new(StandardDeterministicSearch),
+                    Box::new(RolloutLimit::new(10)),
+                    None,
+                    &board,
+                    Color::Black
+                ).unwrap();
+
+            let point = Point::from_packed_parts(index);
+            let played = Played::from_mcts(Color::Black, point, value, &tree);
+
+            assert_eq!(point, Point::new(3, 4));
+            assert_eq!(argmax(&played.softmax), Point::new(3, 4));
+        }
     }
 
     #[test]
@@ -588,4 +1265,67 @@ mod tests {
             ";B[aa]".to_string()
         );
     }
+
+    #[test]
+    fn played_symmetric() {
+        assert_eq!(
+            format!("{}", Played::fixed(Color::Black, Point::new(0, 0)).with_symmetric()),
+            ";B[aa]SY[]".to_string()
+        );
+    }
+
+    #[test]
+    fn symmetric_board() {
+        let mut board = Board::new(7.5);
+        assert!(is_symmetric(&board));
+
+        board.place(Color::Black, Point::new(3, 3));
+        assert!(is_symmetric(&board));
+
+        board.place(Color::Black, Point::new(3, 15));
+        assert!(is_symmetric(&board));
+
+        board.place(Color::Black, Point::new(16, 2));
+        assert!(!is_symmetric(&board));
+    }
+
+    #[test]
+    fn played_time_left() {
+        assert_eq!(
+            format!("{}", Played::fixed(Color::White, Point::new(0, 0)).with_time_left(12.34)),
+            ";W[aa]WL[12.3]".to_string()
+        );
+    }
+
+    #[test]
+    fn random_opening() {
+        let opening = get_random_opening(&RandomPredictor, 8).expect("could not sample an opening");
+        let other = get_random_opening(&RandomPredictor, 8).expect("could not sample an opening");
+
+        assert_eq!(opening.count(), 8);
+        assert_eq!(opening.to_move(), Color::Black);
+        assert!(!opening.position_eq(&other));
+    }
+
+    #[test]
+    fn deterministic_player() {
+        let board = Board::new(7.5);
+
+        assert!(Player::new(Color::Black, &board, true).standard_search().deterministic());
+        assert!(!Player::new(Color::Black, &board, false).standard_search().deterministic());
+    }
+
+    #[test]
+    fn player_spend_time() {
+        let board = Board::new(7.5);
+        let mut player = Player::new(Color::Black, &board, false);
+
+        player.clock = None;
+        assert_eq!(player.spend_time(Duration::from_secs(1), &board), None);
+
+        player.clock = Some(GameClock::new(10.0, 0.0, 0, &board));
+        assert_eq!(player.spend_time(Duration::from_secs(4), &board), Some(6.0));
+        assert_eq!(player.spend_time(Duration::from_secs(4), &board), Some(2.0));
+        assert_eq!(player.spend_time(Duration::from_secs(4), &board), Some(0.0));
+    }
 }
@@ -0,0 +1,92 @@
+// Copyright 2021 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use dg_go::utils::features::{self, HWC, Features};
+use dg_go::utils::symmetry::Transform;
+use dg_go::{Board, Color, Point};
+use dg_utils::types::f16;
+use super::predictor::Predictor;
+
+/// Returns the value of the position after `color` plays each of the given
+/// moves, from the perspective of `color`. Every resulting position is
+/// evaluated in a single batch, using only the value head of the network.
+/// Moves that are not legal get a value of negative infinity.
+///
+/// # Arguments
+///
+/// * `predictor` - the predictor to use for evaluation
+/// * `board` - the current board position
+/// * `color` - the color to play each of the moves
+/// * `moves` - the moves to evaluate
+///
+pub fn evaluate_successors(predictor: &dyn Predictor, board: &Board, color: Color, moves: &[Point]) -> Vec<f32> {
+    let mut features_list = Vec::with_capacity(moves.len() * features::Default::size());
+    let mut indices = Vec::with_capacity(moves.len());
+
+    for (i, &point) in moves.iter().enumerate() {
+        if !board.is_valid(color, point) {
+            continue;
+        }
+
+        let mut other = board.clone();
+        other.place(color, point);
+
+        let features = features::Default::new(&other);
+        features_list.extend_from_slice(&features.get_features::<HWC, f16>(color.opposite(), Transform::Identity));
+        indices.push(i);
+    }
+
+    let mut values = vec! [f32::NEG_INFINITY; moves.len()];
+
+    if !indices.is_empty() {
+        let responses = predictor.predict_value(&features_list, indices.len());
+
+        // the values are from the perspective of the opponent, since it is
+        // their turn to play in each of the resulting positions
+        for (&i, value) in indices.iter().zip(responses) {
+            values[i] = -value;
+        }
+    }
+
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use predictors::RandomPredictor;
+    use super::*;
+
+    #[test]
+    fn evaluate_legal_moves() {
+        let board = Board::new(7.5);
+        let moves = [Point::new(3, 3), Point::new(15, 15), Point::new(9, 9)];
+        let values = evaluate_successors(&RandomPredictor, &board, Color::Black, &moves);
+
+        assert_eq!(values.len(), 3);
+        assert!(values.iter().all(|v| v.is_finite()), "{:?}", values);
+    }
+
+    #[test]
+    fn evaluate_illegal_move() {
+        let mut board = Board::new(7.5);
+        board.place(Color::White, Point::new(3, 3));
+
+        let moves = [Point::new(3, 3), Point::new(4, 4)];
+        let values = evaluate_successors(&RandomPredictor, &board, Color::Black, &moves);
+
+        assert_eq!(values[0], f32::NEG_INFINITY);
+        assert!(values[1].is_finite());
+    }
+}
@@ -0,0 +1,182 @@
+// Copyright 2021 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::{TimeStrategy, TimeStrategyResult};
+use tree;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// The number of visits to perform before the complexity of the position is
+/// considered.
+const MIN_VISITS: i32 = 100;
+
+/// The entropy of the root visit distribution below which there is only one
+/// plausible move, and the search is terminated early.
+const LOW_ENTROPY: f32 = 0.5;
+
+/// The entropy of the root visit distribution above which there are many
+/// plausible moves, and the search is extended past the base budget.
+const HIGH_ENTROPY: f32 = 1.5;
+
+/// The fraction of rollouts, of the number performed when the base budget
+/// expired, to add when extending the search.
+const EXTEND_FRACTION: f32 = 0.5;
+
+/// A time strategy that wraps a base budget, and after an initial burst of
+/// rollouts uses the entropy of the visit distribution at the root to decide
+/// whether it should terminate early (simple positions) or extend the search
+/// past the base budget (complex positions).
+#[derive(Clone)]
+pub struct ComplexityAwareTimeControl<T: TimeStrategy> {
+    /// The base budget of this time strategy.
+    base: T,
+
+    /// The number of visits the tree had in the beginning.
+    starting_visits: i32,
+
+    /// The total number of visits to extend the search until, or zero if the
+    /// search has not been extended.
+    extended_until: Arc<AtomicUsize>
+}
+
+impl<T: TimeStrategy> ComplexityAwareTimeControl<T> {
+    /// Returns a time strategy that follows `base` until the complexity of the
+    /// position is known, and then terminates or extends the search from it.
+    ///
+    /// # Arguments
+    ///
+    /// * `base` - the time strategy to use for the base budget
+    /// * `starting_visits` - the number of visits of the search tree when the
+    ///   search started
+    ///
+    pub fn new(base: T, starting_visits: i32) -> Self {
+        Self {
+            base,
+            starting_visits,
+            extended_until: Arc::new(AtomicUsize::new(0))
+        }
+    }
+}
+
+impl<T: TimeStrategy> TimeStrategy for ComplexityAwareTimeControl<T> {
+    fn try_extend(&self, root: &tree::Node) -> TimeStrategyResult {
+        if root.total_count - self.starting_visits < MIN_VISITS {
+            return self.base.try_extend(root);
+        }
+
+        let entropy = visit_entropy(root);
+
+        if entropy < LOW_ENTROPY {
+            return TimeStrategyResult::Expired;
+        }
+
+        let total_count = root.total_count as usize;
+        let extended_until = self.extended_until.load(Ordering::Acquire);
+
+        if extended_until > 0 {
+            return if total_count < extended_until {
+                TimeStrategyResult::NotExpired(extended_until - total_count)
+            } else {
+                TimeStrategyResult::Expired
+            };
+        }
+
+        match self.base.try_extend(root) {
+            TimeStrategyResult::Expired if entropy > HIGH_ENTROPY => {
+                let num_visits = root.total_count - self.starting_visits;
+                let extend_until = total_count + (EXTEND_FRACTION * num_visits as f32) as usize;
+
+                // if someone else extended before us, then that is ok
+                let _ = self.extended_until.compare_exchange(0, extend_until, Ordering::AcqRel, Ordering::Relaxed);
+
+                TimeStrategyResult::Extended
+            },
+            other => other
+        }
+    }
+}
+
+/// Returns the entropy (in nats) of the visit distribution of the children of
+/// the given node.
+///
+/// # Arguments
+///
+/// * `root` -
+///
+fn visit_entropy(root: &tree::Node) -> f32 {
+    let total = root.children.nonzero()
+        .map(|i| root.with(i, |child| child.count()))
+        .sum::<i32>() as f32;
+
+    if total <= 0.0 {
+        0.0
+    } else {
+        root.children.nonzero()
+            .map(|i| {
+                let p = root.with(i, |child| child.count()) as f32 / total;
+
+                -p * p.ln()
+            })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dg_go::Color;
+    use time_control::RolloutLimit;
+    use tree::{self, Node, NodeTrace};
+    use super::*;
+
+    fn visit(root: &mut Node, index: usize, times: usize) {
+        let trace: NodeTrace = vec! [(root as *mut Node, Color::Black, index)];
+
+        for _ in 0..times {
+            unsafe { tree::backup(&trace, Color::White, 0.5) };
+        }
+    }
+
+    #[test]
+    fn too_few_visits() {
+        let mut root = Node::new(Color::Black, 0.5, vec! [1.0; 362]);
+        let strategy = ComplexityAwareTimeControl::new(RolloutLimit::new(1000), 0);
+        visit(&mut root, 60, 10);
+
+        assert!(matches!(strategy.try_extend(&root), TimeStrategyResult::NotExpired(990)));
+    }
+
+    #[test]
+    fn simple_position() {
+        let mut root = Node::new(Color::Black, 0.5, vec! [1.0; 362]);
+        let strategy = ComplexityAwareTimeControl::new(RolloutLimit::new(1000), 0);
+        visit(&mut root, 60, 150);
+
+        assert!(matches!(strategy.try_extend(&root), TimeStrategyResult::Expired));
+    }
+
+    #[test]
+    fn complex_position() {
+        let mut root = Node::new(Color::Black, 0.5, vec! [1.0; 362]);
+        let strategy = ComplexityAwareTimeControl::new(RolloutLimit::new(150), 0);
+
+        for i in 0..10 {
+            visit(&mut root, 60 + i, 15);
+        }
+
+        assert!(matches!(strategy.try_extend(&root), TimeStrategyResult::Extended));
+        assert!(matches!(strategy.try_extend(&root), TimeStrategyResult::NotExpired(75)));
+    }
+}
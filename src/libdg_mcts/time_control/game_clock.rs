@@ -0,0 +1,237 @@
+// Copyright 2021 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use super::{TimeStrategy, TimeStrategyResult};
+use tree;
+use dg_go::Board;
+use dg_utils::config::SAFE_TIME_MS;
+
+use std::time::{Duration, Instant};
+
+/// The average game length is 257 moves as suggested by _Andries E. Brouwer_:
+///
+/// https://homepages.cwi.nl/~aeb/go/misc/gostat.html
+const AVERAGE_GAME_LENGTH: usize = 257;
+
+/// The minimum number of moves (for both players) that we assume are left in
+/// the game, so that we never spend the entire clock on a single move.
+const MIN_REMAINING_MOVES: usize = 40;
+
+/// The state of the clock for one player over the entire game, which is used
+/// to derive how much time each individual move should be given.
+#[derive(Clone)]
+pub struct GameClock {
+    /// The remaining main time in seconds.
+    main_time: f32,
+
+    /// The time in seconds that is added to the clock after each move.
+    increment: f32,
+
+    /// The number of moves (for both players) that has been played so far.
+    moves_played: usize,
+
+    /// The current board position.
+    board: Board
+}
+
+impl GameClock {
+    /// Returns a clock with the given remaining time.
+    ///
+    /// # Arguments
+    ///
+    /// * `main_time` - the remaining main time in seconds
+    /// * `increment` - the time in seconds that is added after each move
+    /// * `moves_played` - the number of moves played so far
+    /// * `board` - the current board position
+    ///
+    pub fn new(main_time: f32, increment: f32, moves_played: usize, board: &Board) -> GameClock {
+        GameClock {
+            main_time: main_time.max(0.0),
+            increment: increment.max(0.0),
+            moves_played,
+            board: board.clone()
+        }
+    }
+
+    /// Returns the clock after a move that took `elapsed` wall-clock time
+    /// was played, resulting in the given board position.
+    ///
+    /// # Arguments
+    ///
+    /// * `elapsed` - the time spent on the move
+    /// * `board` - the current board position
+    ///
+    pub fn after_move(&self, elapsed: Duration, board: &Board) -> GameClock {
+        let main_time = self.main_time - elapsed.as_secs_f32() + self.increment;
+
+        GameClock::new(main_time, self.increment, board.count(), board)
+    }
+
+    pub fn main_time(&self) -> f32 {
+        self.main_time
+    }
+
+    pub fn increment(&self) -> f32 {
+        self.increment
+    }
+
+    pub fn moves_played(&self) -> usize {
+        self.moves_played
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    /// Returns an estimate of the number of moves that the player whose clock
+    /// this is still has to play. This is the smallest of the remaining moves
+    /// in an average game and the number of vacant points on the board, since
+    /// a game rarely lasts longer than it takes to fill the board.
+    pub fn expected_remaining_moves(&self) -> usize {
+        let num_vacant = self.board.empty_count();
+        let remaining = AVERAGE_GAME_LENGTH.saturating_sub(self.moves_played).min(num_vacant);
+
+        (remaining.max(MIN_REMAINING_MOVES) + 1) / 2
+    }
+
+    /// Returns the amount of time that the next move should be given, which
+    /// is an even share of the remaining main time plus the increment. This
+    /// never exceeds the remaining main time (minus `SAFE_TIME_MS`).
+    pub fn budget(&self) -> Duration {
+        let main_time_ms = 1000.0 * self.main_time;
+        let available_ms = (main_time_ms as usize).saturating_sub(*SAFE_TIME_MS) as f32;
+        let budget_ms = available_ms / self.expected_remaining_moves() as f32 + 1000.0 * self.increment;
+
+        Duration::from_millis(budget_ms.min(available_ms) as u64)
+    }
+}
+
+/// An implementation of `TimeStrategy` that stops the search once the budget
+/// of the given `GameClock` has been spent.
+pub struct SuddenDeath {
+    /// The clock to derive the budget from.
+    clock: GameClock,
+
+    /// The number of visits the tree had in the beginning
+    starting_visits: i32,
+
+    /// The start time of this search.
+    start_time: Instant
+}
+
+impl SuddenDeath {
+    pub fn new(clock: GameClock, starting_visits: i32) -> SuddenDeath {
+        SuddenDeath {
+            clock,
+            starting_visits,
+            start_time: Instant::now()
+        }
+    }
+
+    pub fn clock(&self) -> &GameClock {
+        &self.clock
+    }
+}
+
+impl TimeStrategy for SuddenDeath {
+    fn try_extend(&self, root: &tree::Node) -> TimeStrategyResult {
+        let elapsed = self.start_time.elapsed();
+        let budget = self.clock.budget();
+
+        if elapsed >= budget {
+            TimeStrategyResult::Expired
+        } else {
+            // estimate the number of remaining rollouts by checking how fast
+            // they have been so far.
+            let total_visits = root.total_count - self.starting_visits;
+            let elapsed_ms = elapsed.as_secs_f32() * 1000.0;
+
+            TimeStrategyResult::NotExpired(if total_visits < 5 || elapsed_ms < 1.0 {
+                usize::MAX  // unknown
+            } else {
+                let rate = total_visits as f32 / elapsed_ms;
+                let remaining_ms = (budget - elapsed).as_secs_f32() * 1000.0;
+
+                (rate * remaining_ms) as usize
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dg_go::{Color, Point};
+    use super::*;
+
+    #[test]
+    fn remaining_moves() {
+        let mut board = Board::new(7.5);
+        let opening = GameClock::new(600.0, 0.0, 0, &board);
+
+        assert_eq!(opening.expected_remaining_moves(), 129);
+
+        for &(x, y) in &[(3, 3), (15, 15), (3, 15), (15, 3)] {
+            board.place(Color::Black, Point::new(x, y));
+        }
+
+        let late = GameClock::new(600.0, 0.0, 250, &board);
+
+        assert_eq!(late.expected_remaining_moves(), MIN_REMAINING_MOVES / 2);
+    }
+
+    #[test]
+    fn budget_spreads_main_time() {
+        let board = Board::new(7.5);
+        let clock = GameClock::new(600.0, 0.0, 0, &board);
+        let budget = clock.budget();
+
+        assert!(budget > Duration::from_secs(1));
+        assert!(budget < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn budget_includes_increment() {
+        let board = Board::new(7.5);
+        let without = GameClock::new(600.0, 0.0, 0, &board).budget();
+        let with = GameClock::new(600.0, 5.0, 0, &board).budget();
+
+        assert!(with > without);
+    }
+
+    #[test]
+    fn after_move_spends_time() {
+        let mut board = Board::new(7.5);
+        let clock = GameClock::new(10.0, 1.0, 0, &board);
+
+        board.place(Color::Black, Point::new(3, 3));
+
+        let next = clock.after_move(Duration::from_secs(3), &board);
+
+        assert_eq!(next.main_time(), 8.0);
+        assert_eq!(next.moves_played(), 1);
+
+        let expired = next.after_move(Duration::from_secs(60), &board);
+
+        assert_eq!(expired.main_time(), 0.0);
+    }
+
+    #[test]
+    fn budget_never_exceeds_main_time() {
+        let board = Board::new(7.5);
+        let clock = GameClock::new(1.0, 30.0, 0, &board);
+
+        assert!(clock.budget() < Duration::from_secs(1));
+    }
+}
@@ -13,9 +13,13 @@
 // limitations under the License.
 
 mod byo_yomi;
+mod complexity;
+mod game_clock;
 mod rollout_limit;
 
 pub use self::byo_yomi::*;
+pub use self::complexity::*;
+pub use self::game_clock::*;
 pub use self::rollout_limit::*;
 
 use tree;
@@ -77,18 +81,30 @@ fn min_promote_rollouts(root: &tree::Node) -> usize {
 /// * `EARLY-C` terminate the search early if the second most visited node
 ///   cannot catch up to the most visited node in the remaining time.
 ///
+/// `EARLY-C` never terminates the search before the most visited child has
+/// at least `min_visits` visits, see `SearchOptions::min_visits_for_selection`.
+///
 /// [1] _Hendrik Baier_ and _Mark H.M. Winands_, "Time Management for
 ///     Monte-Carlo Tree Search in Go", https://pdfs.semanticscholar.org/a2e6/299fd3c8ab17e3a1a783d518688b55bb2363.pdf
 ///
-pub fn is_done(root: &tree::Node, ticket: &Box<dyn TimeStrategy + Sync>) -> bool {
+/// # Arguments
+///
+/// * `root` - the root of the search tree
+/// * `ticket` - the time strategy of the search
+/// * `min_visits` - the minimum number of visits of the most visited child
+///   before the search may be terminated early
+///
+pub fn is_done(root: &tree::Node, ticket: &Box<dyn TimeStrategy + Sync>, min_visits: i32) -> bool {
     if root.total_count == 0 {
         false
     } else {
         match ticket.try_extend(root) {
             TimeStrategyResult::NotExpired(remaining) => {
+                let top_1 = root.children.argmax_count();
+                let count_1 = root.children.with(top_1, |child| child.count(), root.initial_value);
                 let min_promote = min_promote_rollouts(root);
 
-                min_promote > remaining
+                count_1 >= min_visits && min_promote > remaining
             },
             TimeStrategyResult::Extended => false,
             _ => true
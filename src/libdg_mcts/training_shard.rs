@@ -0,0 +1,302 @@
+// Copyright 2021 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use dg_go::utils::features::{self, Features, HWC};
+use dg_go::utils::sgf::Sgf;
+use dg_go::utils::symmetry::Transform;
+use dg_go::{Color, Point};
+use dg_utils::b85;
+use dg_utils::types::f16;
+use super::GameResult;
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+const SHARD_MAGIC: &[u8; 4] = b"DGTS";
+const SHARD_VERSION: u32 = 1;
+
+/// The size, in bytes, of the header at the start of each shard.
+pub const SHARD_HEADER_SIZE: usize = 16;
+
+/// Returns the size, in bytes, of a single record in a shard.
+pub const fn shard_record_size() -> usize {
+    2 * features::Default::size() + 2 * 362 + 4 + 4
+}
+
+/// A writer that appends the training positions of finished games to a single
+/// shard of fixed-size records. A shard should only be written to by a single
+/// writer at a time, so concurrent self-play workers should each open their
+/// own shard, e.g. using `shard_path`.
+///
+/// Each shard starts with the following header, all integers are little
+/// endian:
+///
+/// - `4 x u8` the magic bytes `DGTS`
+/// - `u32` the version of the format
+/// - `u32` the number of features, which identifies the feature set
+/// - `u32` the size of each record in bytes
+///
+/// Which is followed by any number of records, where each record is:
+///
+/// - `features::Default::size() x u16` the `HWC` features of the position
+///   (as `f16` bits), from the perspective of the player to move
+/// - `362 x u16` the policy target (as `f16` bits)
+/// - `f32` the value target, `1` if the player to move won, `-1` if they lost
+///   and `0` for a draw
+/// - `f32` the final score from the perspective of the player to move, or
+///   `NaN` if the game ended by resignation
+///
+pub struct ShardWriter {
+    file: File
+}
+
+impl ShardWriter {
+    /// Returns a writer that appends to the shard at the given path. If the
+    /// shard does not exist, or is empty, then it is created with a new
+    /// header. Otherwise the existing header is verified to match the
+    /// current format.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - the path of the shard
+    ///
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<ShardWriter> {
+        let mut file = OpenOptions::new().read(true).append(true).create(true).open(path)?;
+
+        if file.metadata()?.len() == 0 {
+            file.write_all(&shard_header())?;
+        } else {
+            let mut header = [0; SHARD_HEADER_SIZE];
+            file.read_exact(&mut header)?;
+
+            if header != shard_header() {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "shard header does not match the current format"));
+            }
+        }
+
+        Ok(ShardWriter { file })
+    }
+
+    /// Appends every position of the given game to this shard, and returns
+    /// the number of records that were written. The records of a game are
+    /// written using a single call, so that an interrupted write truncates at
+    /// most the game that was being written.
+    ///
+    /// # Arguments
+    ///
+    /// * `result` - the game to append
+    ///
+    pub fn write(&mut self, result: &GameResult) -> io::Result<usize> {
+        let mut buf = vec! [];
+        let num_records = write_records(result, &mut buf)?;
+
+        self.file.write_all(&buf)?;
+        self.file.flush()?;
+
+        Ok(num_records)
+    }
+}
+
+/// Returns the path of the shard with the given index in `directory`, which
+/// can be used to give each self-play worker its own shard.
+///
+/// # Arguments
+///
+/// * `directory` - the directory that contains the shards
+/// * `index` - the index of the shard
+///
+pub fn shard_path<P: AsRef<Path>>(directory: P, index: usize) -> PathBuf {
+    directory.as_ref().join(format!("shard-{:05}.bin", index))
+}
+
+/// Returns the header of a shard in the current format.
+fn shard_header() -> [u8; SHARD_HEADER_SIZE] {
+    let mut header = [0; SHARD_HEADER_SIZE];
+
+    header[0..4].copy_from_slice(SHARD_MAGIC);
+    header[4..8].copy_from_slice(&SHARD_VERSION.to_le_bytes());
+    header[8..12].copy_from_slice(&(features::Default::num_features() as u32).to_le_bytes());
+    header[12..16].copy_from_slice(&(shard_record_size() as u32).to_le_bytes());
+    header
+}
+
+/// Returns the policy target of the given move, which is its `P` property
+/// if present and otherwise all probability on the move that was played.
+///
+/// # Arguments
+///
+/// * `policy` - the `P` property of the move, if any
+/// * `point` - the move that was played
+///
+fn get_policy_target(policy: Option<&[u8]>, point: Point) -> Vec<f32> {
+    let policy = policy
+        .and_then(|value| b85::decode::<f16, f32>(value))
+        .filter(|policy| policy.len() >= 362);
+
+    match policy {
+        Some(policy) => policy,
+        None => {
+            let mut policy = vec! [0.0; 362];
+            policy[point.to_packed_index()] = 1.0;
+            policy
+        }
+    }
+}
+
+/// Writes the records of every position in the given game to `writer`, and
+/// returns the number of records written. The game record is replayed using
+/// `Sgf`, starting from its setup position (if any) with the komi of the
+/// final board, and replaying stops at the first illegal or unparseable move.
+///
+/// # Arguments
+///
+/// * `result` - the game to write
+/// * `writer` - the writer to write the records to
+///
+fn write_records<W: Write>(result: &GameResult, writer: &mut W) -> io::Result<usize> {
+    let (sgf, final_board) = match *result {
+        GameResult::Resign(ref sgf, ref board, _, _) => (sgf, board),
+        GameResult::Ended(ref sgf, ref board) => (sgf, board),
+        GameResult::Capped(ref sgf, ref board) => (sgf, board)
+    };
+    let winner = result.winner();
+    let score = result.score();
+    let mut num_records = 0;
+
+    for entry in Sgf::new(sgf.as_bytes(), final_board.komi()) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(_) => break
+        };
+        let to_move = entry.color;
+        let features = features::Default::new(&entry.board).get_features::<HWC, f16>(to_move, Transform::Identity);
+        let value: f32 = match winner {
+            Some(color) if color == to_move => 1.0,
+            Some(_) => -1.0,
+            None => 0.0
+        };
+        let score = match score {
+            Some(score) if to_move == Color::Black => score,
+            Some(score) => -score,
+            None => f32::NAN
+        };
+
+        for &x in &features {
+            writer.write_all(&x.to_bits().to_le_bytes())?;
+        }
+        for x in get_policy_target(entry.policy, entry.point).into_iter().take(362) {
+            writer.write_all(&f16::from(x).to_bits().to_le_bytes())?;
+        }
+        writer.write_all(&value.to_le_bytes())?;
+        writer.write_all(&score.to_le_bytes())?;
+        num_records += 1;
+    }
+
+    Ok(num_records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dg_go::Board;
+    use std::env;
+    use std::fs;
+
+    fn read_f32(buf: &[u8], offset: usize) -> f32 {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(&buf[offset..offset+4]);
+        f32::from_le_bytes(bytes)
+    }
+
+    #[test]
+    fn policy_target_after_comment() {
+        let mut policy = vec! [0.0; 362];
+        policy[Point::new(15, 15).to_packed_index()] = 1.0;
+
+        let sgf = format!(";B[dd]C[a \\] b]TR[pp]TV[2]P[{}]V[0.5];W[pp]", b85::encode(&policy));
+        let result = GameResult::Ended(sgf, Board::new(7.5));
+        let mut buf = vec! [];
+
+        assert_eq!(write_records(&result, &mut buf).unwrap(), 2);
+
+        let offset = 2 * features::Default::size() + 2 * Point::new(15, 15).to_packed_index();
+
+        assert_eq!(f16::from_bits(u16::from_le_bytes([buf[offset], buf[offset + 1]])), f16::from(1.0));
+    }
+
+    #[test]
+    fn records_from_setup() {
+        let result = GameResult::Ended("AB[aa]PL[W];W[pp]".to_string(), Board::new(7.5));
+        let mut buf = vec! [];
+
+        assert_eq!(write_records(&result, &mut buf).unwrap(), 1);
+
+        let mut board = Board::new(7.5);
+        board.add_setup_stone(Color::Black, Point::new(0, 0));
+        board.set_to_move(Color::White);
+        let expected = features::Default::new(&board).get_features::<HWC, f16>(Color::White, Transform::Identity);
+
+        assert!(expected.iter().enumerate().all(|(i, &x)| {
+            x.to_bits() == u16::from_le_bytes([buf[2 * i], buf[2 * i + 1]])
+        }));
+    }
+
+    #[test]
+    fn records() {
+        let result = GameResult::Ended(";B[dd]C[test];W[pp];B[]".to_string(), Board::new(7.5));
+        let mut buf = vec! [];
+
+        assert_eq!(write_records(&result, &mut buf).unwrap(), 3);
+        assert_eq!(buf.len(), 3 * shard_record_size());
+
+        // white wins by komi, so black has a value target of `-1` and a score
+        // of `-7.5`.
+        let tail = shard_record_size() - 8;
+
+        assert_eq!(read_f32(&buf, tail), -1.0);
+        assert_eq!(read_f32(&buf, tail + 4), -7.5);
+        assert_eq!(read_f32(&buf, shard_record_size() + tail), 1.0);
+        assert_eq!(read_f32(&buf, shard_record_size() + tail + 4), 7.5);
+    }
+
+    #[test]
+    fn append_to_shard() {
+        let directory = env::temp_dir();
+        let path = shard_path(&directory, ::std::process::id() as usize);
+        let result = GameResult::Resign(";B[dd];W[pp]".to_string(), Board::new(7.5), Color::White, 0.0);
+
+        for _ in 0..2 {
+            let mut writer = ShardWriter::open(&path).expect("could not open shard");
+            assert_eq!(writer.write(&result).expect("could not write to shard"), 2);
+        }
+
+        let len = fs::metadata(&path).map(|m| m.len() as usize).unwrap_or(0);
+        fs::remove_file(&path).expect("could not remove shard");
+
+        assert_eq!(len, SHARD_HEADER_SIZE + 4 * shard_record_size());
+    }
+
+    #[test]
+    fn reject_unknown_header() {
+        let path = env::temp_dir().join(format!("shard-invalid-{}.bin", ::std::process::id()));
+        fs::write(&path, b"not a shard, but long enough").expect("could not write file");
+
+        let err = ShardWriter::open(&path).err().map(|e| e.kind());
+        fs::remove_file(&path).expect("could not remove file");
+
+        assert_eq!(err, Some(io::ErrorKind::InvalidData));
+    }
+}
@@ -17,15 +17,16 @@ use dg_go::{Board, Color, Point};
 use dg_utils::lcb::normal_lcb_m;
 use dg_utils::config;
 use super::asm::{argmax_f32, argmax_i32};
-use super::choose::choose;
+use super::choose::{argmax_stable, choose_with_rng};
+use super::options::TieBreak;
 use super::parallel::spin::Mutex;
 use super::parallel::global_rwlock;
 
 use ordered_float::OrderedFloat;
-use rand::{thread_rng, Rng};
-use std::cmp::Ordering;
+use rand::thread_rng;
+use std::cmp::{Ordering, Reverse};
 use std::fmt;
-use std::mem::ManuallyDrop;
+use std::mem::{self, ManuallyDrop};
 use std::intrinsics::{atomic_xadd, atomic_xsub, atomic_cxchg};
 use std::ptr;
 
@@ -1043,6 +1044,10 @@ pub struct Node {
     /// The total number of virtual losses for any edge.
     pub vtotal_count: i32,
 
+    /// The number of nodes in this search tree, including this node. This is
+    /// updated by `insert` so that it does not need to be counted.
+    pub subtree_size: i32,
+
     /// The prior value of each edge as indicated by the policy.
     pub prior: [f32; 368],
 
@@ -1082,6 +1087,7 @@ impl Node {
             pass_count: 0,
             total_count: 0,
             vtotal_count: 0,
+            subtree_size: 1,
             prior: prior_padding,
             children: ChildrenImpl::Small(ManuallyDrop::new(SmallChildrenImpl::with_value(value)))
         }
@@ -1111,11 +1117,38 @@ impl Node {
         (0..362).any(|i| self.is_valid_candidate(board, i))
     }
 
-    /// Returns the total size of this search tree.
-    pub fn size(&self) -> usize {
+    /// Returns the number of visits to the root of this search tree. See
+    /// `size` for the number of nodes.
+    pub fn visit_count(&self) -> usize {
         self.total_count as usize
     }
 
+    /// Returns an estimate of the number of bytes of memory that this search
+    /// tree uses, including this node.
+    pub fn memory_bytes(&self) -> usize {
+        let mut total = mem::size_of::<Node>();
+
+        if let ChildrenImpl::Big(_) = self.children {
+            total += mem::size_of::<BigChildrenImpl>();
+        }
+
+        for i in self.children.nonzero() {
+            let child = self.with(i, |child| child.ptr());
+
+            if !child.is_null() {
+                total += unsafe { (*child).memory_bytes() };
+            }
+        }
+
+        total
+    }
+
+    /// Returns the total size of this search tree, as the number of nodes in
+    /// it including this node.
+    pub fn size(&self) -> usize {
+        self.subtree_size as usize
+    }
+
     /// Returns the result of the given callback, and being called with an immutable
     /// reference for the child for index.
     ///
@@ -1258,12 +1291,18 @@ impl Node {
     /// * `temperature` - How random the process should be, if set to +Inf
     ///   then the values are picked completely at random, and if set to 0
     ///   the selection is greedy.
+    /// * `tie_break` - how to break ties between children with the same
+    ///   number of visits, see `SearchOptions::tiebreak`
     ///
-    pub fn best(&self, temperature: f32) -> (f32, usize) {
+    pub fn best(&self, temperature: f32, tie_break: TieBreak) -> (f32, usize) {
         if temperature <= 9e-2 { // greedy
-            let max_i = self.children.nonzero()
-                .max_by(|&a, &b| compare_children(self, a, b, MIN_LCB_VISITS))
-                .unwrap_or(361);
+            let mut candidates = self.children.nonzero().collect::<Vec<_>>();
+            candidates.sort_unstable();
+
+            let max_i = argmax_stable(
+                &candidates.iter().map(|&i| best_weight(self, i, MIN_LCB_VISITS)).collect::<Vec<_>>(),
+                &candidates.iter().map(|&i| best_tiebreak_key(self, i, tie_break)).collect::<Vec<_>>()
+            ).map(|j| candidates[j]).unwrap_or(361);
 
             (self.with(max_i, |child| child.value()), max_i)
         } else {
@@ -1271,9 +1310,8 @@ impl Node {
                 .map(|i| self.with(i, |child| child.count()))
                 .collect::<Vec<i32>>();
             let temperature = (temperature as f64).recip();
-            let at = thread_rng().gen::<f64>();
 
-            if let Some((i, _)) = choose(&visits, 0.5, temperature, at) {
+            if let Some((i, _)) = choose_with_rng(&visits, 0.5, temperature, &mut thread_rng()) {
                 (self.with(i, |child| child.value()), i)
             } else {
                 (self.initial_value, 361)  // no valid moves
@@ -1281,6 +1319,35 @@ impl Node {
         }
     }
 
+    /// Returns the best move according to `best`, unless no child has at least
+    /// `min_visits` visits, in which case the search is not trusted and the
+    /// valid candidate move with the largest prior value is returned instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `temperature` - the temperature to pass to `best`
+    /// * `min_visits` - the minimum number of visits of the most visited
+    ///   child, or `0` to always use `best`
+    /// * `tie_break` - the tie-breaking rule to pass to `best`
+    ///
+    pub fn best_with_min_visits(&self, temperature: f32, min_visits: i32, tie_break: TieBreak) -> (f32, usize) {
+        let max_count = self.children.nonzero()
+            .map(|i| self.with(i, |child| child.count()))
+            .max()
+            .unwrap_or(0);
+
+        if max_count >= min_visits {
+            return self.best(temperature, tie_break);
+        }
+
+        let max_i = (0..362)
+            .filter(|&i| self.prior[i].is_finite() && self.with(i, |child| child.value()).is_finite())
+            .max_by_key(|&i| OrderedFloat(self.prior[i]))
+            .unwrap_or(361);
+
+        (self.with(max_i, |child| child.value()), max_i)
+    }
+
     /// Returns the best move according to the prior value of the root node.
     pub fn prior(&self) -> (f32, usize) {
         let max_i = argmax_f32(&self.prior).unwrap_or(361);
@@ -1291,15 +1358,40 @@ impl Node {
     /// Returns a vector containing the _correct_ normalized probability that each move
     /// should be played given the current search tree.
     pub fn softmax<T: From<f32> + Clone>(&self) -> Vec<T> {
+        self.visit_target(1.0)
+    }
+
+    /// Returns a vector containing the normalized probability that each move
+    /// should be played, where the visit count of each move has been raised
+    /// to the power of `1 / temperature` before normalization. A temperature
+    /// of `1.0` is the same as `softmax`.
+    ///
+    /// # Arguments
+    ///
+    /// * `temperature` - the temperature of the distribution
+    ///
+    pub fn visit_target<T: From<f32> + Clone>(&self, temperature: f32) -> Vec<T> {
+        let max_count = self.children.nonzero()
+            .map(|i| self.with(i, |child| child.count()))
+            .max()
+            .unwrap_or(0) as f32;
+        let weight = |count: i32| {
+            if temperature == 1.0 {
+                count as f32
+            } else {
+                (count as f32 / max_count).powf(temperature.recip())
+            }
+        };
+
         let mut s = vec! [T::from(0.0f32); 362];
         let mut s_total = 0.0f32;
 
         for i in self.children.nonzero() {
-            s_total += self.with(i, |child| child.count()) as f32;
+            s_total += weight(self.with(i, |child| child.count()));
         }
 
         for i in self.children.nonzero() {
-            s[i] = T::from(self.with(i, |child| child.count()) as f32 / s_total);
+            s[i] = T::from(weight(self.with(i, |child| child.count())) / s_total);
         }
 
         s
@@ -1319,14 +1411,91 @@ impl Node {
         });
     }
 
+    /// Merge the statistics of `other`, which must be an independently searched
+    /// tree of the same position, into this tree. The visit counts and values
+    /// of each child are combined recursively for every sub-tree that both
+    /// trees have expanded, any sub-tree that only exists in `other` is not
+    /// copied but its statistics are still added to the parent edge.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - the tree to merge into this tree
+    ///
+    pub fn merge(&mut self, other: &Node) {
+        assert_eq!(self.to_move, other.to_move);
+
+        for i in other.children.nonzero() {
+            let (other_count, other_value, other_value_s, other_ptr) = other.with(i, |child| {
+                (child.count(), child.value(), child.value_s, child.ptr())
+            });
+
+            let ptr = self.with_mut(i, |mut child| {
+                let count = child.count();
+                let value = child.value();
+
+                if !value.is_finite() && count == 0 {
+                    return ptr::null_mut();  // disqualified
+                }
+
+                // combine the mean and variance using the parallel algorithm
+                // by _Chan et al._
+                let total = count + other_count;
+                let delta = other_value - value;
+
+                child.set_count(total);
+                child.set_value(value + delta * (other_count as f32 / total as f32));
+                child.set_value_s(
+                    child.value_s() + other_value_s + delta * delta * (count as f32 * other_count as f32 / total as f32)
+                );
+                child.ptr()
+            });
+
+            if !ptr.is_null() && !other_ptr.is_null() {
+                unsafe { (*ptr).merge(&*other_ptr) };
+            }
+        }
+
+        self.total_count += other.total_count;
+    }
+
+    /// Returns the valid candidate with the largest number of visits missing to
+    /// reach `sqrt(k * prior * total_count)`, if any candidate has fewer visits
+    /// than that.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - the UCT value of each child
+    /// * `min_exploration_visits` - the scale `k` of the minimum number of visits
+    ///
+    fn get_forced_child(&self, value: &[f32], min_exploration_visits: f32) -> Option<usize> {
+        let total_count = self.total_count as f32;
+
+        (0..362)
+            .filter(|&i| value[i].is_finite() && self.prior[i] > 0.0)
+            .map(|i| {
+                let min_count = (min_exploration_visits * self.prior[i] * total_count).sqrt();
+                let count = self.with(i, |child| child.count() + child.vcount()) as f32;
+
+                (i, min_count - count)
+            })
+            .filter(|&(_, deficit)| deficit > 0.0)
+            .max_by_key(|&(_, deficit)| OrderedFloat(deficit))
+            .map(|(i, _)| i)
+    }
+
     /// Returns the child with the maximum UCT value, and increase its visit count
     /// by one.
     ///
     /// # Arguments
     ///
     /// * `apply_fpu` - whether to use the first-play urgency heuristic
+    /// * `min_exploration_visits` - the scale of the minimum number of visits
+    ///   each candidate must receive, or `0.0` to always pick the maximum UCT
+    ///   value
+    /// * `utility` - an additional utility of each edge that is added to its
+    ///   value, see `SearchOptions::score_utility_weight`
     ///
-    fn select(&mut self, apply_fpu: bool) -> ProbeResult<(usize, f32)> {
+    fn select(&mut self, apply_fpu: bool, min_exploration_visits: f32, utility: Option<&[f32; 368]>) -> ProbeResult<(usize, f32)> {
         let mut value = self.children.value(self.initial_value);
 
         if apply_fpu {
@@ -1347,6 +1516,12 @@ impl Node {
             }
         }
 
+        if let Some(utility) = utility {
+            for i in 0..362 {
+                value[i] += utility[i];
+            }
+        }
+
         // compute all UCB1 values for each node before trying to figure out which
         // to pick to make it possible to do it with SIMD.
         for i in 362..368 {
@@ -1358,7 +1533,11 @@ impl Node {
         // greedy selection based on the maximum ucb1 value, failing if someone else
         // is already expanding the node we want to expand.
         let initial_value = self.initial_value;
-        let max_i = argmax_f32(&value);
+        let max_i = if min_exploration_visits > 0.0 {
+            self.get_forced_child(&value, min_exploration_visits).or_else(|| argmax_f32(&value))
+        } else {
+            argmax_f32(&value)
+        };
         let max_i =
             if let Some(i) = max_i {
                 self.children.with_mut(i, |mut child| {
@@ -1419,13 +1598,38 @@ pub unsafe fn undo(trace: NodeTrace, undo_expanding: bool) {
 /// * `board` - the board to update with the traversed moves
 ///
 pub unsafe fn probe(root: &mut Node, board: &mut Board) -> ProbeResult<NodeTrace> {
+    probe_with(root, board, 0.0, None)
+}
+
+/// Probe down the search tree in the same way as `probe`, but make sure that
+/// every candidate move at the root receives a minimum number of visits, as
+/// described by `SearchOptions::min_exploration_visits`, and add the given
+/// utility to the value of each candidate move at the root.
+///
+/// # Arguments
+///
+/// * `root` - the search tree to probe into
+/// * `board` - the board to update with the traversed moves
+/// * `min_exploration_visits` - the scale of the minimum number of visits
+/// * `root_utility` - the additional utility of each candidate move at the
+///   root, see `SearchOptions::score_utility_weight`
+///
+pub unsafe fn probe_with(
+    root: &mut Node,
+    board: &mut Board,
+    min_exploration_visits: f32,
+    root_utility: Option<&[f32; 368]>
+) -> ProbeResult<NodeTrace>
+{
     let mut trace = Vec::with_capacity(16);
     let mut current = root;
 
     loop {
         let apply_fpu = !trace.is_empty();
+        let min_exploration_visits = if apply_fpu { 0.0 } else { min_exploration_visits };
+        let utility = if apply_fpu { None } else { root_utility };
 
-        match current.select(apply_fpu) {
+        match current.select(apply_fpu, min_exploration_visits, utility) {
             ProbeResult::Conflict => {
                 undo(trace, false);
                 return ProbeResult::Conflict;
@@ -1446,12 +1650,19 @@ pub unsafe fn probe(root: &mut Node, board: &mut Board) -> ProbeResult<NodeTrace
                         current.to_move,
                         point,
                         next_value,
-                        ToPretty { root: current, verbose: true }
+                        ToPretty { root: current, verbose: true, tie_break: TieBreak::default() }
                     );
 
                     board.place(current.to_move, point);
-                } else if current.pass_count >= 1 {
-                    break;  // at least two consecutive passes
+                } else {
+                    // a passing move does not change the stones, but it is
+                    // still part of the history, and the other players turn
+                    // afterwards
+                    board.pass(current.to_move);
+
+                    if current.pass_count >= 1 {
+                        break;  // at least two consecutive passes
+                    }
                 }
 
                 //
@@ -1497,7 +1708,11 @@ pub unsafe fn insert(trace: &NodeTrace, color: Color, value: f32, prior: Vec<f32
             }
         });
 
-        if !updated {
+        if updated {
+            for &(node, _, _) in trace.iter() {
+                atomic_xadd(&mut (*node).subtree_size, 1);
+            }
+        } else {
             debug_assert!(index == 361);
 
             // since we stop probing into a tree once two consecutive passes has
@@ -1509,10 +1724,35 @@ pub unsafe fn insert(trace: &NodeTrace, color: Color, value: f32, prior: Vec<f32
     UCT::update(trace, color, value);
 }
 
+/// Perform the backup pass updating the average values of all nodes in the
+/// given trace, without inserting a new node at the end of it. The last edge
+/// of the trace can be expanded again by a later probe.
+///
+/// # Arguments
+///
+/// * `trace` -
+/// * `color` -
+/// * `value` -
+///
+pub unsafe fn backup(trace: &NodeTrace, color: Color, value: f32) {
+    debug_assert!(value >= 0.0 && value <= 1.0);
+
+    if let Some(&(node, _, index)) = trace.last() {
+        (*node).with_mut(index, |mut child| {
+            if child.ptr().is_null() {
+                child.unset_expanding();
+            }
+        });
+    }
+
+    UCT::update(trace, color, value);
+}
+
 /// Compare two children of an MCTS node such that the better candiate is bigger
-/// than a worse candidate. The algorithm will compare the LCB if both
-/// candidates has at least `min_lcb_visits` visit counts, otherwise fallback to
-/// comparing the visit count.
+/// than a worse candidate, using the same ordering as `Node::best`. That is by
+/// their `best_weight`, then by their `best_tiebreak_key`, and then by the
+/// smallest index. Since no two different children compare as equal, the best
+/// child does not depend on the order in which the children are visited.
 ///
 /// # Arguments
 ///
@@ -1520,41 +1760,65 @@ pub unsafe fn insert(trace: &NodeTrace, color: Color, value: f32, prior: Vec<f32
 /// * `a` -
 /// * `b` -
 /// * `min_lcb_visits` -
+/// * `tie_break` -
 ///
 fn compare_children(
     node: &Node,
     a: usize,
     b: usize,
-    min_lcb_visits: i32
+    min_lcb_visits: i32,
+    tie_break: TieBreak
 ) -> Ordering
 {
-    let a_count = node.with(a, |a| a.count());
-    let b_count = node.with(b, |b| b.count());
+    let key = |index: usize| {
+        (best_weight(node, index, min_lcb_visits), best_tiebreak_key(node, index, tie_break), Reverse(index))
+    };
 
-    if a_count >= min_lcb_visits && b_count >= min_lcb_visits {
-        let a_lcb = node.with(a, |a| normal_lcb_m(a.value(), a.value_std(), a.count(), node.total_count));
-        let b_lcb = node.with(b, |b| normal_lcb_m(b.value(), b.value_std(), b.count(), node.total_count));
+    key(a).cmp(&key(b))
+}
 
-        if a_lcb != b_lcb {
-            return OrderedFloat(a_lcb).cmp(&OrderedFloat(b_lcb));
-        }
-    }
+/// Returns the weight of the child at the given index when picking the best
+/// move, before any tie-breaking. Children with at least `min_lcb_visits`
+/// visits are compared by their lower confidence bound and then their visits,
+/// and all other children by only their visits.
+///
+/// # Arguments
+///
+/// * `node` -
+/// * `index` -
+/// * `min_lcb_visits` -
+///
+fn best_weight(node: &Node, index: usize, min_lcb_visits: i32) -> (OrderedFloat<f32>, i32) {
+    let count = node.with(index, |child| child.count());
+    let lcb =
+        if count >= min_lcb_visits {
+            node.with(index, |child| normal_lcb_m(child.value(), child.value_std(), child.count(), node.total_count))
+        } else {
+            ::std::f32::NEG_INFINITY
+        };
 
-    if a_count != b_count {
-        return a_count.cmp(&b_count);
-    }
+    (OrderedFloat(lcb), count)
+}
 
-    let a_prior = node.prior[a];
-    let b_prior = node.prior[b];
+/// Returns the key to break ties between children with the same `best_weight`
+/// with, according to the given tie-breaking rule. Ties between children with
+/// the same key are broken by the smallest index.
+///
+/// # Arguments
+///
+/// * `node` -
+/// * `index` -
+/// * `tie_break` -
+///
+fn best_tiebreak_key(node: &Node, index: usize, tie_break: TieBreak) -> (OrderedFloat<f32>, OrderedFloat<f32>) {
+    let prior = OrderedFloat(node.prior[index]);
+    let value = node.with(index, |child| OrderedFloat(child.value()));
 
-    if a_prior != b_prior {
-        return OrderedFloat(a_prior).cmp(&OrderedFloat(b_prior));
+    match tie_break {
+        TieBreak::Winrate => (value, prior),
+        TieBreak::Prior => (prior, value),
+        TieBreak::Index => (OrderedFloat(0.0), OrderedFloat(0.0))
     }
-
-    let a_value = node.with(a, |a| a.value());
-    let b_value = node.with(b, |b| b.value());
-
-    OrderedFloat(a_value).cmp(&OrderedFloat(b_value))
 }
 
 /// Type alias for `Node` that acts as a wrapper for calling `as_sgf` from
@@ -1617,8 +1881,8 @@ pub fn to_sgf<'a, S>(root: &'a Node, starting_point: &Board, meta: bool) -> ToSg
 }
 
 /// Type alias for pretty-printing an index based vertex.
-struct PrettyVertex {
-    inner: usize
+pub(crate) struct PrettyVertex {
+    pub(crate) inner: usize
 }
 
 impl fmt::Display for PrettyVertex {
@@ -1642,14 +1906,15 @@ impl fmt::Display for PrettyVertex {
 /// Iterator that traverse the most likely path down a search tree
 pub struct GreedyPath<'a> {
     current: &'a Node,
-    threshold: i32
+    threshold: i32,
+    tie_break: TieBreak
 }
 
 impl<'a> GreedyPath<'a> {
-    pub fn new(node: &'a Node, threshold: i32) -> GreedyPath<'a> {
+    pub fn new(node: &'a Node, threshold: i32, tie_break: TieBreak) -> GreedyPath<'a> {
         debug_assert!(threshold >= 1);
 
-        GreedyPath { current: node, threshold: threshold }
+        GreedyPath { current: node, threshold: threshold, tie_break: tie_break }
     }
 }
 
@@ -1658,7 +1923,7 @@ impl<'a> Iterator for GreedyPath<'a> {
 
     fn next(&mut self) -> Option<usize> {
         let max_i = self.current.children.nonzero()
-            .max_by(|&a, &b| compare_children(self.current, a, b, MIN_LCB_VISITS))
+            .max_by(|&a, &b| compare_children(self.current, a, b, MIN_LCB_VISITS, self.tie_break))
             .unwrap_or(361);
 
         if self.current.with(max_i, |child| child.count()) < self.threshold {
@@ -1677,13 +1942,14 @@ impl<'a> Iterator for GreedyPath<'a> {
 /// within a `write!` macro.
 pub struct ToPretty<'a> {
     root: &'a Node,
-    verbose: bool
+    verbose: bool,
+    tie_break: TieBreak
 }
 
 impl<'a> fmt::Display for ToPretty<'a> {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         let mut children = self.root.children.nonzero().collect::<Vec<usize>>();
-        children.sort_by(|&a, &b| compare_children(self.root, b, a, MIN_LCB_VISITS));
+        children.sort_by(|&a, &b| compare_children(self.root, b, a, MIN_LCB_VISITS, self.tie_break));
 
         if !self.verbose {
             children.truncate(10);
@@ -1695,7 +1961,7 @@ impl<'a> fmt::Display for ToPretty<'a> {
             .filter(|v| v.is_finite())
             .sum();
         let norm_value = total_value / (self.root.total_count as f32 + 1e-5);
-        let likely_path: String = GreedyPath::new(self.root, 1)
+        let likely_path: String = GreedyPath::new(self.root, 1, self.tie_break)
                 .map(|i| PrettyVertex { inner: i })
                 .map(|v| format!("{}", v))
                 .collect::<Vec<String>>().join(" ");
@@ -1710,7 +1976,7 @@ impl<'a> fmt::Display for ToPretty<'a> {
         for i in children {
             let pretty_vertex = PrettyVertex { inner: i };
             let child = unsafe { &*self.root.with(i, |child| child.ptr()) };
-            let likely_path: String = GreedyPath::new(child, 1)
+            let likely_path: String = GreedyPath::new(child, 1, self.tie_break)
                     .map(|i| PrettyVertex { inner: i })
                     .map(|v| format!("{}", v))
                     .collect::<Vec<String>>().join(" ");
@@ -1737,12 +2003,108 @@ impl<'a> fmt::Display for ToPretty<'a> {
 /// # Arguments
 ///
 /// * `root` -
-/// * `starting_point` -
+/// * `tie_break` - how to order children with the same number of visits
 ///
-pub fn to_pretty(root: &Node) -> ToPretty {
+pub fn to_pretty(root: &Node, tie_break: TieBreak) -> ToPretty {
     let verbose = *config::VERBOSE;
 
-    ToPretty { root, verbose }
+    ToPretty { root, verbose, tie_break }
+}
+
+/// The maximum number of children of each node to include in a DOT graph.
+const MAX_DOT_CHILDREN: usize = 5;
+
+/// Type alias for `Node` that acts as a wrapper for writing the top of the
+/// search tree as a GraphViz DOT graph from within a `write!` macro.
+pub struct ToDot<'a> {
+    root: &'a Node,
+    max_depth: usize,
+    min_visits: i32,
+    tie_break: TieBreak
+}
+
+impl<'a> ToDot<'a> {
+    /// Write the children of the given node that has at least `min_visits`
+    /// visits, and then recurse into them until `max_depth` is reached.
+    ///
+    /// # Arguments
+    ///
+    /// * `fmt` - the formatter to write the graph to
+    /// * `node` - the node whose children to write
+    /// * `node_id` - the identifier of `node` in the graph
+    /// * `next_id` - the next free identifier in the graph
+    /// * `depth` - the depth of `node` in the search tree
+    ///
+    fn as_dot(
+        &self,
+        fmt: &mut fmt::Formatter,
+        node: &Node,
+        node_id: usize,
+        next_id: &mut usize,
+        depth: usize
+    ) -> fmt::Result
+    {
+        if depth >= self.max_depth {
+            return Ok(());
+        }
+
+        let mut children = node.children.nonzero()
+            .filter(|&i| node.with(i, |child| child.count()) >= self.min_visits)
+            .collect::<Vec<usize>>();
+        children.sort_by(|&a, &b| compare_children(node, b, a, MIN_LCB_VISITS, self.tie_break));
+        children.truncate(MAX_DOT_CHILDREN);
+
+        for i in children {
+            let child_id = *next_id;
+            *next_id += 1;
+
+            writeln!(fmt, "  n{} [label=\"{}\\nN: {}\\nW: {:.1}%\"];",
+                child_id,
+                PrettyVertex { inner: i },
+                node.with(i, |child| child.count()),
+                100.0 * node.with(i, |child| child.value())
+            )?;
+            writeln!(fmt, "  n{} -> n{} [label=\"{:.4}\"];", node_id, child_id, node.prior[i])?;
+
+            let child = node.with(i, |child| child.ptr());
+
+            if !child.is_null() {
+                self.as_dot(fmt, unsafe { &*child }, child_id, next_id, depth + 1)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a> fmt::Display for ToDot<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(fmt, "digraph {{")?;
+        writeln!(fmt, "  n0 [label=\"{}\\nN: {}\"];",
+            if self.root.to_move == Color::Black { "B" } else { "W" },
+            self.root.total_count
+        )?;
+
+        let mut next_id = 1;
+        self.as_dot(fmt, self.root, 0, &mut next_id, 0)?;
+
+        writeln!(fmt, "}}")
+    }
+}
+
+/// Returns a marker that contains the top of the given search tree and can be
+/// displayed as a GraphViz DOT graph. Each node shows the move, its number of
+/// visits, and its winrate, and each edge is labeled with the prior.
+///
+/// # Arguments
+///
+/// * `root` - the search tree to write
+/// * `max_depth` - the maximum depth of the graph
+/// * `min_visits` - the minimum number of visits for a move to be included
+/// * `tie_break` - how to order children with the same number of visits
+///
+pub fn to_dot(root: &Node, max_depth: usize, min_visits: i32, tie_break: TieBreak) -> ToDot {
+    ToDot { root, max_depth, min_visits, tie_break }
 }
 
 #[cfg(test)]
@@ -1945,6 +2307,263 @@ mod tests {
         unsafe { unsafe_undo_trace() }
     }
 
+    unsafe fn unsafe_backup_without_insert() {
+        let mut board = Board::new(DEFAULT_KOMI);
+        let mut root = Node::new(
+            Color::Black,
+            0.5,
+            (0..362).map(|i| if i == 60 { 1.0 } else { 0.0 }).collect()
+        );
+        let trace = probe(&mut root, &mut board).unwrap();
+
+        backup(&trace, Color::Black, 0.9);
+
+        assert_eq!(root.size(), 1);
+        assert_eq!(root.memory_bytes(), mem::size_of::<Node>());
+        assert_eq!(root.total_count, 1);
+        assert_eq!(root.vtotal_count, 0);
+
+        // the edge should be possible to expand again
+        let mut board = Board::new(DEFAULT_KOMI);
+        let trace = probe(&mut root, &mut board).unwrap();
+
+        insert(&trace, Color::Black, 0.9, vec! [1.0; 362]);
+
+        assert_eq!(root.size(), 2);
+        assert_eq!(root.memory_bytes(), 2 * mem::size_of::<Node>());
+        assert_eq!(root.total_count, 2);
+        assert_eq!(root.visit_count(), 2);
+    }
+
+    #[test]
+    fn backup_without_insert() {
+        unsafe { unsafe_backup_without_insert() }
+    }
+
+    /// Returns the number of nodes in the given search tree, by walking it.
+    fn count_nodes(node: &Node) -> usize {
+        1 + node.children.nonzero()
+            .map(|i| node.with(i, |child| child.ptr()))
+            .filter(|child| !child.is_null())
+            .map(|child| count_nodes(unsafe { &*child }))
+            .sum::<usize>()
+    }
+
+    unsafe fn unsafe_size_after_forward() {
+        let mut root = Node::new(
+            Color::Black,
+            0.5,
+            (0..362).map(|i| if i == 60 { 1.0 } else { 0.0 }).collect()
+        );
+
+        for _ in 0..200 {
+            let mut board = Board::new(DEFAULT_KOMI);
+
+            if let ProbeResult::Found(trace) = probe(&mut root, &mut board) {
+                let &(_, last_move, _) = trace.last().unwrap();
+
+                insert(&trace, last_move.opposite(), 0.5, vec! [1.0; 362]);
+            }
+        }
+
+        assert_eq!(root.size(), count_nodes(&root));
+        assert!(root.size() > 1);
+
+        let index = root.best(0.0, TieBreak::default()).1;
+        let next = root.forward(index).expect("could not forward the tree");
+
+        assert_eq!(next.size(), count_nodes(&next));
+        assert!(next.size() > 1);
+    }
+
+    #[test]
+    fn size_after_forward() {
+        unsafe { unsafe_size_after_forward() }
+    }
+
+    #[test]
+    fn merge() {
+        let mut a = Node::new(Color::Black, 0.5, vec! [1.0; 362]);
+        let mut b = Node::new(Color::Black, 0.5, vec! [1.0; 362]);
+
+        a.with_mut(60, |mut child| { child.set_count(2); child.set_value(0.25); child.set_value_s(0.125); });
+        a.with_mut(61, |mut child| { child.set_count(4); child.set_value(0.5); });
+        a.total_count = 6;
+        b.with_mut(60, |mut child| { child.set_count(2); child.set_value(0.75); child.set_value_s(0.125); });
+        b.with_mut(62, |mut child| { child.set_count(8); child.set_value(0.6); });
+        b.total_count = 10;
+
+        a.merge(&b);
+
+        assert_eq!(a.total_count, 16);
+        assert_eq!(a.with(60, |child| child.count()), 4);
+        assert_eq!(a.with(60, |child| child.value()), 0.5);
+        assert_eq!(a.with(60, |child| child.value_s), 0.5);
+        assert_eq!(a.with(61, |child| child.count()), 4);
+        assert_eq!(a.with(62, |child| child.count()), 8);
+        assert_eq!(a.with(62, |child| child.value()), 0.6);
+        assert_eq!(a.best(0.0, TieBreak::default()).1, 62);
+    }
+
+    #[test]
+    fn best_tie_break() {
+        let mut root = Node::new(
+            Color::Black,
+            0.5,
+            (0..362).map(|i| if i == 60 { 0.2 } else if i == 61 { 0.5 } else if i == 62 { 0.3 } else { 0.0 }).collect()
+        );
+
+        root.with_mut(60, |mut child| { child.set_count(2); child.set_value(0.6); });
+        root.with_mut(61, |mut child| { child.set_count(2); child.set_value(0.4); });
+        root.with_mut(62, |mut child| { child.set_count(2); child.set_value(0.7); });
+        root.total_count = 6;
+
+        assert_eq!(root.best(0.0, TieBreak::Prior).1, 61);
+        assert_eq!(root.best(0.0, TieBreak::Winrate).1, 62);
+        assert_eq!(root.best(0.0, TieBreak::Index).1, 60);
+    }
+
+    #[test]
+    fn best_complete_tie() {
+        let mut root = Node::new(Color::Black, 0.5, vec! [0.1; 362]);
+
+        root.with_mut(62, |mut child| { child.set_count(2); child.set_value(0.5); });
+        root.with_mut(61, |mut child| { child.set_count(2); child.set_value(0.5); });
+        root.with_mut(63, |mut child| { child.set_count(2); child.set_value(0.5); });
+        root.total_count = 6;
+
+        for &tie_break in &[TieBreak::Prior, TieBreak::Winrate, TieBreak::Index] {
+            let mut children = vec! [63, 61, 62];
+
+            children.sort_by(|&a, &b| compare_children(&root, b, a, MIN_LCB_VISITS, tie_break));
+
+            assert_eq!(root.best(0.0, tie_break).1, 61);
+            assert_eq!(children, vec! [61, 62, 63]);
+        }
+    }
+
+    unsafe fn unsafe_min_exploration_visits() {
+        let mut root = Node::new(
+            Color::Black,
+            0.5,
+            (0..362).map(|i| if i == 60 { 0.9 } else if i == 61 { 0.1 } else { 0.0 }).collect()
+        );
+
+        root.with_mut(60, |mut child| { child.set_count(100000); child.set_value(0.9); });
+        root.with_mut(61, |mut child| { child.set_count(2000); child.set_value(0.1); });
+        root.total_count = 102000;
+
+        let trace = probe_with(&mut root, &mut Board::new(DEFAULT_KOMI), 0.0, None).unwrap();
+        assert_eq!(trace[0].2, 60);
+        undo(trace, true);
+
+        let trace = probe_with(&mut root, &mut Board::new(DEFAULT_KOMI), 1000.0, None).unwrap();
+        assert_eq!(trace[0].2, 61);
+        undo(trace, true);
+    }
+
+    #[test]
+    fn min_exploration_visits() {
+        unsafe { unsafe_min_exploration_visits() }
+    }
+
+    unsafe fn unsafe_utility() {
+        let mut root = Node::new(
+            Color::Black,
+            0.5,
+            (0..362).map(|i| if i == 60 || i == 61 { 0.5 } else { 0.0 }).collect()
+        );
+
+        root.with_mut(60, |mut child| { child.set_count(100); child.set_value(0.9); });
+        root.with_mut(61, |mut child| { child.set_count(100); child.set_value(0.8); });
+        root.total_count = 200;
+
+        let trace = probe(&mut root, &mut Board::new(DEFAULT_KOMI)).unwrap();
+        assert_eq!(trace[0].2, 60);
+        undo(trace, true);
+
+        let mut utility = [0.0; 368];
+        utility[61] = 0.2;
+
+        let trace = probe_with(&mut root, &mut Board::new(DEFAULT_KOMI), 0.0, Some(&utility)).unwrap();
+        assert_eq!(trace[0].2, 61);
+        undo(trace, true);
+    }
+
+    #[test]
+    fn utility() {
+        unsafe { unsafe_utility() }
+    }
+
+    #[test]
+    fn best_with_min_visits() {
+        let mut root = Node::new(
+            Color::Black,
+            0.5,
+            (0..362).map(|i| if i == 60 { 0.2 } else if i == 61 { 0.7 } else if i == 62 { 0.1 } else { 0.0 }).collect()
+        );
+
+        root.with_mut(60, |mut child| { child.set_count(3); child.set_value(0.6); });
+        root.with_mut(61, |mut child| { child.set_count(1); child.set_value(0.4); });
+        root.total_count = 4;
+
+        assert_eq!(root.best_with_min_visits(0.0, 0, TieBreak::default()).1, 60);
+        assert_eq!(root.best_with_min_visits(0.0, 3, TieBreak::default()).1, 60);
+        assert_eq!(root.best_with_min_visits(0.0, 4, TieBreak::default()), (0.4, 61));
+
+        root.disqualify(61);
+        assert_eq!(root.best_with_min_visits(0.0, 4, TieBreak::default()).1, 60);
+    }
+
+    #[test]
+    fn visit_target() {
+        let mut root = Node::new(Color::Black, 0.5, vec! [1.0; 362]);
+
+        root.with_mut(60, |mut child| child.set_count(1));
+        root.with_mut(61, |mut child| child.set_count(3));
+
+        let softmax = root.softmax::<f32>();
+        let sharp = root.visit_target::<f32>(0.5);
+        let flat = root.visit_target::<f32>(1e6);
+
+        assert_eq!(root.visit_target::<f32>(1.0), softmax);
+        assert_eq!(softmax[60], 0.25);
+        assert_eq!(softmax[61], 0.75);
+        assert_eq!(sharp[60], 0.1);
+        assert_eq!(sharp[61], 0.9);
+        assert!((flat[60] - 0.5).abs() < 1e-4, "{}", flat[60]);
+        assert!((flat[61] - 0.5).abs() < 1e-4, "{}", flat[61]);
+    }
+
+    unsafe fn unsafe_dot_graph() {
+        let mut board = Board::new(DEFAULT_KOMI);
+        let mut root = Node::new(
+            Color::Black,
+            0.5,
+            (0..362).map(|i| if i == 60 { 1.0 } else { 0.0 }).collect()
+        );
+        let other_prior: Vec<f32> = (0..362).map(|i| if i == 61 { 1.0 } else { 0.0 }).collect();
+        let trace = probe(&mut root, &mut board).unwrap();
+
+        insert(&trace, Color::Black, 0.9, other_prior);
+
+        let dot = to_dot(&root, 4, 1, TieBreak::default()).to_string();
+
+        assert!(dot.starts_with("digraph {"), "{}", dot);
+        assert!(dot.contains("n1 [label=\"d4\\nN: 1\\nW: 90.0%\"];"), "{}", dot);
+        assert!(dot.contains("n0 -> n1 [label=\"1.0000\"];"), "{}", dot);
+        assert!(!dot.contains("n2"), "{}", dot);
+
+        // nothing but the root should remain if we require more visits
+        assert!(!to_dot(&root, 4, 2, TieBreak::default()).to_string().contains("n1"));
+        assert!(!to_dot(&root, 0, 1, TieBreak::default()).to_string().contains("n1"));
+    }
+
+    #[test]
+    fn dot_graph() {
+        unsafe { unsafe_dot_graph() }
+    }
+
     #[bench]
     fn small_uct2(b: &mut Bencher) {
         let node = black_box(Node::new(Color::Black, 0.0, vec! [1.0; 362]));
@@ -20,7 +20,18 @@ pub enum Error {
     CuDNN(cudnn::Status),
     Cuda(cuda::Error),
     MalformedWeights,
-    MissingWeights
+    MissingWeights,
+    MissingTensors(Vec<String>),
+    MissingResidualLayer(usize),
+    InvalidNumLayers(usize),
+
+    /// The number of features given to a forward pass (as the number of
+    /// elements, and the batch size) did not match the batch size the workspace
+    /// was built for.
+    InvalidBatchSize(usize, usize),
+
+    /// A forward pass did not finish within the given time budget.
+    Timeout
 }
 
 impl From<cuda::Error> for Error {
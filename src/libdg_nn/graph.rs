@@ -13,12 +13,15 @@
 // limitations under the License.
 
 use std::collections::HashMap;
-use std::mem::size_of;
+use std::mem::{self, size_of};
 use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use dg_cuda as cuda;
 use dg_cuda::cudnn;
 use dg_go::utils::features;
+use dg_utils::config;
 use dg_utils::types::f16;
 use crate::layers::{PolicyLayer, ResidualLayer, UpLayer, ValueLayer};
 use crate::output_map::*;
@@ -43,6 +46,10 @@ impl Builder {
     /// Returns a mutable workspace that contains everything you need to
     /// perform a forward pass through the network pre-allocated.
     ///
+    /// This builds every layer from scratch, which is expensive. Prefer
+    /// `Network::get_workspace` which re-uses workspaces of the same batch
+    /// size.
+    ///
     /// # Arguments
     ///
     /// * `batch_size` -
@@ -59,12 +66,10 @@ impl Builder {
             allocator: self.allocator.clone(),
 
             handle: handle_dnn,
-
-            tower_finished: cuda::Event::new()?,
-
-            tower_stream: cuda::Stream::new()?,
-            policy_stream: cuda::Stream::new()?,
-            value_stream: cuda::Stream::new()?,
+            stream_sets: (0..(*config::NUM_STREAM_SETS).max(1))
+                .map(|_| StreamSet::new())
+                .collect::<Result<Vec<_>, _>>()?,
+            is_prepared: false,
 
             c_up: c_up,
             c_value: c_value,
@@ -73,6 +78,52 @@ impl Builder {
         })
     }
 
+    /// Pre-allocate enough device memory on the current device for
+    /// `num_workspaces` concurrent forward passes of the given batch size, on
+    /// every stream set of each workspace (see `NUM_STREAM_SETS`), so that
+    /// steady-state forward passes do not need to allocate any more device
+    /// memory. Returns the workspaces that were used to do so, which are ready
+    /// to be used.
+    ///
+    /// # Arguments
+    ///
+    /// * `batch_size` - the batch size of the expected workspaces
+    /// * `num_workspaces` - the number of workspaces that are expected to
+    ///   perform a forward pass at the same time
+    ///
+    pub fn reserve(&self, batch_size: usize, num_workspaces: usize) -> Result<Vec<Workspace>, Error> {
+        let features = vec! [f16::from(0.0); batch_size * features::Default::size()];
+        let mut workspaces = (0..num_workspaces)
+            .map(|_| self.get_workspace(batch_size))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // the weights are copied to the device lazily, so the first forward
+        // pass of each workspace must finish before any other stream set can
+        // use them
+        forward_concurrently(&workspaces, &features, 1)?;
+
+        for workspace in workspaces.iter_mut() {
+            workspace.is_prepared = true;
+        }
+
+        forward_concurrently(&workspaces, &features, usize::MAX)?;
+
+        if cfg!(debug_assertions) {
+            let num_allocations = self.num_allocations();
+
+            forward_concurrently(&workspaces, &features, usize::MAX)?;
+            debug_assert_eq!(self.num_allocations(), num_allocations, "a forward pass allocated after the memory was reserved");
+        }
+
+        Ok(workspaces)
+    }
+
+    /// Returns the number of device allocations that has been made on the
+    /// current device, that could not be served by previously freed memory.
+    fn num_allocations(&self) -> usize {
+        self.allocator.allocator().num_allocations()
+    }
+
     fn get_residual_layers(
         &self,
         handle_dnn: &cudnn::Handle,
@@ -96,15 +147,36 @@ impl Builder {
     }
 }
 
+/// The streams, and the event that synchronize them, that a single batch is
+/// processed on.
+struct StreamSet {
+    tower_finished: cuda::Event,
+    tower_stream: cuda::Stream,
+    policy_stream: cuda::Stream,
+    value_stream: cuda::Stream,
+}
+
+impl StreamSet {
+    fn new() -> Result<StreamSet, Error> {
+        Ok(StreamSet {
+            tower_finished: cuda::Event::new()?,
+            tower_stream: cuda::Stream::new()?,
+            policy_stream: cuda::Stream::new()?,
+            value_stream: cuda::Stream::new()?,
+        })
+    }
+}
+
 pub struct Workspace {
     batch_size: usize,
     allocator: cuda::Concurrent<cuda::Sticky<cuda::Native>>,
 
     handle: cudnn::Handle,
-    tower_finished: cuda::Event,
-    tower_stream: cuda::Stream,
-    policy_stream: cuda::Stream,
-    value_stream: cuda::Stream,
+    stream_sets: Vec<StreamSet>,
+
+    /// Whether the weights has been copied to the device, which happens
+    /// during the first forward pass.
+    is_prepared: bool,
 
     c_up: UpLayer,
     c_value: ValueLayer,
@@ -112,6 +184,56 @@ pub struct Workspace {
     c_residual: Vec<ResidualLayer>
 }
 
+/// Perform a forward pass of the given features on the first
+/// `num_stream_sets` stream sets of every workspace at the same time, and
+/// wait for all of them to finish.
+///
+/// Every forward pass is enqueued before waiting for any of them, since the
+/// intermediate tensors of each forward pass are only released back to the
+/// shared allocator once its own allocator is dropped.
+///
+/// # Arguments
+///
+/// * `workspaces` - the workspaces to perform the forward passes on
+/// * `features` - the input features
+/// * `num_stream_sets` - the maximum number of stream sets to use in each
+///   workspace
+///
+fn forward_concurrently(workspaces: &[Workspace], features: &[f16], num_stream_sets: usize) -> Result<(), Error> {
+    let mut pending = Vec::with_capacity(workspaces.len());
+
+    for workspace in workspaces {
+        for streams in workspace.stream_sets.iter().take(num_stream_sets) {
+            let mut allocator = cuda::Cloneable::new(cuda::Sticky::new(workspace.allocator.clone()));
+
+            pending.push((streams, enqueue(workspace, streams, features, OutputSet::All, &mut allocator)?, allocator));
+        }
+    }
+
+    for (streams, pending, _allocator) in pending {
+        collect(streams, pending, OutputSet::All)?;
+    }
+
+    Ok(())
+}
+
+/// Returns an error if `num_elements` features is not exactly one batch of
+/// `batch_size` features, since the descriptors of a workspace are built for
+/// a specific batch size and any other would produce garbage outputs.
+///
+/// # Arguments
+///
+/// * `num_elements` - the number of elements in the input features
+/// * `batch_size` - the batch size of the workspace
+///
+fn check_batch_size(num_elements: usize, batch_size: usize) -> Result<(), Error> {
+    if num_elements != batch_size * features::Default::size() {
+        Err(Error::InvalidBatchSize(num_elements, batch_size))
+    } else {
+        Ok(())
+    }
+}
+
 /// Returns the value and policy tensors obtained from a forward pass
 /// through the neural network.
 ///
@@ -121,38 +243,252 @@ pub struct Workspace {
 /// * `features` - the input features
 ///
 pub fn forward(workspace: &mut Workspace, features: &[f16]) -> Result<OutputMap<f16>, Error> {
-    debug_assert!(features.len() % features::Default::size() == 0);
-    debug_assert!(features.len() / features::Default::size() == workspace.batch_size);
+    forward_with(workspace, features, OutputSet::All)
+}
+
+/// Returns the requested output tensors obtained from a forward pass through
+/// the neural network. Any head that is not part of `outputs` is skipped
+/// entirely, and its output in the returned map is empty.
+///
+/// # Arguments
+///
+/// * `workspace` - the workspace for the current thread
+/// * `features` - the input features
+/// * `outputs` - the outputs to compute
+///
+pub fn forward_with(workspace: &mut Workspace, features: &[f16], outputs: OutputSet) -> Result<OutputMap<f16>, Error> {
+    check_batch_size(features.len(), workspace.batch_size)?;
+
+    let mut allocator = cuda::Cloneable::new(cuda::Sticky::new(workspace.allocator.clone()));
+    let streams = &workspace.stream_sets[0];
+    let pending = enqueue(workspace, streams, features, outputs, &mut allocator)?;
+    let output_map = collect(streams, pending, outputs)?;
+
+    workspace.is_prepared = true;
+    Ok(output_map)
+}
 
+/// Returns the value and policy tensors obtained from a forward pass through
+/// the neural network, or `Error::Timeout` if it does not finish within the
+/// given time budget. This avoids blocking forever when the device is stalled,
+/// for example because it is shared with another process.
+///
+/// A forward pass that timed out is still running on the device, so any later
+/// forward pass on this workspace is queued behind it. Its device memory is
+/// never released, since it may still be written to.
+///
+/// # Arguments
+///
+/// * `workspace` - the workspace for the current thread
+/// * `features` - the input features
+/// * `timeout` - the maximum amount of time to wait for the forward pass
+///
+pub fn forward_with_timeout(workspace: &mut Workspace, features: &[f16], timeout: Duration) -> Result<OutputMap<f16>, Error> {
+    check_batch_size(features.len(), workspace.batch_size)?;
+
+    let start_time = Instant::now();
     let mut allocator = cuda::Cloneable::new(cuda::Sticky::new(workspace.allocator.clone()));
+    let streams = &workspace.stream_sets[0];
+    let pending = enqueue(workspace, streams, features, OutputSet::All, &mut allocator)?;
+
+    while !streams.value_stream.is_finished()? || !streams.policy_stream.is_finished()? {
+        if start_time.elapsed() >= timeout {
+            mem::forget(pending);
+            mem::forget(allocator);
+
+            return Err(Error::Timeout);
+        }
+
+        thread::sleep(Duration::from_micros(100));
+    }
+
+    let output_map = collect(streams, pending, OutputSet::All)?;
+
+    workspace.is_prepared = true;
+    Ok(output_map)
+}
+
+/// Returns the requested output tensors of every batch in `batches`. The
+/// batches are independent of each other, and are processed concurrently on
+/// the stream sets of the workspace (see `NUM_STREAM_SETS`), which keeps the
+/// GPU busy when each individual batch is too small to do so on its own.
+///
+/// # Arguments
+///
+/// * `workspace` - the workspace for the current thread
+/// * `batches` - the input features of each batch
+/// * `outputs` - the outputs to compute
+///
+pub fn forward_batches(workspace: &mut Workspace, batches: &[&[f16]], outputs: OutputSet) -> Result<Vec<OutputMap<f16>>, Error> {
+    for features in batches {
+        check_batch_size(features.len(), workspace.batch_size)?;
+    }
+
+    if batches.is_empty() {
+        return Ok(vec! []);
+    } else if !workspace.is_prepared {
+        // the weights are copied to the device lazily, so the first batch
+        // must finish before any other stream can use them
+        let mut output_maps = vec! [forward_with(workspace, batches[0], outputs)?];
+        output_maps.extend(forward_batches(workspace, &batches[1..], outputs)?);
+
+        return Ok(output_maps);
+    }
+
+    let num_stream_sets = workspace.stream_sets.len();
+    let mut pending = Vec::with_capacity(batches.len());
+
+    for (i, features) in batches.iter().enumerate() {
+        let mut allocator = cuda::Cloneable::new(cuda::Sticky::new(workspace.allocator.clone()));
+        let streams = &workspace.stream_sets[i % num_stream_sets];
+
+        pending.push((enqueue(workspace, streams, features, outputs, &mut allocator)?, allocator));
+    }
+
+    pending.into_iter()
+        .enumerate()
+        .map(|(i, (pending, _allocator))| collect(&workspace.stream_sets[i % num_stream_sets], pending, outputs))
+        .collect()
+}
+
+/// The device tensors of a forward pass that has been enqueued, but whose
+/// results has not yet been copied to the host.
+struct PendingOutput<A: cuda::Allocator + Clone> {
+    value: cuda::SmartPtr<A>,
+    value_embedding: cuda::SmartPtr<A>,
+    policy: Option<cuda::SmartPtr<A>>,
+    residual: Option<Vec<f16>>
+}
 
+/// Enqueue a forward pass of the given features on `streams`, and returns
+/// the device tensors of the requested outputs without waiting for them.
+///
+/// # Arguments
+///
+/// * `workspace` - the workspace for the current thread
+/// * `streams` - the streams to enqueue the forward pass on
+/// * `features` - the input features
+/// * `outputs` - the outputs to compute
+/// * `allocator` - the allocator to use for every intermediate tensor
+///
+fn enqueue<A: cuda::Allocator + Clone>(
+    workspace: &Workspace,
+    streams: &StreamSet,
+    features: &[f16],
+    outputs: OutputSet,
+    allocator: &mut A
+) -> Result<PendingOutput<A>, Error>
+{
     // copy all of the input features into a temporary workspace
-    let mut input = cuda::malloc(size_of::<f16>() * features.len(), &mut allocator)?;
-    input.copy_from_slice(&features, &workspace.tower_stream)?;
+    let mut input = cuda::malloc(size_of::<f16>() * features.len(), allocator)?;
+    input.copy_from_slice(&features, &streams.tower_stream)?;
 
     // upsample features to `n` channels
-    let mut residual_1 = workspace.c_up.forward(&workspace.handle, &input, &mut allocator, &workspace.tower_stream)?;
+    let mut residual_1 = workspace.c_up.forward(&workspace.handle, &input, allocator, &streams.tower_stream)?;
 
     // residual blocks
     let num_residual = workspace.c_residual.len();
+    let mut intermediate = None;
+
+    if let Some(index) = outputs.residual_index() {
+        if index > num_residual {
+            return Err(Error::MissingResidualLayer(index));
+        } else if index == 0 {
+            intermediate = Some(residual_1.to_vec::<f16>(&streams.tower_stream)?);
+        }
+    }
 
     for i in 0..num_residual {
         let residual = &workspace.c_residual[i];
 
-        residual_1 = residual.forward(&workspace.handle, residual_1, &mut allocator, &workspace.tower_stream)?;
+        residual_1 = residual.forward(&workspace.handle, residual_1, allocator, &streams.tower_stream)?;
+
+        // this synchronizes the tower stream, but it is only used when
+        // debugging the network so the performance does not matter.
+        if outputs.residual_index() == Some(i + 1) {
+            intermediate = Some(residual_1.to_vec::<f16>(&streams.tower_stream)?);
+        }
+    }
+
+    streams.tower_finished.record(&streams.tower_stream)?;
+    streams.value_stream.wait_event(&streams.tower_finished)?;
+
+    // run the value and policy head (if they are requested)
+    let (value, value_embedding) = workspace.c_value.forward(&workspace.handle, &residual_1, allocator, &streams.value_stream)?;
+    let policy = if outputs.has_policy() {
+        streams.policy_stream.wait_event(&streams.tower_finished)?;
+
+        Some(workspace.c_policy.forward(&workspace.handle, &residual_1, allocator, &streams.policy_stream)?)
+    } else {
+        None
+    };
+
+    Ok(PendingOutput { value, value_embedding, policy, residual: intermediate })
+}
+
+/// Wait for the given enqueued forward pass to finish, and returns its
+/// outputs copied to the host.
+///
+/// # Arguments
+///
+/// * `streams` - the streams that the forward pass was enqueued on
+/// * `pending` - the enqueued forward pass
+/// * `outputs` - the outputs to compute
+///
+fn collect<A: cuda::Allocator + Clone>(
+    streams: &StreamSet,
+    pending: PendingOutput<A>,
+    outputs: OutputSet
+) -> Result<OutputMap<f16>, Error>
+{
+    let policy = if let Some(policy) = pending.policy {
+        policy.to_vec::<f16>(&streams.policy_stream)?
+    } else {
+        vec! []
+    };
+
+    let output_map = OutputMap::new(
+        pending.value.to_vec::<f16>(&streams.value_stream)?,
+        policy
+    );
+
+    let output_map = if outputs.has_value_embedding() {
+        output_map.with_value_embedding(pending.value_embedding.to_vec::<f16>(&streams.value_stream)?)
+    } else {
+        output_map
+    };
+
+    if let Some(residual) = pending.residual {
+        Ok(output_map.with_residual(residual))
+    } else {
+        Ok(output_map)
     }
+}
+
+/// Returns the value and policy tensors obtained from a forward pass
+/// through the neural network, widened to `f32` when copied to the host. The
+/// network itself still runs in half precision, this only avoids any further
+/// quantization of the results on the host.
+///
+/// # Arguments
+///
+/// * `workspace` - the workspace for the current thread
+/// * `features` - the input features
+///
+pub fn forward_f32(workspace: &mut Workspace, features: &[f16]) -> Result<OutputMap<f32>, Error> {
+    forward(workspace, features).map(|output| output.convert::<f32>())
+}
 
-    workspace.tower_finished.record(&workspace.tower_stream)?;
-    workspace.value_stream.wait_event(&workspace.tower_finished)?;
-    workspace.policy_stream.wait_event(&workspace.tower_finished)?;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    // run the value and policy head, then wait for them to finish (if
-    // they are requested)
-    let value = workspace.c_value.forward(&workspace.handle, &residual_1, &mut allocator, &workspace.value_stream)?;
-    let policy = workspace.c_policy.forward(&workspace.handle, &residual_1, &mut allocator, &workspace.policy_stream)?;
+    #[test]
+    fn check_batch_size_mismatch() {
+        let size = features::Default::size();
 
-    Ok(OutputMap::new(
-        value.to_vec::<f16>(&workspace.value_stream)?,
-        policy.to_vec::<f16>(&workspace.policy_stream)?
-    ))
+        assert!(check_batch_size(32 * size, 32).is_ok());
+        assert!(matches!(check_batch_size(33 * size, 32), Err(Error::InvalidBatchSize(_, 32))));
+        assert!(matches!(check_batch_size(32 * size + 1, 32), Err(Error::InvalidBatchSize(_, 32))));
+    }
 }
@@ -64,13 +64,23 @@ impl ValueLayer {
         )
     }
 
+    /// Returns the value, and the activations of the convolution that feeds
+    /// into the final linear layer (`2 * 361` elements per image).
+    ///
+    /// # Arguments
+    ///
+    /// * `handle` - The cuDNN handle
+    /// * `input` - The final output of the residual block
+    /// * `allocator` - The allocator to use for the outputs
+    /// * `stream` - The stream to run the layer on
+    ///
     pub fn forward<'a, A: cuda::Allocator + Clone>(
         &self,
         handle: &cudnn::Handle,
         input: &cuda::SmartPtr<A>,
         allocator: &mut A,
         stream: &cuda::Stream
-    ) -> Result<cuda::SmartPtr<A>, Error>
+    ) -> Result<(cuda::SmartPtr<A>, cuda::SmartPtr<A>), Error>
     {
         // perform the forward convolution
         let value_1 = self.conv_1.forward(handle, input, allocator, stream)?;
@@ -80,6 +90,6 @@ impl ValueLayer {
         let value_2 = self.linear_2.forward(handle, &value_1, allocator, stream)?;
         self.tanh.forward(handle, value_2.as_ptr(), value_2.as_ptr())?;
 
-        Ok(value_2)
+        Ok((value_2, value_1))
     }
 }
@@ -31,6 +31,8 @@ mod output_map;
 mod tensor;
 
 pub use self::error::Error;
-pub use self::graph::{Workspace, forward};
+pub use self::graph::{Workspace, forward, forward_batches, forward_f32, forward_with, forward_with_timeout};
+pub use self::loader::load;
 pub use self::network::{Network, WorkspaceGuard};
 pub use self::output_map::*;
+pub use self::tensor::Tensor;
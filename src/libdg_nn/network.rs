@@ -14,14 +14,18 @@
 
 use crossbeam_channel::{self, Sender, Receiver};
 use dashmap::DashMap;
+use std::collections::HashMap;
 use std::env;
+use std::fs;
 use std::ops::{Deref, DerefMut};
 use std::path::Path;
 use std::sync::Arc;
 
 use dg_cuda::{Device, PerDevice};
+use dg_utils::{config, gzip};
 
 use super::{Error, graph, loader};
+use super::tensor::Tensor;
 
 #[derive(Clone)]
 struct WorkspaceQueue {
@@ -49,11 +53,64 @@ impl WorkspaceQueue {
     }
 }
 
+/// Semaphore that limits the number of workspaces that may be in use at the
+/// same time on a device, independent of how many threads want one. Each
+/// permit is a token in a bounded channel, a workspace may only be acquired
+/// while holding one.
+#[derive(Clone)]
+struct WorkspacePermits {
+    tokens: Option<(Sender<()>, Receiver<()>)>
+}
+
+impl Default for WorkspacePermits {
+    fn default() -> Self {
+        Self::new(*config::MAX_WORKSPACES)
+    }
+}
+
+impl WorkspacePermits {
+    /// Returns a semaphore with `max_workspaces` permits, or without any
+    /// limit if `max_workspaces` is zero.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_workspaces` -
+    ///
+    fn new(max_workspaces: usize) -> Self {
+        if max_workspaces == 0 {
+            return Self { tokens: None };
+        }
+
+        let (tx, rx) = crossbeam_channel::bounded(max_workspaces);
+
+        for _ in 0..max_workspaces {
+            tx.send(()).expect("could not add permit to `WorkspacePermits`");
+        }
+
+        Self { tokens: Some((tx, rx)) }
+    }
+
+    /// Block until a permit is available, and then take it.
+    fn acquire(&self) {
+        if let Some((_, ref rx)) = self.tokens {
+            rx.recv().expect("could not acquire permit from `WorkspacePermits`");
+        }
+    }
+
+    /// Returns a previously acquired permit.
+    fn release(&self) {
+        if let Some((ref tx, _)) = self.tokens {
+            tx.send(()).expect("could not release permit to `WorkspacePermits`");
+        }
+    }
+}
+
 /// Wrapper around a `Workspace` that when dropped returns it to the
-/// pool it was acquired from.
+/// pool it was acquired from, and releases its permit.
 pub struct WorkspaceGuard {
     workspace: Option<graph::Workspace>,
-    pool: WorkspaceQueue
+    pool: WorkspaceQueue,
+    permits: WorkspacePermits
 }
 
 impl Deref for WorkspaceGuard {
@@ -75,6 +132,8 @@ impl Drop for WorkspaceGuard {
         if let Some(workspace) = self.workspace.take() {
             self.pool.push(workspace);
         }
+
+        self.permits.release();
     }
 }
 
@@ -82,12 +141,27 @@ impl Drop for WorkspaceGuard {
 #[derive(Clone)]
 pub struct Network {
     builder: Arc<graph::Builder>,
-    workspaces: Arc<PerDevice<DashMap<usize, WorkspaceQueue>>>
+    workspaces: Arc<PerDevice<DashMap<usize, WorkspaceQueue>>>,
+    permits: Arc<PerDevice<WorkspacePermits>>,
+    identifier: String
 }
 
 unsafe impl Send for Network { }  // this is safe because the Rc<...> is guarded by a Mutex and/or Arc
 unsafe impl Sync for Network { }  // this is safe because the Rc<...> is guarded by a Mutex and/or Arc
 
+/// Returns the CRC-32 checksum of the file at the given path as a hexadecimal
+/// string, or an empty string if the file could not be read.
+///
+/// # Arguments
+///
+/// * `path` - the path of the weights file
+///
+fn get_identifier(path: &Path) -> String {
+    fs::read(path)
+        .map(|content| format!("{:08x}", gzip::crc32(&content)))
+        .unwrap_or_default()
+}
+
 impl Network {
     pub fn new() -> Option<Network> {
         let paths = vec! [
@@ -108,39 +182,122 @@ impl Network {
 
         paths.iter()
             .filter_map(|path| {
-                match loader::load(Path::new(path)) {
-                    Ok(weights) => Some(weights),
+                match loader::load(Path::new(path)).and_then(|weights| validate(&weights).map(|_| weights)) {
+                    Ok(weights) => Some((path, weights)),
                     Err(Error::MissingWeights) => None,
+                    Err(Error::MissingTensors(names)) => {
+                        panic!("Failed to load network weights -- missing tensor {}", names.join(", "))
+                    },
+                    Err(Error::MissingResidualLayer(i)) => {
+                        panic!("Failed to load network weights -- missing residual block {:02}_residual", i)
+                    },
+                    Err(Error::InvalidNumLayers(num_layers)) => {
+                        panic!("Failed to load network weights -- invalid number of residual blocks {}", num_layers)
+                    },
                     Err(reason) => {
                         panic!("Failed to load network weights -- {:?}", reason)
                     }
                 }
             })
             .next()
-            .map(|weights| Network {
+            .map(|(path, weights)| Network {
                 builder: Arc::new(graph::Builder::new(weights)),
-                workspaces: Arc::new(PerDevice::new().expect("could not create PerDevice<T>"))
+                workspaces: Arc::new(PerDevice::new().expect("could not create PerDevice<T>")),
+                permits: Arc::new(PerDevice::new().expect("could not create PerDevice<T>")),
+                identifier: get_identifier(Path::new(path))
             })
     }
 
-    /// Returns a `Workspace` with the given batch size.
+    /// Returns an identifier of the weights of this network, which is the
+    /// CRC-32 checksum of the weights file as a hexadecimal string.
+    pub fn identifier(&self) -> &str {
+        &self.identifier
+    }
+
+    /// Returns the names of every tensor that the graph looks up in the
+    /// weights file for a network with the given number of residual blocks.
+    /// Tensors that have a default value, such as `num_channels:0`, are not
+    /// included.
+    ///
+    /// # Arguments
+    ///
+    /// * `num_layers` - the number of residual blocks
+    ///
+    pub fn required_tensor_names(num_layers: usize) -> Vec<String> {
+        let mut layers = vec! ["01_upsample/conv_1".to_string()];
+
+        for i in 2..(2 + num_layers) {
+            layers.push(format!("{:02}_residual/conv_1", i));
+            layers.push(format!("{:02}_residual/conv_2", i));
+        }
+
+        let i = 2 + num_layers;
+
+        layers.push(format!("{:02}v_value/conv_1", i));
+        layers.push(format!("{:02}v_value/linear_2", i));
+        layers.push(format!("{:02}p_policy/conv_1", i));
+        layers.push(format!("{:02}p_policy/linear_1", i));
+
+        layers.into_iter()
+            .flat_map(|name| vec! [format!("{}:0", name), format!("{}/offset:0", name)])
+            .collect()
+    }
+
+    /// Returns a `Workspace` with the given batch size for the current device.
+    /// Workspaces are pooled per device and batch size, so a new one is only
+    /// built if there are no idle workspaces in the pool. The workspace is
+    /// returned to the pool when the guard is dropped.
+    ///
+    /// If `MAX_WORKSPACES` is set, then this blocks until fewer than that many
+    /// workspaces are in use on the current device.
     ///
     /// # Arguments
     ///
     /// * `batch_size` -
     ///
     pub fn get_workspace(&self, batch_size: usize) -> Result<WorkspaceGuard, Error> {
+        let permits = (*self.permits).clone();
+        permits.acquire();
+
         let candidates = self.workspaces.entry(batch_size).or_default();
+        let workspace = match candidates.try_pop() {
+            None => match self.builder.get_workspace(batch_size) {
+                Ok(workspace) => workspace,
+                Err(reason) => {
+                    permits.release();
+                    return Err(reason);
+                }
+            },
+            Some(workspace) => workspace
+        };
 
         Ok(WorkspaceGuard {
             pool: candidates.clone(),
-            workspace: match candidates.try_pop() {
-                None => Some(self.builder.get_workspace(batch_size)?),
-                x => x
-            }
+            workspace: Some(workspace),
+            permits
         })
     }
 
+    /// Pre-allocate enough device memory on the current device for
+    /// `num_workspaces` concurrent forward passes of the given batch size, and
+    /// add the workspaces used to do so to the pool. This avoids the latency
+    /// of growing the allocator in the middle of a game.
+    ///
+    /// # Arguments
+    ///
+    /// * `batch_size` -
+    /// * `num_workspaces` -
+    ///
+    pub fn reserve(&self, batch_size: usize, num_workspaces: usize) -> Result<(), Error> {
+        let candidates = self.workspaces.entry(batch_size).or_default();
+
+        for workspace in self.builder.reserve(batch_size, num_workspaces)? {
+            candidates.push(workspace);
+        }
+
+        Ok(())
+    }
+
     /// Wait for all jobs on the current device to finish, and then drain all of the workspaces.
     pub fn synchronize(&self) {
         let original_device = Device::default();
@@ -158,3 +315,198 @@ impl Network {
         original_device.set_current().expect("Failed to set the device for the current thread");
     }
 }
+
+/// Returns the number of residual blocks in the given weights, which are the
+/// consecutive blocks starting at index `2` with at least one of their
+/// convolutions present.
+///
+/// # Arguments
+///
+/// * `tensors` -
+///
+fn get_num_layers(tensors: &HashMap<String, Tensor>) -> usize {
+    (2..).take_while(|i| {
+        tensors.contains_key(&format!("{:02}_residual/conv_1:0", i)) ||
+            tensors.contains_key(&format!("{:02}_residual/conv_2:0", i))
+    }).count()
+}
+
+/// The maximum number of residual blocks that a network may contain.
+const MAX_NUM_LAYERS: usize = 128;
+
+/// Returns the index of the first residual block that is missing, if any
+/// residual block with a larger index is present in the given weights.
+///
+/// # Arguments
+///
+/// * `tensors` -
+///
+fn get_missing_layer(tensors: &HashMap<String, Tensor>) -> Option<usize> {
+    let num_layers = get_num_layers(tensors);
+    let max_index = tensors.keys()
+        .filter_map(|name| {
+            let (index, rest) = name.split_at(name.find('_')?);
+
+            if rest.starts_with("_residual/") { index.parse::<usize>().ok() } else { None }
+        })
+        .max();
+
+    match max_index {
+        Some(max_index) if max_index >= 2 + num_layers => Some(2 + num_layers),
+        _ => None
+    }
+}
+
+/// Returns the names of the tensors that the graph requires but that are
+/// missing from the given weights, and the names of the tensors in the given
+/// weights that the graph will never use, as `(missing, extra)`.
+///
+/// # Arguments
+///
+/// * `tensors` -
+///
+fn diff_tensor_names(tensors: &HashMap<String, Tensor>) -> (Vec<String>, Vec<String>) {
+    let num_layers = get_num_layers(tensors);
+    let required = Network::required_tensor_names(num_layers);
+    let mut optional = vec! ["num_channels:0".to_string(), "num_samples:0".to_string()];
+
+    for i in 2..(2 + num_layers) {
+        optional.push(format!("{:02}_residual/alpha:0", i));
+    }
+
+    let mut missing = required.iter()
+        .filter(|name| !tensors.contains_key(*name))
+        .cloned()
+        .collect::<Vec<_>>();
+    let mut extra = tensors.keys()
+        .filter(|name| !required.contains(name) && !optional.contains(name))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    missing.sort();
+    extra.sort();
+
+    (missing, extra)
+}
+
+/// Returns an error if the given weights is missing any tensor that the graph
+/// requires. Tensors that are present but never used are only warned about.
+///
+/// # Arguments
+///
+/// * `tensors` -
+///
+fn validate(tensors: &HashMap<String, Tensor>) -> Result<(), Error> {
+    if let Some(i) = get_missing_layer(tensors) {
+        return Err(Error::MissingResidualLayer(i));
+    }
+
+    let num_layers = get_num_layers(tensors);
+    if num_layers == 0 || num_layers > MAX_NUM_LAYERS {
+        return Err(Error::InvalidNumLayers(num_layers));
+    }
+
+    let (missing, extra) = diff_tensor_names(tensors);
+
+    for name in &extra {
+        eprintln!("Warning: unused tensor {}", name);
+    }
+
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::MissingTensors(missing))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tensors_with(names: &[String]) -> HashMap<String, Tensor> {
+        names.iter().map(|name| (name.clone(), Tensor::default())).collect()
+    }
+
+    #[test]
+    fn permits_are_limited() {
+        let permits = WorkspacePermits::new(2);
+
+        permits.acquire();
+        permits.acquire();
+        assert!(permits.tokens.as_ref().unwrap().1.try_recv().is_err());
+
+        permits.release();
+        permits.acquire();
+        assert!(permits.tokens.as_ref().unwrap().1.try_recv().is_err());
+    }
+
+    #[test]
+    fn permits_are_unlimited() {
+        let permits = WorkspacePermits::new(0);
+
+        for _ in 0..1000 {
+            permits.acquire();
+        }
+
+        assert!(permits.tokens.is_none());
+    }
+
+    #[test]
+    fn required_tensor_names() {
+        let names = Network::required_tensor_names(1);
+
+        assert_eq!(names.len(), 14);
+        assert!(names.contains(&"01_upsample/conv_1:0".to_string()));
+        assert!(names.contains(&"02_residual/conv_2/offset:0".to_string()));
+        assert!(names.contains(&"03v_value/linear_2:0".to_string()));
+        assert!(names.contains(&"03p_policy/linear_1/offset:0".to_string()));
+    }
+
+    #[test]
+    fn diff_complete() {
+        let mut tensors = tensors_with(&Network::required_tensor_names(4));
+        tensors.insert("num_channels:0".to_string(), Tensor::default());
+        tensors.insert("03_residual/alpha:0".to_string(), Tensor::default());
+
+        assert_eq!(diff_tensor_names(&tensors), (vec! [], vec! []));
+        assert!(validate(&tensors).is_ok());
+    }
+
+    #[test]
+    fn diff_missing_and_extra() {
+        let mut tensors = tensors_with(&Network::required_tensor_names(6));
+        tensors.remove("07_residual/conv_2/offset:0");
+        tensors.insert("foo:0".to_string(), Tensor::default());
+
+        assert_eq!(
+            diff_tensor_names(&tensors),
+            (vec! ["07_residual/conv_2/offset:0".to_string()], vec! ["foo:0".to_string()])
+        );
+    }
+
+    #[test]
+    fn missing_residual_layer() {
+        let mut tensors = tensors_with(&Network::required_tensor_names(6));
+        tensors.remove("05_residual/conv_1:0");
+        tensors.remove("05_residual/conv_2:0");
+
+        assert_eq!(get_missing_layer(&tensors), Some(5));
+
+        match validate(&tensors) {
+            Err(Error::MissingResidualLayer(5)) => {},
+            other => panic!("{:?}", other)
+        }
+    }
+
+    #[test]
+    fn invalid_num_layers() {
+        let tensors = tensors_with(&Network::required_tensor_names(0));
+
+        assert_eq!(get_missing_layer(&tensors), None);
+
+        match validate(&tensors) {
+            Err(Error::InvalidNumLayers(0)) => {},
+            other => panic!("{:?}", other)
+        }
+    }
+}
@@ -12,9 +12,51 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+/// The outputs of the network that should be computed during a forward pass.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputSet {
+    /// Compute both the value and the policy.
+    All,
+
+    /// Only compute the value, the policy of the output map will be empty.
+    Value,
+
+    /// Compute the value, the policy, and the value embedding.
+    AllWithValueEmbedding,
+
+    /// Compute the value, the policy, and the output of the residual tower
+    /// after the given number of residual blocks, where `0` is the output of
+    /// the upsampling layer. This is intended for comparing the intermediate
+    /// activations to those of another implementation of the same network.
+    AllWithResidual(usize)
+}
+
+impl OutputSet {
+    /// Returns true if the policy should be computed.
+    pub fn has_policy(self) -> bool {
+        self != OutputSet::Value
+    }
+
+    /// Returns true if the value embedding should be computed.
+    pub fn has_value_embedding(self) -> bool {
+        self == OutputSet::AllWithValueEmbedding
+    }
+
+    /// Returns the number of residual blocks after which the output of the
+    /// residual tower should be returned, if any.
+    pub fn residual_index(self) -> Option<usize> {
+        match self {
+            OutputSet::AllWithResidual(index) => Some(index),
+            _ => None
+        }
+    }
+}
+
 pub struct OutputMap<T: Sized> {
     value: Vec<T>,
     policy: Vec<T>,
+    value_embedding: Option<Vec<T>>,
+    residual: Option<Vec<T>>
 }
 
 impl<T: Sized> OutputMap<T> {
@@ -23,12 +65,113 @@ impl<T: Sized> OutputMap<T> {
         policy: Vec<T>
     ) -> Self
     {
-        Self { value, policy }
+        Self { value, policy, value_embedding: None, residual: None }
+    }
+
+    /// Returns this output map with the given value embedding, which are the
+    /// activations that feed into the final linear layer of the value head.
+    ///
+    /// # Arguments
+    ///
+    /// * `value_embedding` - the value embedding of every image in the batch
+    ///
+    pub fn with_value_embedding(self, value_embedding: Vec<T>) -> Self {
+        Self { value_embedding: Some(value_embedding), ..self }
+    }
+
+    /// Returns the value embedding, if it was computed.
+    pub fn value_embedding(&self) -> Option<&[T]> {
+        self.value_embedding.as_deref()
+    }
+
+    /// Returns this output map with the given intermediate output of the
+    /// residual tower, as requested by `OutputSet::AllWithResidual`.
+    ///
+    /// # Arguments
+    ///
+    /// * `residual` - the activations of every image in the batch, in the
+    ///   same layout as the input features
+    ///
+    pub fn with_residual(self, residual: Vec<T>) -> Self {
+        Self { residual: Some(residual), ..self }
+    }
+
+    /// Returns the intermediate output of the residual tower, if it was
+    /// computed.
+    pub fn residual(&self) -> Option<&[T]> {
+        self.residual.as_deref()
     }
 
     pub fn unwrap(self) -> (Vec<T>, Vec<T>) {
         match self {
-            OutputMap { value, policy } => (value, policy)
+            OutputMap { value, policy, .. } => (value, policy)
         }
     }
 }
+
+impl<T: Sized + Copy> OutputMap<T> {
+    /// Returns this output map with every element converted to `U`.
+    pub fn convert<U: From<T>>(self) -> OutputMap<U> {
+        OutputMap {
+            value: self.value.into_iter().map(U::from).collect(),
+            policy: self.policy.into_iter().map(U::from).collect(),
+            value_embedding: self.value_embedding.map(|v| v.into_iter().map(U::from).collect()),
+            residual: self.residual.map(|v| v.into_iter().map(U::from).collect())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dg_utils::types::f16;
+    use super::*;
+
+    #[test]
+    fn has_policy() {
+        assert!(OutputSet::All.has_policy());
+        assert!(!OutputSet::Value.has_policy());
+        assert!(OutputSet::AllWithValueEmbedding.has_policy());
+        assert!(OutputSet::AllWithResidual(5).has_policy());
+    }
+
+    #[test]
+    fn residual_index() {
+        assert_eq!(OutputSet::All.residual_index(), None);
+        assert_eq!(OutputSet::AllWithValueEmbedding.residual_index(), None);
+        assert_eq!(OutputSet::AllWithResidual(5).residual_index(), Some(5));
+    }
+
+    #[test]
+    fn residual() {
+        let output = OutputMap::new(vec! [f16::from(0.25)], vec! []);
+        assert_eq!(output.residual(), None);
+
+        let output = output.with_residual(vec! [f16::from(2.0)]).convert::<f32>();
+        assert_eq!(output.residual(), Some(&[2.0f32][..]));
+    }
+
+    #[test]
+    fn has_value_embedding() {
+        assert!(!OutputSet::All.has_value_embedding());
+        assert!(!OutputSet::Value.has_value_embedding());
+        assert!(OutputSet::AllWithValueEmbedding.has_value_embedding());
+    }
+
+    #[test]
+    fn value_embedding() {
+        let output = OutputMap::new(vec! [f16::from(0.25)], vec! []);
+        assert_eq!(output.value_embedding(), None);
+
+        let output = output.with_value_embedding(vec! [f16::from(1.5)]).convert::<f32>();
+        assert_eq!(output.value_embedding(), Some(&[1.5f32][..]));
+    }
+
+    #[test]
+    fn convert_f16_to_f32() {
+        let output = OutputMap::new(vec! [f16::from(0.25)], vec! [f16::from(0.5), f16::from(-1.0)]);
+        let (value, policy) = output.convert::<f32>().unwrap();
+
+        assert_eq!(value, vec! [0.25]);
+        assert_eq!(policy, vec! [0.5, -1.0]);
+    }
+}
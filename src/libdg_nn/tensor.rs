@@ -17,6 +17,7 @@ use std::sync::{Arc, Mutex, MutexGuard};
 
 use dg_cuda::cudnn::DataType;
 use dg_cuda::{PerDevice, Ptr, Stream};
+use dg_utils::types::f16;
 use super::Error;
 
 /// A data structure with interior mutability that store the host,
@@ -120,6 +121,36 @@ impl Tensor {
         }
     }
 
+    /// Returns the host values of this tensor as `f32`, with any quantized
+    /// (`Int8`) values scaled back in the same way as during inference.
+    pub fn to_f32(&self) -> Vec<f32> {
+        let n = self.size_in_elements;
+
+        unsafe {
+            match self.data_type() {
+                DataType::Int8 => {
+                    let data = ::std::slice::from_raw_parts(self.host.as_ptr() as *const i8, n);
+
+                    data.iter().map(|&x| x as f32 * self.scale / 127.0).collect()
+                },
+                DataType::Int32 => {
+                    let data = ::std::slice::from_raw_parts(self.host.as_ptr() as *const i32, n);
+
+                    data.iter().map(|&x| x as f32).collect()
+                },
+                DataType::Half => {
+                    let data = ::std::slice::from_raw_parts(self.host.as_ptr() as *const f16, n);
+
+                    data.iter().map(|&x| f32::from(x)).collect()
+                },
+                DataType::Float => {
+                    ::std::slice::from_raw_parts(self.host.as_ptr() as *const f32, n).to_vec()
+                },
+                _ => unreachable!()
+            }
+        }
+    }
+
     pub fn copy_to_device(&self, stream: &Stream) -> Result<bool, Error> {
         let mut ptr = self.ptr.lock().unwrap();
 
@@ -148,3 +179,23 @@ impl Tensor {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn int8_to_f32() {
+        let mut tensor = Tensor::from_vec(DataType::Int8, vec! [127i8, -127, 0, 64]).unwrap();
+        tensor.set_scale(2.0);
+
+        assert_eq!(tensor.to_f32(), vec! [2.0, -2.0, 0.0, 128.0 / 127.0]);
+    }
+
+    #[test]
+    fn half_to_f32() {
+        let tensor = Tensor::from_vec(DataType::Half, vec! [f16::from(1.5), f16::from(-0.25)]).unwrap();
+
+        assert_eq!(tensor.to_f32(), vec! [1.5, -0.25]);
+    }
+}
@@ -13,13 +13,16 @@
 // limitations under the License.
 
 use std::env;
+use std::fmt;
+use std::process;
 use std::str::FromStr;
 
 use regex::Regex;
 
-#[derive(PartialEq)]
+#[derive(Debug, PartialEq)]
 pub enum Procedure {
     Benchmark,
+    DumpConfig,
     Reanalyze(Vec<String>),
     SelfPlay(usize, bool),
     Gtp,
@@ -65,6 +68,7 @@ impl FromStr for RolloutLimit {
     }
 }
 
+#[derive(Debug)]
 pub enum SamplingStrategy {
     Percent(f32),
     Fixed(usize)
@@ -90,12 +94,45 @@ impl FromStr for SamplingStrategy {
     }
 }
 
+/// What the engine looks at when deciding whether to resign.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResignMetric {
+    Value,
+    Score,
+    Both
+}
+
+impl FromStr for ResignMetric {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s.trim() {
+            "value" => Ok(ResignMetric::Value),
+            "score" => Ok(ResignMetric::Score),
+            "both" => Ok(ResignMetric::Both),
+            _ => Err(())
+        }
+    }
+}
+
+impl fmt::Display for ResignMetric {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ResignMetric::Value => write!(f, "value"),
+            ResignMetric::Score => write!(f, "score"),
+            ResignMetric::Both => write!(f, "both")
+        }
+    }
+}
+
 lazy_static! {
     /// The main producedure to run during this execution.
     pub static ref PROCEDURE: Procedure = if has_opt("--help") {
         Procedure::Help
     } else if has_opt("--bench") {
         Procedure::Benchmark
+    } else if has_opt("--dump-config") {
+        Procedure::DumpConfig
     } else if has_opt("--reanalyze") {
         Procedure::Reanalyze(
             get_args()
@@ -118,6 +155,54 @@ lazy_static! {
     /// Whether to allow the GTP interface to resign.
     pub static ref NO_RESIGN: bool = has_opt("--no-resign");
 
+    /// Whether to allow the engine to resign during self-play. By default every
+    /// self-play game is played until the end.
+    pub static ref SELF_PLAY_RESIGN: bool = has_opt("--self-play-resign");
+
+    /// The fraction of self-play games where resigning is disabled even if
+    /// `SELF_PLAY_RESIGN` is set, so that the false resign rate can be
+    /// measured.
+    pub static ref SELF_PLAY_NO_RESIGN_FRACTION: f32 = get_env("SELF_PLAY_NO_RESIGN_FRACTION").unwrap_or(0.1);
+
+    /// Whether the GTP interface should pass back when the opponent passes, if
+    /// the position is settled and the engine is winning.
+    pub static ref FRIENDLY_PASS: bool = has_opt("--friendly-pass");
+
+    /// The winrate that both the value of the neural network, and the best move
+    /// of the search, must fall below before the engine resigns.
+    pub static ref RESIGN_THRESHOLD: f32 = get_env("RESIGN_THRESHOLD").unwrap_or(0.05);
+
+    /// What the engine looks at when deciding whether to resign, one of
+    /// `value`, `score`, or `both`.
+    pub static ref RESIGN_CRITERION: ResignMetric = match env::var("RESIGN_CRITERION") {
+        Ok(value) => ResignMetric::from_str(&value).unwrap_or_else(|_| {
+            eprintln!("Unknown RESIGN_CRITERION `{}`, expected one of `value`, `score`, or `both`", value);
+            process::exit(1)
+        }),
+        Err(_) => ResignMetric::Value
+    };
+
+    /// The estimated score margin (including komi) that the engine must fall
+    /// below before it resigns, when resigning based on the score.
+    pub static ref RESIGN_SCORE_THRESHOLD: f32 = get_env("RESIGN_SCORE_THRESHOLD").unwrap_or(-20.0);
+
+    /// The minimum number of visits the best move must have for the search to
+    /// be trusted when deciding whether to resign.
+    pub static ref RESIGN_MIN_VISITS: i32 = get_env("RESIGN_MIN_VISITS").unwrap_or(100);
+
+    /// The number of consecutive moves that must agree that the game is lost
+    /// before the engine resigns.
+    pub static ref RESIGN_CONSECUTIVE: usize = get_env("RESIGN_CONSECUTIVE").unwrap_or(2);
+
+    /// Whether to ignore the move history when computing the features of a
+    /// position, so that an isolated position gets the same features as the
+    /// same position reached through a game.
+    pub static ref POSITION_ONLY_FEATURES: bool = get_env("POSITION_ONLY_FEATURES").unwrap_or(false);
+
+    /// The number of times to retry the evaluation of the root of a search tree
+    /// before falling back to a uniform prior.
+    pub static ref FORWARD_RETRIES: usize = get_env("FORWARD_RETRIES").unwrap_or(2);
+
     /// The number of milliseconds to never let the total game game fall below.
     ///
     /// Safe time is intended to compensate for lag or other uncontrollable factors
@@ -132,10 +217,79 @@ lazy_static! {
     /// The target number of rollouts for each search tree.
     pub static ref NUM_ROLLOUT: RolloutLimit = get_opt("--num-rollout").unwrap_or(RolloutLimit::Default(1600));
 
+    /// The maximum number of nodes in each search tree. Once a search tree has
+    /// this many nodes the search stops expanding new nodes, and instead only
+    /// updates the statistics of the existing ones. Each node uses about 2 kB
+    /// of memory, and there is one search tree for every game that is played
+    /// in parallel.
+    pub static ref MAX_NODES: usize = get_opt("--max-nodes").unwrap_or(500_000);
+
     /// The maximum batch size to forward to the neural network. A larger batch
     /// size typically result in a faster program but requires more GPU memory.
     pub static ref BATCH_SIZE: usize = get_opt("--batch-size").unwrap_or(16);
 
+    /// The number of independent sets of GPU streams in each workspace, which
+    /// is the number of batches that `forward_batches` keeps in flight at the
+    /// same time. Batches that are smaller than `BATCH_SIZE`, i.e. a few
+    /// concurrent small requests, are split into at most this many
+    /// sub-batches so that they can overlap on the GPU. A value of `1`
+    /// disables the split.
+    pub static ref NUM_STREAM_SETS: usize = get_env("NUM_STREAM_SETS").unwrap_or(1);
+
+    /// The maximum number of workspaces that may be in use at the same time on
+    /// each device, or `0` for no limit. Threads that want a workspace beyond
+    /// this limit will block until another thread is done with theirs.
+    pub static ref MAX_WORKSPACES: usize = get_env("MAX_WORKSPACES").unwrap_or(0);
+
+    /// Whether to select the algorithm of each convolution by timing every
+    /// available algorithm on the device (`cudnnFindConvolutionForwardAlgorithm`)
+    /// instead of using the heuristics of cuDNN
+    /// (`cudnnGetConvolutionForwardAlgorithm_v7`). Timing the algorithms can
+    /// find a faster one, but makes the startup slower and the selected
+    /// algorithms nondeterministic.
+    pub static ref CUDNN_FIND_ALGO: bool = get_env("CUDNN_FIND_ALGO").unwrap_or(false);
+
+    /// The fraction of the other workers that are currently probing a search
+    /// tree that an idle worker should expect to add to a partial batch. An idle
+    /// worker does not evaluate a partial batch smaller than this, unless it
+    /// could not find anything to probe itself.
+    pub static ref IDLE_BATCH_FRACTION: f32 = get_env("IDLE_BATCH_FRACTION").unwrap_or(0.5);
+
+    /// The maximum number of positions that the worker pool may have probed
+    /// but not yet inserted into their search tree, or `0` for no limit. Once
+    /// this many positions are queued, batched, or being evaluated the workers
+    /// stop probing for new positions until some of them have been drained.
+    pub static ref MAX_QUEUE_DEPTH: usize = get_env("MAX_QUEUE_DEPTH").unwrap_or(0);
+
+    /// The number of consecutive conflicts a worker may encounter when probing
+    /// before it starts backing off.
+    pub static ref MAX_CONFLICTS: usize = get_env("MAX_CONFLICTS").unwrap_or(16);
+
+    /// The number of times the same position may occur during a self-play
+    /// game before the game is ended and scored as-is.
+    pub static ref MAX_REPETITIONS: usize = get_env("MAX_REPETITIONS").unwrap_or(3);
+
+    /// The minimum score margin required to declare a winner of a self-play
+    /// game that reached the move cap, games with a smaller margin are
+    /// recorded as a draw.
+    pub static ref CAPPED_SCORE_MARGIN: f32 = get_env("CAPPED_SCORE_MARGIN").unwrap_or(0.0);
+
+    /// The main time (in seconds) of each player to record in self-play
+    /// games, if set then every move is annotated with the time that player
+    /// has left (`BL` and `WL`) on its game clock after subtracting the
+    /// wall-clock time spent on each move.
+    pub static ref SGF_MAIN_TIME: Option<f32> = get_env("SGF_MAIN_TIME");
+
+    /// Whether to annotate each move in self-play games (with `SY`) that was
+    /// played in a symmetric position, where symmetric candidate moves are
+    /// eliminated before the search.
+    pub static ref SGF_SYMMETRY: bool = get_env("SGF_SYMMETRY").unwrap_or(false);
+
+    /// The number of moves at the start of each pair of games played during
+    /// `play_match` that are sampled from the policy, so that the games do not
+    /// all follow the same line.
+    pub static ref MATCH_OPENING_MOVES: usize = get_env("MATCH_OPENING_MOVES").unwrap_or(8);
+
     /// The maximum number of games to play in parallel during `SelfPlay`,
     /// `PolicyPlay`, and `Extract` (with expert iteration).
     pub static ref NUM_GAMES: usize = get_opt("--num-games")
@@ -148,6 +302,16 @@ lazy_static! {
     /// increase the `NUM_GAMES` variable instead as that scaled much better.
     pub static ref NUM_THREADS: usize = get_opt("--num-threads").unwrap_or(16);
 
+    /// The maximum number of network evaluations to keep in the evaluation
+    /// cache before the least recently used one is evicted. There is one cache
+    /// per predictor, which during `SelfPlay` is shared by every game played in
+    /// parallel.
+    pub static ref CACHE_SIZE: usize = get_opt("--cache-size").unwrap_or(200_000);
+
+    /// The file to load the evaluation cache from at startup, and to save it
+    /// to before exiting, so that it stays warm across restarts.
+    pub static ref CACHE_FILE: Option<String> = get_opt("--cache-file");
+
     /// The number of samples to extract from each game record.
     pub static ref NUM_SAMPLES: SamplingStrategy = get_opt("--num-samples")
         .unwrap_or(SamplingStrategy::Percent(0.01));
@@ -171,16 +335,54 @@ lazy_static! {
     pub static ref TEMPERATURE: f32 = get_env("TEMPERATURE")
         .unwrap_or_else(|| if *PROCEDURE == Procedure::Gtp { 0.3 } else { 0.8 });
 
+    /// The initial temperature of the move selection during self-play games
+    /// that are played using only the policy, i.e. without any rollouts.
+    pub static ref POLICY_TEMPERATURE: f32 = get_env("POLICY_TEMPERATURE")
+        .unwrap_or(*TEMPERATURE);
+
+    /// The factor that `POLICY_TEMPERATURE` is multiplied with after each move,
+    /// a value of `1.0` keeps the temperature constant for the entire game.
+    pub static ref POLICY_TEMPERATURE_DECAY: f32 = get_env("POLICY_TEMPERATURE_DECAY")
+        .unwrap_or(1.0);
+
+    /// The smallest temperature that `POLICY_TEMPERATURE_DECAY` may anneal the
+    /// policy temperature to.
+    pub static ref POLICY_TEMPERATURE_MIN: f32 = get_env("POLICY_TEMPERATURE_MIN")
+        .unwrap_or(0.2);
+
     /// The softmax temperature to use at the end of the _policy head_. This
     /// temperature is applied for the entire game.
     pub static ref SOFTMAX_TEMPERATURE: f32 = get_env("SOFTMAX_TEMPERATURE")
         .unwrap_or(0.709888);
 
+    /// The temperature of the visit distribution that is written as the
+    /// policy training target during self-play. A value of `1.0` writes
+    /// the raw visit proportions.
+    pub static ref TARGET_TEMPERATURE: f32 = get_env("TARGET_TEMPERATURE")
+        .unwrap_or(1.0);
+
     /// The _First Play Urgency_ reduction. Setting this is `1.0`, or `0.0`
     /// effectively disables FPU.
     pub static ref FPU_REDUCE: Vec<(i32, f32)> = get_intp_list("FPU_REDUCE")
         .unwrap_or_else(|| vec! [(0, 0.631571), (800, 0.431547), (1600, 0.656083), (3200, 0.429231), (6400, 0.514494)]);
 
+    /// The scale of the minimum number of visits each candidate move at the
+    /// root must receive, see `SearchOptions::min_exploration_visits`.
+    pub static ref MIN_EXPLORATION_VISITS: f32 = get_env("MIN_EXPLORATION_VISITS").unwrap_or(0.0);
+
+    /// The minimum number of visits of the most visited candidate move at the
+    /// root, see `SearchOptions::min_visits_for_selection`.
+    pub static ref MIN_SELECTION_VISITS: i32 = get_env("MIN_SELECTION_VISITS").unwrap_or(0);
+
+    /// The weight of the score margin in the value of each candidate move at
+    /// the root when it is clearly won, see `SearchOptions::score_utility_weight`.
+    pub static ref SCORE_UTILITY_WEIGHT: f32 = get_env("SCORE_UTILITY_WEIGHT").unwrap_or(0.0);
+
+    /// Whether the scoring playouts should never play a first line move that
+    /// neither captures any stones nor connects to an existing stone, see
+    /// `ScoringSearch::with_forbid_first_line`.
+    pub static ref FORBID_FIRST_LINE: bool = get_env("FORBID_FIRST_LINE").unwrap_or(false);
+
     /// The number of virtual losses to add during async probes into the monte
     /// carlo search tree. A higher value avoids multiple probes exploring the
     /// same search tree.
@@ -195,20 +397,108 @@ lazy_static! {
         .unwrap_or_else(|| vec! [(0, 1.91753), (800, 1.86478), (1600, 1.86943), (3200, 2.20033), (6400, 1.78053)]);
 }
 
+/// Returns the given configuration value as its name, its value as displayed
+/// in the description, its value as displayed in the dump, and whether it is
+/// part of the description that is given to GTP clients.
+///
+/// # Arguments
+///
+/// * `name` -
+/// * `value` -
+/// * `is_described` -
+///
+fn entry<T: fmt::Display + fmt::Debug>(name: &'static str, value: &T, is_described: bool) -> (&'static str, String, String, bool) {
+    (name, format!("{}", value), format!("{:?}", value), is_described)
+}
+
+/// Returns the given configuration value the same way as `entry`, but for a
+/// value that can only be displayed using its `Debug` implementation.
+///
+/// # Arguments
+///
+/// * `name` -
+/// * `value` -
+/// * `is_described` -
+///
+fn debug_entry<T: fmt::Debug>(name: &'static str, value: &T, is_described: bool) -> (&'static str, String, String, bool) {
+    (name, format!("{:?}", value), format!("{:?}", value), is_described)
+}
+
+/// Returns every configuration value of this engine, see `entry`.
+fn get_entries() -> Vec<(&'static str, String, String, bool)> {
+    vec! [
+        entry("NO_PONDER", &*NO_PONDER, false),
+        entry("TROMP_TAYLOR", &*TROMP_TAYLOR, true),
+        entry("NO_RESIGN", &*NO_RESIGN, true),
+        entry("SELF_PLAY_RESIGN", &*SELF_PLAY_RESIGN, true),
+        entry("SELF_PLAY_NO_RESIGN_FRACTION", &*SELF_PLAY_NO_RESIGN_FRACTION, true),
+        entry("FRIENDLY_PASS", &*FRIENDLY_PASS, true),
+        entry("RESIGN_THRESHOLD", &*RESIGN_THRESHOLD, true),
+        entry("RESIGN_CRITERION", &*RESIGN_CRITERION, true),
+        entry("RESIGN_SCORE_THRESHOLD", &*RESIGN_SCORE_THRESHOLD, true),
+        entry("RESIGN_MIN_VISITS", &*RESIGN_MIN_VISITS, true),
+        entry("RESIGN_CONSECUTIVE", &*RESIGN_CONSECUTIVE, true),
+        entry("FORWARD_RETRIES", &*FORWARD_RETRIES, true),
+        entry("POSITION_ONLY_FEATURES", &*POSITION_ONLY_FEATURES, true),
+        entry("SAFE_TIME_MS", &*SAFE_TIME_MS, false),
+        debug_entry("NUM_EX_IT_ROLLOUT", &*NUM_EX_IT_ROLLOUT, false),
+        debug_entry("NUM_ROLLOUT", &*NUM_ROLLOUT, true),
+        entry("MAX_NODES", &*MAX_NODES, true),
+        entry("BATCH_SIZE", &*BATCH_SIZE, false),
+        entry("NUM_STREAM_SETS", &*NUM_STREAM_SETS, true),
+        entry("MAX_WORKSPACES", &*MAX_WORKSPACES, true),
+        entry("CUDNN_FIND_ALGO", &*CUDNN_FIND_ALGO, false),
+        entry("IDLE_BATCH_FRACTION", &*IDLE_BATCH_FRACTION, true),
+        entry("MAX_QUEUE_DEPTH", &*MAX_QUEUE_DEPTH, true),
+        entry("MAX_CONFLICTS", &*MAX_CONFLICTS, true),
+        entry("MAX_REPETITIONS", &*MAX_REPETITIONS, true),
+        entry("CAPPED_SCORE_MARGIN", &*CAPPED_SCORE_MARGIN, true),
+        debug_entry("SGF_MAIN_TIME", &*SGF_MAIN_TIME, true),
+        entry("SGF_SYMMETRY", &*SGF_SYMMETRY, true),
+        entry("MATCH_OPENING_MOVES", &*MATCH_OPENING_MOVES, true),
+        entry("NUM_GAMES", &*NUM_GAMES, false),
+        entry("NUM_THREADS", &*NUM_THREADS, false),
+        entry("CACHE_SIZE", &*CACHE_SIZE, false),
+        debug_entry("CACHE_FILE", &*CACHE_FILE, false),
+        debug_entry("NUM_SAMPLES", &*NUM_SAMPLES, false),
+        entry("VERBOSE", &*VERBOSE, false),
+        entry("NUM_POLICY_ROLLOUT", &*NUM_POLICY_ROLLOUT, false),
+        entry("DIRICHLET_NOISE", &*DIRICHLET_NOISE, true),
+        entry("TEMPERATURE", &*TEMPERATURE, true),
+        entry("POLICY_TEMPERATURE", &*POLICY_TEMPERATURE, true),
+        entry("POLICY_TEMPERATURE_DECAY", &*POLICY_TEMPERATURE_DECAY, true),
+        entry("POLICY_TEMPERATURE_MIN", &*POLICY_TEMPERATURE_MIN, true),
+        entry("SOFTMAX_TEMPERATURE", &*SOFTMAX_TEMPERATURE, true),
+        entry("TARGET_TEMPERATURE", &*TARGET_TEMPERATURE, true),
+        debug_entry("FPU_REDUCE", &*FPU_REDUCE, true),
+        entry("MIN_EXPLORATION_VISITS", &*MIN_EXPLORATION_VISITS, true),
+        entry("MIN_SELECTION_VISITS", &*MIN_SELECTION_VISITS, true),
+        entry("SCORE_UTILITY_WEIGHT", &*SCORE_UTILITY_WEIGHT, true),
+        entry("FORBID_FIRST_LINE", &*FORBID_FIRST_LINE, true),
+        entry("VLOSS_CNT", &*VLOSS_CNT, true),
+        debug_entry("UCT_EXP", &*UCT_EXP, true),
+        debug_entry("CRITICAL_VALUE", &*CRITICAL_VALUE, true)
+    ]
+}
+
 /// Returns a description of the configurations for this engine.
 pub fn get_description() -> String {
-    vec! [
-        format!("TROMP_TAYLOR {}", *TROMP_TAYLOR),
-        format!("NO_RESIGN {}", *NO_RESIGN),
-        format!("NUM_ROLLOUT {:?}", *NUM_ROLLOUT),
-        format!("DIRICHLET_NOISE {}", *DIRICHLET_NOISE),
-        format!("TEMPERATURE {}", *TEMPERATURE),
-        format!("SOFTMAX_TEMPERATURE {}", *SOFTMAX_TEMPERATURE),
-        format!("VLOSS_CNT {}", *VLOSS_CNT),
-        format!("FPU_REDUCE {:?}", *FPU_REDUCE),
-        format!("UCT_EXP {:?}", *UCT_EXP),
-        format!("CRITICAL_VALUE {:?}", *CRITICAL_VALUE)
-    ].join("\n")
+    get_entries().into_iter()
+        .filter(|&(_, _, _, is_described)| is_described)
+        .map(|(name, value, _, _)| format!("{} {}", name, value))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Returns every effective configuration value of this engine, one per line,
+/// whether it was given explicitly or is a default value. This is intended to
+/// be attached to bug reports, so that the exact configuration can be
+/// reproduced.
+pub fn dump() -> String {
+    get_entries().into_iter()
+        .map(|(name, _, value, _)| format!("{} {}", name, value))
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
 fn args() -> ::std::vec::IntoIter<String> {
@@ -275,12 +565,20 @@ pub fn get_intp_list(name: &str) -> Option<Vec<(i32, f32)>> {
     })
 }
 
+/// The named arguments whose value is not a number, so it can not be told apart
+/// from an unnamed argument by `get_args` on its own.
+const PATH_OPTS: [&str; 1] = ["--cache-file"];
+
 /// Returns all unnamed arguments given to this program.
 pub fn get_args() -> Vec<String> {
     let mut rest = vec! [];
 
     for (i, arg) in args().enumerate().skip(1) {
-        if !arg.starts_with("--") && usize::from_str(&arg).is_err() {
+        let is_opt_value = args().nth(i - 1).map(|prev| PATH_OPTS.contains(&prev.as_str())).unwrap_or(false);
+
+        if is_opt_value {
+            continue
+        } else if !arg.starts_with("--") && usize::from_str(&arg).is_err() {
             rest.push(arg);
         } else if arg == "--" {
             for arg in args().skip(i + 1) {
@@ -349,6 +647,45 @@ pub fn get_lcb_critical_value(visits: i32) -> f32 {
 mod tests {
     use super::*;
 
+    #[test]
+    fn dump_contains_every_value() {
+        let dump = dump();
+
+        assert!(dump.lines().any(|line| line == format!("BATCH_SIZE {}", *BATCH_SIZE)), "{}", dump);
+        assert!(dump.lines().any(|line| line == format!("NUM_THREADS {}", *NUM_THREADS)), "{}", dump);
+        assert!(dump.lines().any(|line| line.starts_with("NUM_ROLLOUT ")), "{}", dump);
+        assert!(dump.lines().any(|line| line.starts_with("CRITICAL_VALUE ")), "{}", dump);
+    }
+
+    #[test]
+    fn description_is_part_of_dump() {
+        let dump = dump();
+
+        for line in get_description().lines() {
+            let name = line.split_whitespace().next();
+
+            assert!(dump.lines().any(|other| other.split_whitespace().next() == name), "{}", line);
+        }
+    }
+
+    #[test]
+    fn description_uses_display() {
+        let description = get_description();
+
+        assert!(description.lines().any(|line| line == format!("RESIGN_CRITERION {}", *RESIGN_CRITERION)), "{}", description);
+        assert!(description.lines().any(|line| line == format!("UCT_EXP {:?}", *UCT_EXP)), "{}", description);
+        assert!(dump().lines().any(|line| line == format!("RESIGN_CRITERION {:?}", *RESIGN_CRITERION)));
+    }
+
+    #[test]
+    fn parse_resign_metric() {
+        assert_eq!(ResignMetric::from_str("value"), Ok(ResignMetric::Value));
+        assert_eq!(ResignMetric::from_str("score"), Ok(ResignMetric::Score));
+        assert_eq!(ResignMetric::from_str(" both "), Ok(ResignMetric::Both));
+        assert_eq!(ResignMetric::from_str("winrate"), Err(()));
+        assert_eq!(ResignMetric::Both.to_string(), "both");
+    }
+
     #[test]
     fn intp_out_of_bounds_1() {
         assert_eq!(get_intp_value(&vec! [(0, 0.0), (100, 1.0)], -100), 0.0);
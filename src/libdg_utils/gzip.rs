@@ -0,0 +1,132 @@
+// Copyright 2021 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use flate2::bufread::MultiGzDecoder;
+use flate2::Crc;
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+
+/// The two magic bytes at the start of every gzip stream.
+const MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Returns the CRC-32 checksum of the given bytes.
+///
+/// # Arguments
+///
+/// * `bytes` -
+///
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = Crc::new();
+    crc.update(bytes);
+    crc.sum()
+}
+
+/// Returns true if the given bytes starts with the gzip magic bytes.
+///
+/// # Arguments
+///
+/// * `bytes` -
+///
+pub fn is_gzip(bytes: &[u8]) -> bool {
+    bytes.starts_with(&MAGIC)
+}
+
+/// Returns the decompressed content of the given gzip stream. If the stream
+/// contains multiple members then their content is concatenated.
+///
+/// # Arguments
+///
+/// * `bytes` - the gzip stream to decompress
+///
+pub fn decompress(bytes: &[u8]) -> io::Result<Vec<u8>> {
+    let mut output = vec! [];
+
+    MultiGzDecoder::new(bytes).read_to_end(&mut output)?;
+    Ok(output)
+}
+
+/// Returns a reader for the file at the given path, that is transparently
+/// decompressed if the file is gzip compressed. Compressed files are
+/// decompressed incrementally as they are read.
+///
+/// # Arguments
+///
+/// * `path` - the path to the file to open
+///
+pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Box<dyn BufRead + Send>> {
+    let mut reader = BufReader::new(File::open(path)?);
+
+    if is_gzip(reader.fill_buf()?) {
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader))))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXED: [u8; 51] = [
+        31, 139, 8, 0, 0, 0, 0, 0, 2, 3, 211, 176, 118, 247, 141, 54, 140, 117, 115, 139,
+        54, 137, 13, 142, 138, 54, 180, 140, 181, 118, 138, 46, 72, 137, 181, 14, 143, 78,
+        41, 136, 213, 4, 0, 26, 219, 107, 15, 31, 0, 0, 0
+    ];
+
+    #[test]
+    fn magic() {
+        assert!(is_gzip(&FIXED));
+        assert!(!is_gzip(b"(;GM[1])"));
+    }
+
+    #[test]
+    fn fixed_block() {
+        assert_eq!(decompress(&FIXED).unwrap(), b"(;GM[1]FF[4]SZ[19];B[pd];W[dp])".to_vec());
+    }
+
+    #[test]
+    fn stored_block() {
+        let mut bytes = vec! [31, 139, 8, 0, 0, 0, 0, 0, 0, 255, 1, 8, 0, 247, 255];
+        bytes.extend_from_slice(b"(;GM[1])");
+        bytes.extend_from_slice(&crc32(b"(;GM[1])").to_le_bytes());
+        bytes.extend_from_slice(&8u32.to_le_bytes());
+
+        assert_eq!(decompress(&bytes).unwrap(), b"(;GM[1])".to_vec());
+    }
+
+    #[test]
+    fn checksum() {
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+
+        let mut bytes = FIXED.to_vec();
+        bytes[43] ^= 1;
+
+        assert!(decompress(&bytes).is_err());
+    }
+
+    #[test]
+    fn concatenated_members() {
+        let mut bytes = FIXED.to_vec();
+        bytes.extend_from_slice(&FIXED);
+
+        assert_eq!(decompress(&bytes).unwrap(), b"(;GM[1]FF[4]SZ[19];B[pd];W[dp])(;GM[1]FF[4]SZ[19];B[pd];W[dp])".to_vec());
+    }
+
+    #[test]
+    fn truncated() {
+        assert!(decompress(&FIXED[..30]).is_err());
+    }
+}
@@ -15,6 +15,7 @@
 #![feature(test)]
 
 extern crate dashmap;
+extern crate flate2;
 #[macro_use] extern crate lazy_static;
 extern crate regex;
 #[cfg(test)] extern crate test;
@@ -22,6 +23,7 @@ extern crate memchr;
 
 pub mod b85;
 pub mod config;
+pub mod gzip;
 pub mod json;
 pub mod lcb;
 pub mod types;